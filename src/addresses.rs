@@ -0,0 +1,126 @@
+use crate::{
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotify, CharacteristicNotifyMethod, CharacteristicRead, ReqError,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use std::{collections::BTreeMap, time::Duration};
+use systemstat::{IpAddr, Platform, System};
+use tokio::time;
+
+/// How often addresses are checked for changes, e.g. a new DHCP lease
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// IPv4 and IPv6 addresses per interface, loopback left out.
+fn addresses() -> std::io::Result<BTreeMap<String, Vec<String>>> {
+    let networks = System::new().networks()?;
+    Ok(networks
+        .into_values()
+        .filter(|network| network.name != "lo")
+        .map(|network| {
+            let addrs = network
+                .addrs
+                .iter()
+                .filter_map(|addr| match addr.addr {
+                    IpAddr::V4(ip) => Some(ip.to_string()),
+                    IpAddr::V6(ip) => Some(ip.to_string()),
+                    IpAddr::Empty | IpAddr::Unsupported => None,
+                })
+                .collect();
+            (network.name, addrs)
+        })
+        .collect())
+}
+
+/// Addresses encoded as a CBOR map from interface name to address list.
+fn encode(addresses: &BTreeMap<String, Vec<String>>) -> Vec<u8> {
+    let mut payload = Vec::new();
+    ciborium::into_writer(addresses, &mut payload).expect("string map is encodable");
+    payload
+}
+
+async fn serve(control: CharacteristicControl, retry: RetryPolicy) {
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut sequence = NotifySequence::new("IP addresses");
+    // Last addresses sent, so only changes are notified
+    let mut last_sent: Option<BTreeMap<String, Vec<String>>> = None;
+    let mut interval = time::interval(POLL_INTERVAL);
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting IP addresses notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "IP addresses");
+                        writer_opt = Some(notifier);
+                        // A new subscriber gets the current addresses right away
+                        last_sent = None;
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            _ = interval.tick() => {
+                let Some(writer) = &mut writer_opt else {
+                    continue;
+                };
+                match addresses() {
+                    Ok(current) if last_sent.as_ref() != Some(&current) => {
+                        if retry.notify(writer, &sequence.stamp(encode(&current))).await.is_err() {
+                            writer_opt = None;
+                        } else {
+                            last_sent = Some(current);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(err) => eprintln!("Reading IP addresses failed: {err}"),
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `IP_ADDRESSES` characteristic.
+///
+/// Reads return the IPv4 and IPv6 addresses of every interface as a CBOR
+/// map, e.g. `{"wlan0": ["192.168.1.23", "fe80::1"]}`. Subscribers are
+/// notified whenever the addresses change.
+pub fn characteristic(retry: &RetryPolicies) -> Characteristic {
+    let (control, control_handle) = characteristic_control();
+    crate::tasks::spawn("ip-addresses", serve(control, retry.get("ip_addresses")));
+
+    Characteristic {
+        uuid: crate::IP_ADDRESSES,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(|_req| {
+                let result = addresses()
+                    .map(|addresses| encode(&addresses))
+                    .map_err(|err| {
+                        eprintln!("Reading IP addresses failed: {err}");
+                        ReqError::Failed
+                    });
+                async move { result }.boxed()
+            }),
+            ..Default::default()
+        }),
+        notify: Some(CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Io,
+            ..Default::default()
+        }),
+        control_handle,
+        ..Default::default()
+    }
+}
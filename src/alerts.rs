@@ -0,0 +1,98 @@
+use crate::{encoding::Encode, payload::NotifySequence};
+use bluer::gatt::local::{
+    Characteristic, CharacteristicNotifier, CharacteristicNotify, CharacteristicNotifyMethod,
+};
+use futures::FutureExt;
+use std::{collections::VecDeque, sync::OnceLock};
+use tokio::sync::mpsc;
+
+/// Alerts kept until a central confirms them
+const QUEUE_LEN: usize = 10;
+
+/// Remaining disk life dropped below the threshold, followed by the `u8` wear level
+pub const DISK_WEAR: u8 = 0x01;
+/// CPU temperature exceeded the threshold, followed by the `f32` °C
+pub const OVER_TEMPERATURE: u8 = 0x02;
+/// The firmware detected undervoltage, followed by the `u32` `get_throttled` bitfield
+pub const UNDERVOLTAGE: u8 = 0x03;
+
+static PUBLISHER: OnceLock<mpsc::UnboundedSender<Vec<u8>>> = OnceLock::new();
+
+/// Indicates alert `code` followed by `value` on `ALERTS`, right away or once
+/// a central subscribes.
+pub fn raise(code: u8, value: impl Encode) {
+    let mut alert = vec![code];
+    alert.extend(value.encode());
+    if let Some(publisher) = PUBLISHER.get() {
+        let _ = publisher.send(alert);
+    }
+}
+
+async fn serve(
+    mut notifiers: mpsc::Receiver<CharacteristicNotifier>,
+    mut alerts: mpsc::UnboundedReceiver<Vec<u8>>,
+) {
+    let mut notifier_opt: Option<CharacteristicNotifier> = None;
+    let mut pending: VecDeque<Vec<u8>> = VecDeque::with_capacity(QUEUE_LEN);
+    let mut sequence = NotifySequence::new("alerts");
+
+    loop {
+        tokio::select! {
+            Some(notifier) = notifiers.recv() => notifier_opt = Some(notifier),
+            Some(alert) = alerts.recv() => {
+                println!("Alert: {alert:02x?}");
+                if pending.len() == QUEUE_LEN {
+                    pending.pop_front();
+                }
+                pending.push_back(alert);
+            },
+            else => break,
+        }
+
+        // Indications resolve once the central confirms, unconfirmed alerts are kept
+        while let (Some(notifier), Some(alert)) = (&mut notifier_opt, pending.front()) {
+            if !crate::security::admits_indications().await {
+                break;
+            }
+            match notifier.notify(sequence.stamp(alert)).await {
+                Ok(()) => {
+                    pending.pop_front();
+                }
+                Err(err) => {
+                    eprintln!("Alert not confirmed, keeping it for the next subscriber: {err}");
+                    notifier_opt = None;
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `ALERTS` characteristic.
+///
+/// Critical events are indicated as an alert code, [DISK_WEAR],
+/// [OVER_TEMPERATURE] or [UNDERVOLTAGE], followed by the value that raised
+/// it. An alert is only dropped after the central confirmed its indication;
+/// up to ten are kept until then, also while an unpaired central keeps
+/// [admits_indications](crate::security::admits_indications) from sending.
+pub fn characteristic() -> Characteristic {
+    let (notifier_tx, notifier_rx) = mpsc::channel(1);
+    let (alert_tx, alert_rx) = mpsc::unbounded_channel();
+    let _ = PUBLISHER.set(alert_tx);
+    crate::tasks::spawn("alerts", serve(notifier_rx, alert_rx));
+
+    Characteristic {
+        uuid: crate::ALERTS,
+        notify: Some(CharacteristicNotify {
+            indicate: true,
+            method: CharacteristicNotifyMethod::Fun(Box::new(move |notifier| {
+                let notifier_tx = notifier_tx.clone();
+                async move {
+                    let _ = notifier_tx.send(notifier).await;
+                }
+                .boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
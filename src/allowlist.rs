@@ -0,0 +1,97 @@
+use bluer::{
+    gatt::local::{Application, CharacteristicWriteMethod, ReqError},
+    Adapter, AdapterEvent, Address, DeviceEvent, DeviceProperty,
+};
+use futures::{pin_mut, stream::FuturesUnordered, FutureExt, StreamExt};
+use std::{collections::HashSet, sync::Arc};
+
+/// Disconnects `address` whenever it connects.
+async fn reject(adapter: Adapter, address: Address) {
+    let device = match adapter.device(address) {
+        Ok(device) => device,
+        Err(err) => {
+            eprintln!("Cannot watch central {address}: {err}");
+            return;
+        }
+    };
+    if device.is_connected().await.unwrap_or(false) {
+        println!("Dropping connection of unknown central {address}");
+        let _ = device.disconnect().await;
+    }
+    let device_events = match device.events().await {
+        Ok(device_events) => device_events,
+        Err(err) => {
+            eprintln!("Cannot watch central {address}: {err}");
+            return;
+        }
+    };
+    pin_mut!(device_events);
+    while let Some(event) = device_events.next().await {
+        if let DeviceEvent::PropertyChanged(DeviceProperty::Connected(true)) = event {
+            println!("Dropping connection of unknown central {address}");
+            if let Err(err) = device.disconnect().await {
+                eprintln!("Disconnecting {address} failed: {err}");
+            }
+        }
+    }
+}
+
+/// Watches every device BlueZ knows of and drops the connections of those
+/// not in `allowed`.
+pub async fn enforce(adapter: Adapter, allowed: Arc<HashSet<Address>>) {
+    let adapter_events = match adapter.events().await {
+        Ok(adapter_events) => adapter_events,
+        Err(err) => {
+            eprintln!("Cannot enforce central allowlist: {err}");
+            return;
+        }
+    };
+    let known = adapter.device_addresses().await.unwrap_or_default();
+    let added = adapter_events.filter_map(|event| async move {
+        match event {
+            AdapterEvent::DeviceAdded(address) => Some(address),
+            _ => None,
+        }
+    });
+    let addresses = futures::stream::iter(known).chain(added);
+    pin_mut!(addresses);
+
+    // Watched within this task, BlueZ may know of many devices
+    let mut watches = FuturesUnordered::new();
+    loop {
+        tokio::select! {
+            Some(address) = addresses.next() => {
+                if !allowed.contains(&address) {
+                    watches.push(reject(adapter.clone(), address));
+                }
+            },
+            Some(()) = watches.next() => {},
+            else => break,
+        }
+    }
+}
+
+/// Makes every characteristic in `app` reject writes from centrals not in `allowed`.
+///
+/// Covers writes in the short window before [enforce] drops a connection.
+pub fn restrict_writes(app: &mut Application, allowed: Arc<HashSet<Address>>) {
+    for service in &mut app.services {
+        for characteristic in &mut service.characteristics {
+            let Some(write) = &mut characteristic.write else {
+                continue;
+            };
+            if let CharacteristicWriteMethod::Fun(inner) = &mut write.method {
+                let inner = std::mem::replace(inner, Box::new(|_, _| unreachable!()));
+                let allowed = allowed.clone();
+                write.method = CharacteristicWriteMethod::Fun(Box::new(move |value, req| {
+                    if allowed.contains(&req.device_address) {
+                        inner(value, req)
+                    } else {
+                        println!("Ignoring write from unknown central {}", req.device_address);
+                        async { Err(ReqError::NotAuthorized) }.boxed()
+                    }
+                }));
+            }
+        }
+    }
+}
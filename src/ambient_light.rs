@@ -0,0 +1,127 @@
+use crate::retry::RetryPolicies;
+use bluer::gatt::local::{
+    characteristic_control, Characteristic, CharacteristicNotify, CharacteristicNotifyMethod,
+};
+use rppal::i2c::I2c;
+use std::time::Duration;
+
+/// BH1750 address with ADDR pin low
+const BH1750_ADDRESS: u16 = 0x23;
+const BH1750_POWER_ON: u8 = 0x01;
+const BH1750_CONTINUOUS_HIGH_RES: u8 = 0x10;
+
+/// TSL2561 address with ADDR pin floating
+const TSL2561_ADDRESS: u16 = 0x39;
+const TSL2561_COMMAND: u8 = 0x80;
+const TSL2561_WORD: u8 = 0x20;
+const TSL2561_CONTROL: u8 = 0x00;
+const TSL2561_TIMING: u8 = 0x01;
+const TSL2561_ID: u8 = 0x0A;
+const TSL2561_DATA0: u8 = 0x0C;
+const TSL2561_DATA1: u8 = 0x0E;
+const TSL2561_POWER_ON: u8 = 0x03;
+/// 16x gain and 402 ms integration, the reference setting of the lux formula
+const TSL2561_GAIN_16X_402MS: u8 = 0x12;
+
+enum LightSensor {
+    Bh1750(I2c),
+    Tsl2561(I2c),
+}
+
+impl LightSensor {
+    /// Probes the BH1750 first, then the TSL2561.
+    fn detect() -> rppal::i2c::Result<Option<LightSensor>> {
+        let mut i2c = I2c::new()?;
+
+        i2c.set_slave_address(BH1750_ADDRESS)?;
+        if i2c.smbus_send_byte(BH1750_POWER_ON).is_ok() {
+            i2c.smbus_send_byte(BH1750_CONTINUOUS_HIGH_RES)?;
+            return Ok(Some(LightSensor::Bh1750(i2c)));
+        }
+
+        i2c.set_slave_address(TSL2561_ADDRESS)?;
+        let is_tsl2561 = i2c
+            .smbus_read_byte(TSL2561_COMMAND | TSL2561_ID)
+            .is_ok_and(|id| id & 0xF0 == 0x50);
+        if is_tsl2561 {
+            i2c.smbus_write_byte(TSL2561_COMMAND | TSL2561_CONTROL, TSL2561_POWER_ON)?;
+            i2c.smbus_write_byte(TSL2561_COMMAND | TSL2561_TIMING, TSL2561_GAIN_16X_402MS)?;
+            return Ok(Some(LightSensor::Tsl2561(i2c)));
+        }
+
+        Ok(None)
+    }
+
+    fn read_lux(&mut self) -> rppal::i2c::Result<f32> {
+        match self {
+            LightSensor::Bh1750(i2c) => {
+                let mut raw = [0u8; 2];
+                i2c.read(&mut raw)?;
+                Ok(u16::from_be_bytes(raw) as f32 / 1.2)
+            }
+            LightSensor::Tsl2561(i2c) => {
+                let cmd = TSL2561_COMMAND | TSL2561_WORD;
+                let ch0 = i2c.smbus_read_word(cmd | TSL2561_DATA0)? as f32;
+                let ch1 = i2c.smbus_read_word(cmd | TSL2561_DATA1)? as f32;
+                Ok(tsl2561_lux(ch0, ch1))
+            }
+        }
+    }
+}
+
+/// Lux approximation from the TSL2561 datasheet (T, FN and CL packages).
+fn tsl2561_lux(ch0: f32, ch1: f32) -> f32 {
+    if ch0 == 0.0 {
+        return 0.0;
+    }
+    let ratio = ch1 / ch0;
+    let lux = match ratio {
+        r if r <= 0.50 => 0.0304 * ch0 - 0.062 * ch0 * r.powf(1.4),
+        r if r <= 0.61 => 0.0224 * ch0 - 0.031 * ch1,
+        r if r <= 0.80 => 0.0128 * ch0 - 0.0153 * ch1,
+        r if r <= 1.30 => 0.00146 * ch0 - 0.00112 * ch1,
+        _ => 0.0,
+    };
+    lux.max(0.0)
+}
+
+/// Creates the `AMBIENT_LIGHT_LUX` characteristic and starts sampling the sensor.
+pub fn characteristic(retry: &RetryPolicies) -> Characteristic {
+    let (control, control_handle) = characteristic_control();
+    let mut sensor: Option<LightSensor> = None;
+
+    crate::tasks::spawn(
+        "ambient-light",
+        crate::periodic::notify(
+            control,
+            "ambient light",
+            Duration::from_secs(1),
+            retry.get("ambient_light_lux"),
+            move || {
+                if sensor.is_none() {
+                    sensor = LightSensor::detect().ok().flatten();
+                }
+                match sensor.as_mut()?.read_lux() {
+                    Ok(lux) => Some((lux.round() as u32).to_le_bytes().to_vec()),
+                    Err(err) => {
+                        // Detect again on the next tick, the sensor may have been replugged
+                        eprintln!("Reading ambient light sensor failed: {err}");
+                        sensor = None;
+                        None
+                    }
+                }
+            },
+        ),
+    );
+
+    Characteristic {
+        uuid: crate::AMBIENT_LIGHT_LUX,
+        notify: Some(CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Io,
+            ..Default::default()
+        }),
+        control_handle,
+        ..Default::default()
+    }
+}
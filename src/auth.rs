@@ -0,0 +1,348 @@
+use bluer::{
+    gatt::local::{Application, CharacteristicWriteMethod, ReqError},
+    Adapter, AdapterEvent, Address, Device, DeviceEvent, DeviceProperty,
+};
+use futures::{pin_mut, stream::FuturesUnordered, FutureExt, Stream, StreamExt};
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time;
+
+/// Bytes of a challenge
+const CHALLENGE_LEN: usize = 16;
+
+const AUTH: &str = "AUTH";
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok())
+        .collect()
+}
+
+#[derive(Default)]
+struct State {
+    /// Challenge sent to each central, valid for one answer
+    challenges: HashMap<Address, [u8; CHALLENGE_LEN]>,
+    /// Centrals that answered their challenge, until they disconnect
+    authenticated: HashSet<Address>,
+}
+
+/// Challenge-response handshake on `WRITE_REQUEST_RESPONSE` with a token
+/// shared between server and app.
+///
+/// A central writes `AUTH` and is notified `CHALLENGE <hex>` with 16 random
+/// bytes. It then writes `AUTH <hex>` with the HMAC-SHA256 of the challenge
+/// bytes keyed by the token, and is notified `AUTH OK` or `AUTH FAILED`.
+/// The token itself never goes over the air.
+///
+/// Authentications last until [forget_disconnected] sees the central go.
+pub struct Gate {
+    key: hmac::Key,
+    random: SystemRandom,
+    state: Mutex<State>,
+}
+
+impl Gate {
+    pub fn new(token: &str) -> Self {
+        Gate {
+            key: hmac::Key::new(hmac::HMAC_SHA256, token.as_bytes()),
+            random: SystemRandom::new(),
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn is_authenticated(&self, device: Address) -> bool {
+        self.state.lock().unwrap().authenticated.contains(&device)
+    }
+
+    /// Drops the authentication and any pending challenge of `device`.
+    fn forget(&self, device: Address) {
+        let mut state = self.state.lock().unwrap();
+        state.challenges.remove(&device);
+        state.authenticated.remove(&device);
+    }
+
+    /// Handles a handshake message from `device` and returns the reply to
+    /// notify, or `None` when `value` is not part of the handshake.
+    pub fn handle(&self, device: Address, value: &[u8]) -> Option<Vec<u8>> {
+        let message = std::str::from_utf8(value).ok()?.trim();
+        let (word, answer) = match message.split_once(' ') {
+            Some((word, answer)) => (word, Some(answer.trim())),
+            None => (message, None),
+        };
+        if !word.eq_ignore_ascii_case(AUTH) {
+            return None;
+        }
+        let reply = match answer {
+            None => self.challenge(device),
+            Some(answer) => self.verify(device, answer),
+        };
+        Some(reply.into_bytes())
+    }
+
+    fn challenge(&self, device: Address) -> String {
+        let mut challenge = [0u8; CHALLENGE_LEN];
+        if self.random.fill(&mut challenge).is_err() {
+            return "AUTH FAILED".to_string();
+        }
+        self.state
+            .lock()
+            .unwrap()
+            .challenges
+            .insert(device, challenge);
+        format!("CHALLENGE {}", to_hex(&challenge))
+    }
+
+    fn verify(&self, device: Address, answer: &str) -> String {
+        let challenge = self.state.lock().unwrap().challenges.remove(&device);
+        let verified = match (challenge, from_hex(answer)) {
+            (Some(challenge), Some(tag)) => hmac::verify(&self.key, &challenge, &tag).is_ok(),
+            _ => false,
+        };
+        if !verified {
+            println!("Authentication of {device} failed");
+            return "AUTH FAILED".to_string();
+        }
+        println!("Authenticated {device}");
+        self.state.lock().unwrap().authenticated.insert(device);
+        "AUTH OK".to_string()
+    }
+}
+
+/// Makes every characteristic in `app` but `WRITE_REQUEST_RESPONSE`, which
+/// runs the handshake itself, reject writes from centrals `gate` has not
+/// authenticated.
+pub fn restrict_writes(app: &mut Application, gate: Arc<Gate>) {
+    for service in &mut app.services {
+        for characteristic in &mut service.characteristics {
+            if characteristic.uuid == crate::WRITE_REQUEST_RESPONSE {
+                continue;
+            }
+            let Some(write) = &mut characteristic.write else {
+                continue;
+            };
+            if let CharacteristicWriteMethod::Fun(inner) = &mut write.method {
+                let inner = std::mem::replace(inner, Box::new(|_, _| unreachable!()));
+                let gate = gate.clone();
+                write.method = CharacteristicWriteMethod::Fun(Box::new(move |value, req| {
+                    if gate.is_authenticated(req.device_address) {
+                        inner(value, req)
+                    } else {
+                        println!(
+                            "Ignoring write from unauthenticated central {}",
+                            req.device_address
+                        );
+                        async { Err(ReqError::NotAuthorized) }.boxed()
+                    }
+                }));
+            }
+        }
+    }
+}
+
+/// Addresses of every device BlueZ knows of, then of each one it adds.
+async fn devices(adapter: &Adapter) -> bluer::Result<impl Stream<Item = Address>> {
+    let adapter_events = adapter.events().await?;
+    let known = adapter.device_addresses().await.unwrap_or_default();
+    let added = adapter_events.filter_map(|event| async move {
+        match event {
+            AdapterEvent::DeviceAdded(address) => Some(address),
+            _ => None,
+        }
+    });
+    Ok(futures::stream::iter(known).chain(added))
+}
+
+/// Watches every device BlueZ knows of and disconnects centrals that have not
+/// authenticated with `gate` within `timeout` of connecting.
+pub async fn enforce_timeout(adapter: Adapter, gate: Arc<Gate>, timeout: Duration) {
+    let addresses = match devices(&adapter).await {
+        Ok(addresses) => addresses,
+        Err(err) => {
+            eprintln!("Cannot enforce the authentication timeout: {err}");
+            return;
+        }
+    };
+    pin_mut!(addresses);
+
+    // Watched within this task, BlueZ may know of many devices
+    let mut watches = FuturesUnordered::new();
+    loop {
+        tokio::select! {
+            Some(address) = addresses.next() => {
+                watches.push(expire(adapter.clone(), address, gate.clone(), timeout));
+            },
+            Some(()) = watches.next() => {},
+            else => break,
+        }
+    }
+}
+
+/// Disconnects `address` whenever it stays unauthenticated for `timeout` after connecting.
+async fn expire(adapter: Adapter, address: Address, gate: Arc<Gate>, timeout: Duration) {
+    let device = match adapter.device(address) {
+        Ok(device) => device,
+        Err(err) => {
+            eprintln!("Cannot watch central {address}: {err}");
+            return;
+        }
+    };
+    let device_events = match device.events().await {
+        Ok(device_events) => device_events,
+        Err(err) => {
+            eprintln!("Cannot watch central {address}: {err}");
+            return;
+        }
+    };
+    if device.is_connected().await.unwrap_or(false) {
+        disconnect_unauthenticated(&device, &gate, timeout).await;
+    }
+    pin_mut!(device_events);
+    while let Some(event) = device_events.next().await {
+        if let DeviceEvent::PropertyChanged(DeviceProperty::Connected(true)) = event {
+            disconnect_unauthenticated(&device, &gate, timeout).await;
+        }
+    }
+}
+
+async fn disconnect_unauthenticated(device: &Device, gate: &Gate, timeout: Duration) {
+    time::sleep(timeout).await;
+    let address = device.address();
+    if !device.is_connected().await.unwrap_or(false) || gate.is_authenticated(address) {
+        return;
+    }
+    println!(
+        "Disconnecting {address}: not authenticated within {}s",
+        timeout.as_secs()
+    );
+    if let Err(err) = device.disconnect().await {
+        eprintln!("Disconnecting {address} failed: {err}");
+    }
+}
+
+/// Watches every device BlueZ knows of and drops the authentication of
+/// centrals with `gate` when they disconnect.
+pub async fn forget_disconnected(adapter: Adapter, gate: Arc<Gate>) {
+    let addresses = match devices(&adapter).await {
+        Ok(addresses) => addresses,
+        Err(err) => {
+            eprintln!("Cannot watch centrals, authentications last until restart: {err}");
+            return;
+        }
+    };
+    pin_mut!(addresses);
+
+    let mut watches = FuturesUnordered::new();
+    loop {
+        tokio::select! {
+            Some(address) = addresses.next() => {
+                watches.push(forget_on_disconnect(adapter.clone(), address, gate.clone()));
+            },
+            Some(()) = watches.next() => {},
+            else => break,
+        }
+    }
+}
+
+/// Drops the authentication of `address` whenever its connection drops.
+async fn forget_on_disconnect(adapter: Adapter, address: Address, gate: Arc<Gate>) {
+    let device_events = match adapter.device(address) {
+        Ok(device) => device.events().await,
+        Err(err) => Err(err),
+    };
+    let device_events = match device_events {
+        Ok(device_events) => device_events,
+        Err(err) => {
+            eprintln!("Cannot watch central {address}: {err}");
+            return;
+        }
+    };
+    pin_mut!(device_events);
+    while let Some(event) = device_events.next().await {
+        if let DeviceEvent::PropertyChanged(DeviceProperty::Connected(false)) = event {
+            gate.forget(address);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "secret";
+    const CENTRAL: Address = Address::new([1, 2, 3, 4, 5, 6]);
+    const OTHER: Address = Address::new([6, 5, 4, 3, 2, 1]);
+
+    /// Requests a challenge for `device` and returns the answer `token` gives.
+    fn answer(gate: &Gate, device: Address, token: &str) -> Vec<u8> {
+        let reply = String::from_utf8(gate.handle(device, b"AUTH").unwrap()).unwrap();
+        let challenge = from_hex(reply.strip_prefix("CHALLENGE ").unwrap()).unwrap();
+        let key = hmac::Key::new(hmac::HMAC_SHA256, token.as_bytes());
+        let tag = hmac::sign(&key, &challenge);
+        format!("AUTH {}", to_hex(tag.as_ref())).into_bytes()
+    }
+
+    #[test]
+    fn authenticates_the_right_mac() {
+        let gate = Gate::new(TOKEN);
+        let answer = answer(&gate, CENTRAL, TOKEN);
+        assert_eq!(gate.handle(CENTRAL, &answer).unwrap(), b"AUTH OK");
+        assert!(gate.is_authenticated(CENTRAL));
+        assert!(!gate.is_authenticated(OTHER));
+    }
+
+    #[test]
+    fn rejects_a_wrong_mac() {
+        let gate = Gate::new(TOKEN);
+        let answer = answer(&gate, CENTRAL, "guess");
+        assert_eq!(gate.handle(CENTRAL, &answer).unwrap(), b"AUTH FAILED");
+        assert!(!gate.is_authenticated(CENTRAL));
+        assert_eq!(gate.handle(CENTRAL, b"AUTH zz").unwrap(), b"AUTH FAILED");
+    }
+
+    #[test]
+    fn rejects_a_replayed_challenge() {
+        let gate = Gate::new(TOKEN);
+        let answer = answer(&gate, CENTRAL, TOKEN);
+        // Only the central that was challenged can answer
+        assert_eq!(gate.handle(OTHER, &answer).unwrap(), b"AUTH FAILED");
+        assert_eq!(gate.handle(CENTRAL, &answer).unwrap(), b"AUTH OK");
+        gate.forget(CENTRAL);
+        // Each challenge is valid for one answer
+        assert_eq!(gate.handle(CENTRAL, &answer).unwrap(), b"AUTH FAILED");
+        assert!(!gate.is_authenticated(CENTRAL));
+    }
+
+    #[test]
+    fn leaves_other_writes_unauthenticated() {
+        let gate = Gate::new(TOKEN);
+        assert_eq!(gate.handle(CENTRAL, b"reboot"), None);
+        assert_eq!(gate.handle(CENTRAL, b"AUTHORIZE"), None);
+        assert!(!gate.is_authenticated(CENTRAL));
+        let answer = answer(&gate, OTHER, TOKEN);
+        gate.handle(OTHER, &answer);
+        assert!(!gate.is_authenticated(CENTRAL));
+    }
+
+    #[test]
+    fn forgets_disconnected_centrals() {
+        let gate = Gate::new(TOKEN);
+        let answer = answer(&gate, CENTRAL, TOKEN);
+        gate.handle(CENTRAL, &answer);
+        gate.forget(CENTRAL);
+        assert!(!gate.is_authenticated(CENTRAL));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::retry::RetryPolicy;
+use bluer::{
+    gatt::{
+        local::{
+            characteristic_control, Characteristic, CharacteristicControl,
+            CharacteristicControlEvent, CharacteristicNotify, CharacteristicNotifyMethod,
+            CharacteristicRead, ReqError, Service,
+        },
+        CharacteristicWriter,
+    },
+    id,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use rppal::i2c::I2c;
+use std::{
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::time;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Stored level while no gauge has been read yet
+const UNKNOWN: u8 = u8::MAX;
+
+/// MAX17040/MAX17043 fuel gauge
+const MAX17040_ADDRESS: u16 = 0x36;
+/// State of charge, high byte in % and low byte in 1/256 %
+const MAX17040_SOC: u8 = 0x04;
+
+/// INA219 current monitor, as used by the Waveshare UPS HATs
+const INA219_ADDRESS: u16 = 0x42;
+/// Bus voltage in bits 15..3, 4 mV per bit
+const INA219_BUS_VOLTAGE: u8 = 0x02;
+
+/// Single Li-ion cell, empty and full
+const CELL_EMPTY_VOLTS: f32 = 3.0;
+const CELL_FULL_VOLTS: f32 = 4.2;
+
+enum FuelGauge {
+    Max17040(I2c),
+    /// Without a gauge, the charge is estimated from the cell voltage
+    Ina219(I2c),
+}
+
+/// Reads a big endian SMBus word; SMBus itself transfers the low byte first.
+fn read_word_be(i2c: &I2c, register: u8) -> rppal::i2c::Result<u16> {
+    i2c.smbus_read_word(register).map(u16::swap_bytes)
+}
+
+impl FuelGauge {
+    /// Probes the MAX17040 first, then the INA219.
+    fn detect() -> rppal::i2c::Result<Option<FuelGauge>> {
+        let mut i2c = I2c::new()?;
+
+        i2c.set_slave_address(MAX17040_ADDRESS)?;
+        if read_word_be(&i2c, MAX17040_SOC).is_ok() {
+            return Ok(Some(FuelGauge::Max17040(i2c)));
+        }
+
+        i2c.set_slave_address(INA219_ADDRESS)?;
+        if read_word_be(&i2c, INA219_BUS_VOLTAGE).is_ok() {
+            return Ok(Some(FuelGauge::Ina219(i2c)));
+        }
+
+        Ok(None)
+    }
+
+    /// State of charge in %, 0 to 100.
+    fn read_level(&mut self) -> rppal::i2c::Result<u8> {
+        let percent = match self {
+            FuelGauge::Max17040(i2c) => read_word_be(i2c, MAX17040_SOC)? as f32 / 256.0,
+            FuelGauge::Ina219(i2c) => {
+                let volts = (read_word_be(i2c, INA219_BUS_VOLTAGE)? >> 3) as f32 * 0.004;
+                (volts - CELL_EMPTY_VOLTS) / (CELL_FULL_VOLTS - CELL_EMPTY_VOLTS) * 100.0
+            }
+        };
+        Ok(percent.clamp(0.0, 100.0).round() as u8)
+    }
+}
+
+async fn serve(control: CharacteristicControl, level: Arc<AtomicU8>, retry: RetryPolicy) {
+    let mut gauge: Option<FuelGauge> = None;
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut interval = time::interval(SAMPLE_INTERVAL);
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting battery level notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "battery level");
+                        writer_opt = Some(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            _ = interval.tick() => {
+                if gauge.is_none() {
+                    gauge = FuelGauge::detect().ok().flatten();
+                }
+                let Some(sensor) = gauge.as_mut() else {
+                    continue;
+                };
+                let percent = match sensor.read_level() {
+                    Ok(percent) => percent,
+                    Err(err) => {
+                        // Detect again on the next tick, the HAT may have been replugged
+                        eprintln!("Reading battery level failed: {err}");
+                        gauge = None;
+                        continue;
+                    }
+                };
+                let previous = level.swap(percent, Ordering::Relaxed);
+                if previous == percent {
+                    continue;
+                }
+                // Battery Level is a plain uint8, without the sequence header of custom characteristics
+                if let Some(writer) = &mut writer_opt {
+                    if retry.write_all(writer, &[percent]).await.is_err() {
+                        writer_opt = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the Bluetooth SIG Battery Service (0x180F) for a UPS HAT.
+///
+/// Battery Level is read from a MAX17040 fuel gauge, or estimated from the
+/// cell voltage measured by an INA219, and notified when it changes.
+pub fn service(retry: RetryPolicy) -> Service {
+    let (control, control_handle) = characteristic_control();
+    let level = Arc::new(AtomicU8::new(UNKNOWN));
+    crate::tasks::spawn("battery", serve(control, level.clone(), retry));
+
+    Service {
+        uuid: id::Service::BatteryService.into(),
+        primary: true,
+        characteristics: vec![Characteristic {
+            uuid: id::Characteristic::BatteryLevel.into(),
+            read: Some(CharacteristicRead {
+                read: true,
+                fun: Box::new(move |_req| {
+                    let result = match level.load(Ordering::Relaxed) {
+                        UNKNOWN => Err(ReqError::Failed),
+                        percent => Ok(vec![percent]),
+                    };
+                    async move { result }.boxed()
+                }),
+                ..Default::default()
+            }),
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle,
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
@@ -0,0 +1,89 @@
+use crate::response::Responder;
+use bluer::{
+    gatt::local::{Characteristic, CharacteristicWrite, CharacteristicWriteMethod, ReqError},
+    Adapter, AdapterEvent, Address,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use std::time::Duration;
+use tokio::time;
+
+/// How long to scan for the remote device
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(15);
+
+const PAIRED: u8 = 0x00;
+const NOT_FOUND: u8 = 0x01;
+const PAIRING_FAILED: u8 = 0x02;
+
+/// Scans until the device with `address` is seen or the timeout expires.
+async fn discover(adapter: &Adapter, address: Address) -> bluer::Result<bool> {
+    if adapter.device_addresses().await?.contains(&address) {
+        return Ok(true);
+    }
+    let events = adapter.discover_devices_with_changes().await?;
+    pin_mut!(events);
+    let found = time::timeout(DISCOVERY_TIMEOUT, async {
+        while let Some(event) = events.next().await {
+            if matches!(event, AdapterEvent::DeviceAdded(added) if added == address) {
+                return true;
+            }
+        }
+        false
+    })
+    .await;
+    Ok(found.unwrap_or(false))
+}
+
+async fn pair(adapter: &Adapter, address: Address) -> u8 {
+    match discover(adapter, address).await {
+        Ok(true) => {}
+        Ok(false) => return NOT_FOUND,
+        Err(err) => {
+            eprintln!("Discovery for {address} failed: {err}");
+            return NOT_FOUND;
+        }
+    }
+    let result = async {
+        let device = adapter.device(address)?;
+        if !device.is_paired().await? {
+            device.pair().await?;
+        }
+        bluer::Result::Ok(())
+    }
+    .await;
+    match result {
+        Ok(()) => PAIRED,
+        Err(err) => {
+            eprintln!("Pairing with {address} failed: {err}");
+            PAIRING_FAILED
+        }
+    }
+}
+
+/// Creates the `BT_PAIR_REMOTE` characteristic.
+///
+/// Accepts a 6-byte device address and reports the pairing outcome on
+/// `WRITE_REQUEST_RESPONSE`.
+pub fn characteristic(adapter: Adapter, responder: Responder) -> Characteristic {
+    Characteristic {
+        uuid: crate::BT_PAIR_REMOTE,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                let Ok(address) = <[u8; 6]>::try_from(value) else {
+                    return async move { Err(ReqError::InvalidValueLength) }.boxed();
+                };
+                let address = Address::new(address);
+                let adapter = adapter.clone();
+                let responder = responder.clone();
+                crate::tasks::spawn(&format!("pair-{address}"), async move {
+                    println!("Pairing with remote device {address}");
+                    let status = pair(&adapter, address).await;
+                    let _ = responder.send(vec![status]).await;
+                });
+                async move { Ok(()) }.boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
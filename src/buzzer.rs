@@ -0,0 +1,64 @@
+use bluer::gatt::local::{
+    Characteristic, CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+};
+use futures::FutureExt;
+use rppal::gpio::{Gpio, OutputPin};
+use std::time::Duration;
+use tokio::{sync::mpsc, time};
+
+/// Alerts waiting to be played
+const QUEUE_LEN: usize = 10;
+
+/// Highest frequency in units of 100 Hz
+const MAX_FREQUENCY: u8 = 40;
+
+struct Alert {
+    frequency_hz: f64,
+    duration: Duration,
+}
+
+/// Plays queued alerts one after another.
+async fn play(mut pin: OutputPin, mut alerts: mpsc::Receiver<Alert>) {
+    while let Some(alert) = alerts.recv().await {
+        if let Err(err) = pin.set_pwm_frequency(alert.frequency_hz, 0.5) {
+            eprintln!("Could not drive buzzer: {err}");
+            continue;
+        }
+        time::sleep(alert.duration).await;
+        if let Err(err) = pin.clear_pwm() {
+            eprintln!("Could not silence buzzer: {err}");
+        }
+    }
+}
+
+/// Creates the `SPEAKER_ALERT` characteristic for a buzzer on GPIO `pin`.
+///
+/// Writes are two bytes: frequency in 100 Hz and duration in 100 ms units.
+pub fn characteristic(pin: u8) -> rppal::gpio::Result<Characteristic> {
+    let pin = Gpio::new()?.get(pin)?.into_output_low();
+    let (alert_tx, alert_rx) = mpsc::channel(QUEUE_LEN);
+    crate::tasks::spawn("buzzer", play(pin, alert_rx));
+
+    Ok(Characteristic {
+        uuid: crate::SPEAKER_ALERT,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                let result = match value[..] {
+                    [frequency @ 1..=MAX_FREQUENCY, duration @ 1..=u8::MAX] => alert_tx
+                        .try_send(Alert {
+                            frequency_hz: frequency as f64 * 100.0,
+                            duration: Duration::from_millis(duration as u64 * 100),
+                        })
+                        // Queue is full
+                        .map_err(|_| ReqError::InProgress),
+                    [_, _] => Err(ReqError::NotSupported),
+                    _ => Err(ReqError::InvalidValueLength),
+                };
+                async move { result }.boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,234 @@
+use crate::{
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::{
+    gatt::{
+        local::{
+            characteristic_control, Characteristic, CharacteristicControl,
+            CharacteristicControlEvent, CharacteristicNotify, CharacteristicNotifyMethod,
+            CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+        },
+        CharacteristicWriter,
+    },
+    Address,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tokio::{fs, io::AsyncWriteExt, sync::mpsc};
+use x509_parser::pem::Pem;
+
+const CERT_PATH: &str = "/etc/ble-raspi/tls/server.pem";
+
+const MORE_DATA: u8 = 0x01;
+const DONE: u8 = 0x00;
+
+/// `CERT_STATUS` values, the first byte of each notification
+const INSTALLED: u8 = 0x00;
+const INVALID_CHAIN: u8 = 0x01;
+const INSTALL_FAILED: u8 = 0x02;
+
+/// Upper bound for a reassembled chain
+const MAX_CHAIN_LEN: usize = 16 * 1024;
+
+/// Frames received so far for the chain one central is transferring
+#[derive(Default)]
+struct Reassembly {
+    next_sequence: u16,
+    pem: Vec<u8>,
+}
+
+impl Reassembly {
+    /// Adds a `u16` LE sequence number, flag, data frame and returns the
+    /// complete chain after the `DONE` frame. Sequence 0 starts a new chain.
+    fn push(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, ReqError> {
+        let [s0, s1, flag, ref data @ ..] = frame[..] else {
+            return Err(ReqError::InvalidValueLength);
+        };
+        let sequence = u16::from_le_bytes([s0, s1]);
+        if sequence == 0 {
+            *self = Reassembly::default();
+        } else if sequence != self.next_sequence {
+            *self = Reassembly::default();
+            return Err(ReqError::InvalidOffset);
+        }
+        if self.pem.len() + data.len() > MAX_CHAIN_LEN {
+            *self = Reassembly::default();
+            return Err(ReqError::InvalidValueLength);
+        }
+        self.pem.extend_from_slice(data);
+        self.next_sequence = sequence.wrapping_add(1);
+        match flag {
+            MORE_DATA => Ok(None),
+            DONE => Ok(Some(std::mem::take(&mut self.pem))),
+            _ => Err(ReqError::NotSupported),
+        }
+    }
+}
+
+/// Checks that every certificate is currently valid and signed by the next
+/// one in the chain. Returns the leaf's expiry as Unix timestamp.
+fn validate(chain: &[u8]) -> Result<u32, String> {
+    let pems = Pem::iter_from_buffer(chain)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("invalid PEM: {err}"))?;
+    let certificates = pems
+        .iter()
+        .map(|pem| pem.parse_x509())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("invalid certificate: {err}"))?;
+    let leaf = certificates.first().ok_or("no certificate in chain")?;
+
+    for (index, certificate) in certificates.iter().enumerate() {
+        if !certificate.validity().is_valid() {
+            return Err(format!("certificate {index} is not valid now"));
+        }
+        if let Some(issuer) = certificates.get(index + 1) {
+            if certificate.issuer() != issuer.subject() {
+                return Err(format!("certificate {index} is not issued by the next one"));
+            }
+            certificate
+                .verify_signature(Some(issuer.public_key()))
+                .map_err(|err| format!("certificate {index} has a bad signature: {err}"))?;
+        }
+    }
+    Ok(leaf
+        .validity()
+        .not_after
+        .timestamp()
+        .clamp(0, u32::MAX as i64) as u32)
+}
+
+/// Replaces the certificate file via a temporary file and rename.
+async fn install(chain: &[u8]) -> std::io::Result<()> {
+    let path = Path::new(CERT_PATH);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    let temporary = path.with_extension("pem.tmp");
+    let mut file = fs::File::create(&temporary).await?;
+    file.write_all(chain).await?;
+    file.sync_all().await?;
+    fs::rename(&temporary, path).await
+}
+
+async fn serve(
+    control: CharacteristicControl,
+    mut chains: mpsc::Receiver<Vec<u8>>,
+    retry: RetryPolicy,
+) {
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut sequence = NotifySequence::new("certificate status");
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting certificate status notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "certificate status");
+                        writer_opt = Some(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Some(chain) = chains.recv() => {
+                let status = match validate(&chain) {
+                    Ok(expiry) => match install(&chain).await {
+                        Ok(()) => {
+                            println!("Installed certificate expiring at {expiry}");
+                            let mut status = vec![INSTALLED];
+                            status.extend(expiry.to_le_bytes());
+                            status
+                        }
+                        Err(err) => {
+                            eprintln!("Could not install certificate to {CERT_PATH}: {err}");
+                            vec![INSTALL_FAILED]
+                        }
+                    },
+                    Err(err) => {
+                        eprintln!("Rejecting certificate chain: {err}");
+                        vec![INVALID_CHAIN]
+                    }
+                };
+                if let Some(writer) = &mut writer_opt {
+                    if retry.notify(writer, &sequence.stamp(&status)).await.is_err() {
+                        writer_opt = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `CERT_WRITE` and `CERT_STATUS` characteristics.
+///
+/// A PEM chain is written in frames of a `u16` LE sequence number starting
+/// at 0, a `MORE_DATA` or `DONE` flag and data; each central transfers its own
+/// chain. The outcome of each complete chain is notified on `CERT_STATUS`:
+/// `0x00` and the leaf expiry as `u32` LE once installed, `0x01` for an
+/// invalid chain or `0x02` when it could not be installed.
+pub fn characteristics(retry: &RetryPolicies) -> Vec<Characteristic> {
+    let reassemblies: Arc<Mutex<HashMap<Address, Reassembly>>> = Arc::default();
+    let (chain_tx, chain_rx) = mpsc::channel(1);
+    let (control, control_handle) = characteristic_control();
+    crate::tasks::spawn(
+        "cert-provisioning",
+        serve(control, chain_rx, retry.get("cert_status")),
+    );
+
+    vec![
+        // Framed PEM certificate chain
+        Characteristic {
+            uuid: crate::CERT_WRITE,
+            write: Some(CharacteristicWrite {
+                write: true,
+                encrypt_authenticated_write: true,
+                method: CharacteristicWriteMethod::Fun(Box::new(move |value, req| {
+                    let result = {
+                        let mut reassemblies = reassemblies.lock().unwrap();
+                        let result = reassemblies
+                            .entry(req.device_address)
+                            .or_default()
+                            .push(&value);
+                        // Finished or failed transfers start over with sequence 0
+                        if !matches!(result, Ok(None)) {
+                            reassemblies.remove(&req.device_address);
+                        }
+                        result
+                    };
+                    let result = match result {
+                        Ok(Some(chain)) => {
+                            chain_tx.try_send(chain).map_err(|_| ReqError::InProgress)
+                        }
+                        Ok(None) => Ok(()),
+                        Err(err) => Err(err),
+                    };
+                    async move { result }.boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        // Expiry of the installed certificate
+        Characteristic {
+            uuid: crate::CERT_STATUS,
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle,
+            ..Default::default()
+        },
+    ]
+}
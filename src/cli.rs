@@ -0,0 +1,121 @@
+use crate::{security::Security, transport::Transport};
+use clap::Parser;
+use std::path::PathBuf;
+
+/// BLE GATT server exposing Raspberry Pi system metrics
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Configuration file, e.g. with `[characteristic_aliases]`
+    #[arg(long, default_value = "/etc/ble-raspi/config.toml")]
+    pub config: PathBuf,
+
+    /// JSON file keeping state across restarts, e.g. the per-peer statistics
+    #[arg(long, default_value = "/var/lib/ble-raspi/state.json")]
+    pub state_file: PathBuf,
+
+    /// Set the timezone from a GeoIP lookup on startup and after Wi-Fi changes
+    #[arg(long)]
+    pub auto_timezone: bool,
+
+    /// Bluetooth adapter to serve on, e.g. `hci1`; the default adapter otherwise
+    #[arg(long)]
+    pub adapter: Option<String>,
+
+    /// Advertised local name, overriding `device_name` from the config file; `{hostname}` is expanded
+    #[arg(long)]
+    pub local_name: Option<String>,
+
+    /// Seconds between metric updates, unless set per metric in the config file
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub update_interval: u64,
+
+    /// Log level, overriding `RUST_LOG`
+    #[arg(long)]
+    pub log_level: Option<log::LevelFilter>,
+
+    /// Instance number, placed in the UUIDs so several servers can share an adapter
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=15))]
+    pub instance_id: u8,
+
+    /// Bluetooth transport to serve GATT over
+    #[arg(long, value_enum, default_value_t = Transport::Le)]
+    pub transport: Transport,
+
+    /// Only pair with devices that wrote the pairing code logged on startup
+    #[arg(long)]
+    pub require_pairing: bool,
+
+    /// Register a pairing agent printing the passkey of each pairing
+    #[arg(long, conflicts_with = "require_pairing")]
+    pub pairing_agent: bool,
+
+    /// Fixed six digit passkey the pairing agent answers with instead
+    #[arg(long, requires = "pairing_agent", value_parser = clap::value_parser!(u32).range(0..=999_999))]
+    pub passkey: Option<u32>,
+
+    /// Link security required to read or write characteristics
+    #[arg(long, value_enum, default_value_t = Security::Open)]
+    pub security: Security,
+
+    /// Wear level (100 is new) below which a disk replacement alert is indicated
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub disk_wear_alert: u8,
+
+    /// Seconds a central has to authenticate with the config's `control_token`
+    /// before it is disconnected, 0 to never disconnect
+    #[arg(long, default_value_t = 10)]
+    pub auth_timeout_secs: u64,
+
+    /// CPU temperature in °C above which an over-temperature alert is indicated
+    #[arg(long, default_value_t = 80.0)]
+    pub temperature_alert: f32,
+
+    /// Interface whose throughput is reported, `wlan0` or else `eth0` by default
+    #[arg(long)]
+    pub network_interface: Option<String>,
+
+    /// Serve the characteristic for injecting test metric values
+    #[arg(long, alias = "simulate")]
+    pub test_mode: bool,
+
+    /// CO₂ level in ppm above which an alert is indicated
+    #[cfg(feature = "co2-sensor")]
+    #[arg(long, default_value_t = 1000)]
+    pub co2_alert_ppm: u16,
+
+    /// GPIO pin (BCM numbering) of the PIR motion sensor
+    #[cfg(feature = "pir-sensor")]
+    #[arg(long)]
+    pub pir_gpio_pin: Option<u8>,
+
+    /// GPIO pin (BCM numbering) of the door reed switch
+    #[cfg(feature = "door-sensor")]
+    #[arg(long)]
+    pub door_gpio_pin: Option<u8>,
+
+    /// GPIO pin (BCM numbering) driving the alert buzzer
+    #[cfg(feature = "audio-alert")]
+    #[arg(long)]
+    pub buzzer_gpio_pin: Option<u8>,
+
+    /// GPIO pins (BCM numbering) of relays that may be power cycled, comma separated
+    #[cfg(feature = "gpio")]
+    #[arg(long, value_delimiter = ',')]
+    pub power_cycle_gpio_pins: Vec<u8>,
+
+    /// Named pipe bridged to the response characteristic; client writes go to `<PATH>.out`
+    #[cfg(feature = "pipe-bridge")]
+    #[arg(long)]
+    pub pipe_path: Option<PathBuf>,
+}
+
+/// `wlan0` if present, else `eth0`.
+pub fn default_network_interface() -> String {
+    let interface = if std::path::Path::new("/sys/class/net/wlan0").exists() {
+        "wlan0"
+    } else {
+        "eth0"
+    };
+    interface.to_string()
+}
@@ -0,0 +1,288 @@
+use crate::{
+    events::SystemEvent,
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotifier, CharacteristicNotify, CharacteristicNotifyMethod,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use std::time::Duration;
+use tokio::{sync::mpsc, time};
+
+#[cfg(not(any(feature = "co2-scd30", feature = "co2-mhz19")))]
+compile_error!("feature \"co2-sensor\" needs a driver: enable \"co2-scd30\" or \"co2-mhz19\"");
+
+/// SCD30 over I²C
+#[cfg(feature = "co2-scd30")]
+mod scd30 {
+    use rppal::i2c::I2c;
+    use std::{thread, time::Duration};
+
+    const ADDRESS: u16 = 0x61;
+    const CMD_START_CONTINUOUS: u16 = 0x0010;
+    const CMD_DATA_READY: u16 = 0x0202;
+    const CMD_READ_MEASUREMENT: u16 = 0x0300;
+    /// Minimum delay between a command and reading its response
+    const COMMAND_DELAY: Duration = Duration::from_millis(3);
+
+    pub struct Scd30 {
+        i2c: I2c,
+    }
+
+    /// CRC-8 used by Sensirion sensors (polynomial 0x31, init 0xFF).
+    fn crc8(data: &[u8]) -> u8 {
+        let mut crc = 0xFFu8;
+        for byte in data {
+            crc ^= byte;
+            for _ in 0..8 {
+                crc = if crc & 0x80 != 0 {
+                    (crc << 1) ^ 0x31
+                } else {
+                    crc << 1
+                };
+            }
+        }
+        crc
+    }
+
+    impl Scd30 {
+        pub fn open() -> rppal::i2c::Result<Scd30> {
+            let mut i2c = I2c::new()?;
+            i2c.set_slave_address(ADDRESS)?;
+            let mut scd30 = Scd30 { i2c };
+            // Continuous measurement without ambient pressure compensation
+            scd30.command(CMD_START_CONTINUOUS, Some(0))?;
+            Ok(scd30)
+        }
+
+        fn command(&mut self, command: u16, argument: Option<u16>) -> rppal::i2c::Result<()> {
+            let mut frame = command.to_be_bytes().to_vec();
+            if let Some(argument) = argument {
+                let argument = argument.to_be_bytes();
+                frame.extend_from_slice(&argument);
+                frame.push(crc8(&argument));
+            }
+            self.i2c.write(&frame)?;
+            Ok(())
+        }
+
+        /// Reads `N` 16-bit words, dropping the words with a bad checksum.
+        fn read_words<const N: usize>(&mut self) -> rppal::i2c::Result<Option<[[u8; 2]; N]>> {
+            thread::sleep(COMMAND_DELAY);
+            let mut raw = vec![0u8; N * 3];
+            self.i2c.read(&mut raw)?;
+            let mut words = [[0u8; 2]; N];
+            for (word, chunk) in words.iter_mut().zip(raw.chunks(3)) {
+                if crc8(&chunk[..2]) != chunk[2] {
+                    return Ok(None);
+                }
+                *word = [chunk[0], chunk[1]];
+            }
+            Ok(Some(words))
+        }
+
+        /// Returns the latest CO₂ reading, or `None` if no new one is available.
+        pub fn read_ppm(&mut self) -> rppal::i2c::Result<Option<u16>> {
+            self.command(CMD_DATA_READY, None)?;
+            if self.read_words::<1>()? != Some([[0, 1]]) {
+                return Ok(None);
+            }
+            self.command(CMD_READ_MEASUREMENT, None)?;
+            // CO₂, temperature and humidity as big-endian floats
+            let Some(words) = self.read_words::<6>()? else {
+                return Ok(None);
+            };
+            let co2 = f32::from_be_bytes([words[0][0], words[0][1], words[1][0], words[1][1]]);
+            Ok(Some(co2.round() as u16))
+        }
+    }
+}
+
+/// MH-Z19 over UART
+#[cfg(feature = "co2-mhz19")]
+mod mhz19 {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_serial::{SerialPortBuilderExt, SerialStream};
+
+    const PORT: &str = "/dev/serial0";
+    const BAUD_RATE: u32 = 9600;
+    const CMD_READ_CO2: [u8; 9] = [0xFF, 0x01, 0x86, 0x00, 0x00, 0x00, 0x00, 0x00, 0x79];
+
+    pub struct Mhz19 {
+        port: SerialStream,
+    }
+
+    fn checksum(frame: &[u8; 9]) -> u8 {
+        let sum = frame[1..8].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        0xFFu8.wrapping_sub(sum).wrapping_add(1)
+    }
+
+    impl Mhz19 {
+        pub fn open() -> tokio_serial::Result<Mhz19> {
+            let port = tokio_serial::new(PORT, BAUD_RATE).open_native_async()?;
+            Ok(Mhz19 { port })
+        }
+
+        pub async fn read_ppm(&mut self) -> std::io::Result<Option<u16>> {
+            self.port.write_all(&CMD_READ_CO2).await?;
+            let mut response = [0u8; 9];
+            self.port.read_exact(&mut response).await?;
+            if response[0] != 0xFF || response[1] != 0x86 || checksum(&response) != response[8] {
+                return Ok(None);
+            }
+            Ok(Some(u16::from_be_bytes([response[2], response[3]])))
+        }
+    }
+}
+
+enum Co2Sensor {
+    #[cfg(feature = "co2-scd30")]
+    Scd30(scd30::Scd30),
+    #[cfg(feature = "co2-mhz19")]
+    Mhz19(mhz19::Mhz19),
+}
+
+impl Co2Sensor {
+    /// Opens the first available driver, the SCD30 before the MH-Z19.
+    fn open() -> Option<Co2Sensor> {
+        #[cfg(feature = "co2-scd30")]
+        match scd30::Scd30::open() {
+            Ok(sensor) => return Some(Co2Sensor::Scd30(sensor)),
+            Err(err) => eprintln!("SCD30 unavailable: {err}"),
+        }
+        #[cfg(feature = "co2-mhz19")]
+        match mhz19::Mhz19::open() {
+            Ok(sensor) => return Some(Co2Sensor::Mhz19(sensor)),
+            Err(err) => eprintln!("MH-Z19 unavailable: {err}"),
+        }
+        None
+    }
+
+    async fn read_ppm(&mut self) -> std::io::Result<Option<u16>> {
+        match self {
+            #[cfg(feature = "co2-scd30")]
+            Co2Sensor::Scd30(sensor) => sensor.read_ppm().map_err(std::io::Error::other),
+            #[cfg(feature = "co2-mhz19")]
+            Co2Sensor::Mhz19(sensor) => sensor.read_ppm().await,
+        }
+    }
+}
+
+async fn serve(
+    mut sensor: Co2Sensor,
+    alert_ppm: u16,
+    control: CharacteristicControl,
+    mut alert_notifiers: mpsc::Receiver<CharacteristicNotifier>,
+    retry: RetryPolicy,
+) {
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut alert_notifier_opt: Option<CharacteristicNotifier> = None;
+    let mut ppm_sequence = NotifySequence::new("CO2");
+    let mut alert_sequence = NotifySequence::new("CO2 alert");
+    let mut above_threshold = false;
+    let mut interval = time::interval(Duration::from_secs(2));
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting CO2 notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "CO2");
+                        writer_opt = Some(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Some(notifier) = alert_notifiers.recv() => alert_notifier_opt = Some(notifier),
+            _ = interval.tick() => {
+                let ppm = match sensor.read_ppm().await {
+                    Ok(Some(ppm)) => ppm,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        eprintln!("Reading CO2 sensor failed: {err}");
+                        continue;
+                    }
+                };
+                println!("CO2 is: {ppm} ppm");
+
+                if let Some(writer) = &mut writer_opt {
+                    if retry.notify(writer, &ppm_sequence.stamp(&ppm.to_le_bytes()[..])).await.is_err() {
+                        writer_opt = None;
+                    }
+                }
+
+                // Alert once per excursion above the threshold
+                let was_above = above_threshold;
+                above_threshold = ppm > alert_ppm;
+                if above_threshold && !was_above {
+                    eprintln!("CO2 level {ppm} ppm exceeds {alert_ppm} ppm");
+                    crate::events::publish(SystemEvent::ThresholdBreached {
+                        metric: "co2".to_string(),
+                        value: ppm as f32,
+                    });
+                    if let (Some(notifier), true) = (&mut alert_notifier_opt, crate::security::admits_indications().await) {
+                        if notifier.notify(alert_sequence.stamp(&ppm.to_le_bytes()[..])).await.is_err() {
+                            alert_notifier_opt = None;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `CO2_PPM` and `CO2_ALERT` characteristics.
+///
+/// Returns no characteristics when no sensor could be opened.
+pub fn characteristics(alert_ppm: u16, retry: &RetryPolicies) -> Vec<Characteristic> {
+    let Some(sensor) = Co2Sensor::open() else {
+        return Vec::new();
+    };
+    let (control, control_handle) = characteristic_control();
+    let (alert_tx, alert_rx) = mpsc::channel(1);
+    crate::tasks::spawn(
+        "co2",
+        serve(sensor, alert_ppm, control, alert_rx, retry.get("co2_ppm")),
+    );
+
+    vec![
+        // CO₂ concentration
+        Characteristic {
+            uuid: crate::CO2_PPM,
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle,
+            ..Default::default()
+        },
+        // Threshold alert with confirmed delivery
+        Characteristic {
+            uuid: crate::CO2_ALERT,
+            notify: Some(CharacteristicNotify {
+                indicate: true,
+                method: CharacteristicNotifyMethod::Fun(Box::new(move |notifier| {
+                    let alert_tx = alert_tx.clone();
+                    async move {
+                        let _ = alert_tx.send(notifier).await;
+                    }
+                    .boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    ]
+}
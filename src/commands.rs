@@ -0,0 +1,99 @@
+use bluer::{
+    gatt::local::{Characteristic, CharacteristicRead, ReqError},
+    Address,
+};
+use futures::FutureExt;
+use serde::Serialize;
+use std::{collections::VecDeque, sync::Mutex};
+
+/// Shell commands clients may run, by key
+const WHITELIST: [(&str, &str); 7] = [
+    ("uptime", "uptime"),
+    ("disk", "df -h"),
+    ("memory", "free -m"),
+    ("processes", "ps aux --sort=-%cpu | head -n 10"),
+    ("network", "ip -brief address"),
+    ("journal", "journalctl -n 20 --no-pager"),
+    ("throttled", "vcgencmd get_throttled"),
+];
+
+/// Returns the command line for a whitelisted key.
+pub fn lookup(key: &str) -> Option<&'static str> {
+    WHITELIST
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, command)| *command)
+}
+
+/// Commands kept in `COMMAND_HISTORY`
+const HISTORY_LEN: usize = 20;
+
+/// Largest `COMMAND_HISTORY` value, fits one long ATT read
+const MAX_HISTORY_SIZE: usize = 512;
+
+static HISTORY: Mutex<VecDeque<CommandRecord>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Serialize)]
+pub struct CommandRecord {
+    /// Seconds since the Unix epoch when the command finished
+    pub timestamp: u64,
+    pub command_key: String,
+    /// `-1` when the command could not be run or was killed by a signal
+    pub exit_code: i32,
+    pub peer: Address,
+}
+
+/// Adds a command to the history, dropping the oldest beyond [HISTORY_LEN].
+pub fn record(command_key: &str, exit_code: i32, peer: Address) {
+    let mut history = HISTORY.lock().unwrap();
+    if history.len() == HISTORY_LEN {
+        history.pop_front();
+    }
+    history.push_back(CommandRecord {
+        timestamp: crate::payload::unix_timestamp() as u64,
+        command_key: command_key.to_string(),
+        exit_code,
+        peer,
+    });
+}
+
+pub fn clear_history() {
+    HISTORY.lock().unwrap().clear();
+}
+
+/// Encodes the most recent commands that fit in [MAX_HISTORY_SIZE] as a CBOR array.
+fn encode_history() -> Result<Vec<u8>, ciborium::ser::Error<std::io::Error>> {
+    let history = HISTORY.lock().unwrap();
+    let mut skip = 0;
+    loop {
+        let mut value = Vec::new();
+        ciborium::into_writer(&history.iter().skip(skip).collect::<Vec<_>>(), &mut value)?;
+        if value.len() <= MAX_HISTORY_SIZE || skip == history.len() {
+            return Ok(value);
+        }
+        skip += 1;
+    }
+}
+
+/// Creates the `COMMAND_HISTORY` characteristic.
+///
+/// Reads return the last commands run through `EXEC_STREAM`, oldest first,
+/// as a CBOR array of maps with `timestamp`, `command_key`, `exit_code` and
+/// `peer`. `STATS_RESET` clears it.
+pub fn history_characteristic() -> Characteristic {
+    Characteristic {
+        uuid: crate::COMMAND_HISTORY,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(|_req| {
+                let result = encode_history().map_err(|err| {
+                    eprintln!("Encoding command history failed: {err}");
+                    ReqError::Failed
+                });
+                async move { result }.boxed()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
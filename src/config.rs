@@ -0,0 +1,503 @@
+use crate::retry::{RetryPolicies, RetryPolicy};
+use bluer::{
+    gatt::local::{Application, Characteristic},
+    Address,
+};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::Duration,
+};
+use tokio::time::{self, Interval};
+use uuid::Uuid;
+
+/// Characteristics that can be aliased, by their name in the config file
+const CHARACTERISTICS: &[(&str, Uuid)] = &[
+    ("temperature", crate::TEMPERATURE),
+    ("cpu_load", crate::CPU_LOAD),
+    ("ram_usage", crate::RAM_USAGE),
+    ("uptime", crate::UPTIME),
+    ("disk_usage", crate::DISK_USAGE),
+    ("cpu_core_load", crate::CPU_CORE_LOAD),
+    ("load_average", crate::LOAD_AVERAGE),
+    ("swap_usage", crate::SWAP_USAGE),
+    ("network_throughput", crate::NETWORK_THROUGHPUT),
+    ("gpu_temperature", crate::GPU_TEMPERATURE),
+    ("gpu_clock", crate::GPU_CLOCK),
+    ("throttled", crate::THROTTLED),
+    ("cpu_frequency", crate::CPU_FREQUENCY),
+    ("wifi_status", crate::WIFI_STATUS),
+    ("ip_addresses", crate::IP_ADDRESSES),
+    ("system_info", crate::SYSTEM_INFO),
+    ("process_count", crate::PROCESS_COUNT),
+    ("top_processes", crate::TOP_PROCESSES),
+    ("metrics_snapshot", crate::METRICS_SNAPSHOT),
+    ("snapshot_format", crate::SNAPSHOT_FORMAT),
+    ("profile_version", crate::PROFILE_VERSION),
+    ("write_request_response", crate::WRITE_REQUEST_RESPONSE),
+    ("disk_health", crate::DISK_HEALTH),
+    ("wifi_scan_trigger", crate::WIFI_SCAN_TRIGGER),
+    ("wifi_scan_results", crate::WIFI_SCAN_RESULTS),
+    ("wifi_connect", crate::WIFI_CONNECT),
+    ("bt_pair_remote", crate::BT_PAIR_REMOTE),
+    ("exec_stream", crate::EXEC_STREAM),
+    ("task_status", crate::TASK_STATUS),
+    ("voltage_history", crate::VOLTAGE_HISTORY),
+    ("degraded_metrics", crate::DEGRADED_METRICS),
+    ("latency_histogram", crate::LATENCY_HISTOGRAM),
+    ("stats_reset", crate::STATS_RESET),
+    ("notify_retries", crate::NOTIFY_RETRIES),
+    ("tick_jitter", crate::TICK_JITTER),
+    ("proto_negotiate", crate::PROTO_NEGOTIATE),
+    ("ts_query", crate::TS_QUERY),
+    ("mtu_changed", crate::MTU_CHANGED),
+    ("qos_priority", crate::QOS_PRIORITY),
+    ("uptime_human", crate::UPTIME_HUMAN),
+    ("sensor_pairing_code", crate::SENSOR_PAIRING_CODE),
+    ("system_locale", crate::SYSTEM_LOCALE),
+    ("disk_wear", crate::DISK_WEAR),
+    ("alerts", crate::ALERTS),
+    ("system_events", crate::SYSTEM_EVENTS),
+    ("peer_disconnect", crate::PEER_DISCONNECT),
+    ("temp_map", crate::TEMP_MAP),
+    ("stress_test", crate::STRESS_TEST),
+    ("hw_random", crate::HW_RANDOM),
+    ("command_history", crate::COMMAND_HISTORY),
+    ("peer_stats", crate::PEER_STATS),
+    ("broadcast", crate::BROADCAST),
+    ("char_lock", crate::CHAR_LOCK),
+    ("char_inject", crate::CHAR_INJECT),
+    ("error_detail", crate::ERROR_DETAIL),
+    #[cfg(feature = "status-led")]
+    ("status_led", crate::STATUS_LED),
+    #[cfg(feature = "status-led")]
+    ("led_pattern", crate::LED_PATTERN),
+    #[cfg(feature = "ambient-sensor")]
+    ("ambient_light_lux", crate::AMBIENT_LIGHT_LUX),
+    #[cfg(feature = "environment-sensor")]
+    ("pressure_pa", crate::PRESSURE_PA),
+    #[cfg(feature = "environment-sensor")]
+    ("humidity_pct", crate::HUMIDITY_PCT),
+    #[cfg(feature = "co2-sensor")]
+    ("co2_ppm", crate::CO2_PPM),
+    #[cfg(feature = "co2-sensor")]
+    ("co2_alert", crate::CO2_ALERT),
+    #[cfg(feature = "pir-sensor")]
+    ("pir_motion", crate::PIR_MOTION),
+    #[cfg(feature = "pir-sensor")]
+    ("pir_motion_count", crate::PIR_MOTION_COUNT),
+    #[cfg(feature = "door-sensor")]
+    ("door_state", crate::DOOR_STATE),
+    #[cfg(feature = "door-sensor")]
+    ("door_open_seconds", crate::DOOR_OPEN_SECONDS),
+    #[cfg(feature = "audio-alert")]
+    ("speaker_alert", crate::SPEAKER_ALERT),
+    #[cfg(feature = "obd2")]
+    ("obd2_pid", crate::OBD2_PID),
+    #[cfg(feature = "gpio")]
+    ("power_cycle", crate::POWER_CYCLE),
+    #[cfg(feature = "posix-mq")]
+    ("mq_notify", crate::MQ_NOTIFY),
+    #[cfg(feature = "posix-mq")]
+    ("mq_publish", crate::MQ_PUBLISH),
+    #[cfg(feature = "traffic-control")]
+    ("tc_limit", crate::TC_LIMIT),
+    #[cfg(feature = "screenshot")]
+    ("screen_capture", crate::SCREEN_CAPTURE),
+    #[cfg(feature = "cert-provisioning")]
+    ("cert_write", crate::CERT_WRITE),
+    #[cfg(feature = "cert-provisioning")]
+    ("cert_status", crate::CERT_STATUS),
+    #[cfg(feature = "cron-bridge")]
+    ("cron_add", crate::CRON_ADD),
+    #[cfg(feature = "cron-bridge")]
+    ("cron_list", crate::CRON_LIST),
+    #[cfg(feature = "cron-bridge")]
+    ("cron_delete", crate::CRON_DELETE),
+    #[cfg(feature = "kmsg")]
+    ("kernel_messages", crate::KERNEL_MESSAGES),
+];
+
+/// Contents of `config.toml`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Advertised local name, `{hostname}` is replaced by the hostname
+    pub device_name: Option<String>,
+
+    /// Service UUID, used as is regardless of `--instance-id`
+    pub service_uuid: Option<Uuid>,
+
+    /// Centrals allowed to connect, e.g. `["AA:BB:CC:DD:EE:FF"]`, any when unset.
+    /// Phones using private addresses are known by their identity address once bonded.
+    pub allowed_centrals: Option<HashSet<Address>>,
+
+    /// Token shared with the app, which then has to authenticate on
+    /// `WRITE_REQUEST_RESPONSE` before its writes are accepted
+    pub control_token: Option<String>,
+
+    /// Replacement UUIDs by characteristic name, e.g. `cpu_load`
+    #[serde(default)]
+    pub characteristic_aliases: HashMap<String, Uuid>,
+
+    /// Settings by characteristic name, e.g. `[metrics.cpu_load]`
+    #[serde(default)]
+    pub metrics: HashMap<String, MetricConfig>,
+
+    /// Initial encoding of `METRICS_SNAPSHOT`, `json`, `cbor` or `protobuf`
+    #[serde(default)]
+    pub snapshot_format: SnapshotFormat,
+
+    /// Notify retries of characteristics without a `retry` of their own
+    #[serde(default)]
+    pub retry: RetryPolicy,
+}
+
+/// Encoding of the aggregated metrics payload
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum SnapshotFormat {
+    #[default]
+    Json = 0x00,
+    /// Compact enough for a snapshot in one notification at small MTUs
+    Cbor = 0x01,
+    /// `MetricFrame` from `proto/metrics.proto`
+    Protobuf = 0x02,
+}
+
+/// Settings of a single characteristic
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricConfig {
+    /// Whether the characteristic is served at all
+    pub enabled: bool,
+
+    /// Seconds between notifications of the metric,
+    /// `--update-interval` by default
+    pub interval_secs: Option<u64>,
+
+    /// Notify retries, e.g. `[metrics.cpu_load.retry]`, `[retry]` by default
+    pub retry: Option<RetryPolicy>,
+}
+
+impl Default for MetricConfig {
+    fn default() -> Self {
+        MetricConfig {
+            enabled: true,
+            interval_secs: None,
+            retry: None,
+        }
+    }
+}
+
+/// Advertised name when neither `--local-name` nor `device_name` is set
+const DEFAULT_DEVICE_NAME: &str = "gatt_echo_server";
+
+/// Expands `{hostname}` in a device name template, e.g. `pi-{hostname}`.
+fn expand_device_name(template: &str) -> String {
+    if !template.contains("{hostname}") {
+        return template.to_string();
+    }
+    let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname").unwrap_or_default();
+    template.replace("{hostname}", hostname.trim())
+}
+
+fn known_uuid(name: &str) -> Option<Uuid> {
+    CHARACTERISTICS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, uuid)| *uuid)
+}
+
+impl Config {
+    /// Reads the config file, or returns the defaults when there is none.
+    pub fn load(path: &Path) -> std::io::Result<Config> {
+        let config: Config = match std::fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).map_err(|err| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string())
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => return Err(err),
+        };
+        let policies = config.retry_policies();
+        let overrides = policies.overrides.iter();
+        for (name, policy) in overrides.chain([(&"retry".to_string(), &policies.default)]) {
+            policy.validate().map_err(|err| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{name}: {err}"))
+            })?;
+        }
+        Ok(config)
+    }
+
+    /// Notify retries from `[retry]` and the `retry` of each characteristic.
+    pub fn retry_policies(&self) -> RetryPolicies {
+        RetryPolicies {
+            default: self.retry.clone(),
+            overrides: self
+                .metrics
+                .iter()
+                .filter_map(|(name, metric)| Some((name.clone(), metric.retry.clone()?)))
+                .collect(),
+        }
+    }
+
+    /// Replaces the UUIDs of aliased characteristics in `app` and returns
+    /// the aliases served, which instance remapping leaves alone.
+    pub fn apply_aliases(&self, app: &mut Application) -> HashSet<Uuid> {
+        let mut aliases = HashMap::new();
+        for (name, alias) in &self.characteristic_aliases {
+            match known_uuid(name) {
+                Some(uuid) => {
+                    aliases.insert(uuid, *alias);
+                }
+                None => log::warn!("Ignoring alias for unknown characteristic {name}"),
+            }
+        }
+        let mut served = HashSet::new();
+        for service in &mut app.services {
+            for characteristic in &mut service.characteristics {
+                if let Some(alias) = aliases.get(&characteristic.uuid) {
+                    println!("Serving {} as {alias}", characteristic.uuid);
+                    characteristic.uuid = *alias;
+                    served.insert(*alias);
+                }
+            }
+        }
+        served
+    }
+
+    /// Advertised name, from `local_name` (e.g. `--local-name`) or `device_name`.
+    pub fn device_name(&self, local_name: Option<&str>) -> String {
+        let template = local_name
+            .or(self.device_name.as_deref())
+            .unwrap_or(DEFAULT_DEVICE_NAME);
+        expand_device_name(template)
+    }
+
+    /// Whether the characteristic `name` is served, i.e. not `enabled = false`.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.metrics.get(name).is_none_or(|metric| metric.enabled)
+    }
+
+    /// Takes characteristics disabled with `enabled = false` out of `app`.
+    ///
+    /// Runs before aliasing. The removed characteristics are returned and must
+    /// be kept until shutdown: dropping their control handles would end the
+    /// control streams of the tasks serving them.
+    pub fn remove_disabled(&self, app: &mut Application) -> Vec<Characteristic> {
+        let mut disabled = Vec::new();
+        for (name, metric) in &self.metrics {
+            match known_uuid(name) {
+                Some(uuid) if !metric.enabled => disabled.push(uuid),
+                Some(_) => {}
+                None => log::warn!("Ignoring settings for unknown characteristic {name}"),
+            }
+        }
+        let mut removed = Vec::new();
+        for service in &mut app.services {
+            let (kept, dropped) = std::mem::take(&mut service.characteristics)
+                .into_iter()
+                .partition(|characteristic| !disabled.contains(&characteristic.uuid));
+            service.characteristics = kept;
+            removed.extend(dropped);
+        }
+        for characteristic in &removed {
+            println!(
+                "Not serving disabled characteristic {}",
+                characteristic.uuid
+            );
+        }
+        removed
+    }
+
+    /// Main loop schedule for metrics without an `interval_secs` of their own
+    /// being notified every `update_interval` seconds.
+    pub fn schedule(&self, update_interval: u64) -> Schedule {
+        let intervals: HashMap<String, u64> = self
+            .metrics
+            .iter()
+            .filter_map(|(name, metric)| Some((name.clone(), metric.interval_secs?.max(1) * 1000)))
+            .collect();
+        let mut schedule = Schedule {
+            tick_ms: 0,
+            default_ms: update_interval.max(1) * 1000,
+            intervals,
+        };
+        schedule.update_tick();
+        schedule
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+/// Metrics notified on their [Schedule] interval
+const SCHEDULED: &[&str] = &[
+    "cpu_load",
+    "temperature",
+    "ram_usage",
+    "uptime",
+    "disk_usage",
+    "cpu_core_load",
+    "load_average",
+    "swap_usage",
+    "network_throughput",
+    "cpu_frequency",
+    "metrics_snapshot",
+];
+
+/// Shortest interval a metric can be changed to at runtime
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Runtime intervals are a multiple of this, so the metrics never tick faster
+const INTERVAL_STEP: Duration = Duration::from_millis(50);
+
+/// Longest interval a metric can be changed to at runtime
+const MAX_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Per metric notification intervals, shared with the tasks notifying them
+/// so they can be changed at runtime
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    tick_ms: u64,
+    default_ms: u64,
+    intervals: HashMap<String, u64>,
+}
+
+impl Schedule {
+    /// Time between metric ticks.
+    pub fn tick(&self) -> Duration {
+        Duration::from_millis(self.tick_ms)
+    }
+
+    /// Time between notifications of the metric `name`.
+    pub fn interval(&self, name: &str) -> Duration {
+        Duration::from_millis(*self.intervals.get(name).unwrap_or(&self.default_ms))
+    }
+
+    /// Whether the metric `name` is notified on tick number `tick`.
+    pub fn is_due(&self, name: &str, tick: u64) -> bool {
+        let interval_ms = self.intervals.get(name).unwrap_or(&self.default_ms);
+        (tick * self.tick_ms).is_multiple_of(*interval_ms)
+    }
+
+    /// Changes the interval of the metric `name`, or returns why it cannot.
+    pub fn set(&mut self, name: &str, interval: Duration) -> Result<(), String> {
+        if !SCHEDULED.contains(&name) {
+            return Err(format!("{name} has no interval"));
+        }
+        if interval < MIN_INTERVAL || interval > MAX_INTERVAL {
+            return Err(format!(
+                "interval has to be between {}ms and {}s",
+                MIN_INTERVAL.as_millis(),
+                MAX_INTERVAL.as_secs()
+            ));
+        }
+        if !interval.as_nanos().is_multiple_of(INTERVAL_STEP.as_nanos()) {
+            return Err(format!(
+                "interval has to be a multiple of {}ms",
+                INTERVAL_STEP.as_millis()
+            ));
+        }
+        self.intervals
+            .insert(name.to_string(), interval.as_millis() as u64);
+        self.update_tick();
+        Ok(())
+    }
+
+    fn update_tick(&mut self) {
+        // Ticking at the greatest common divisor hits every interval exactly
+        self.tick_ms = self
+            .intervals
+            .values()
+            .fold(self.default_ms, |a, &b| gcd(a, b));
+    }
+}
+
+/// Restarts `interval` with `period` when that differs from its current one.
+pub fn retime(interval: &mut Interval, period: Duration) {
+    if interval.period() != period {
+        let missed_tick_behavior = interval.missed_tick_behavior();
+        *interval = time::interval(period);
+        interval.set_missed_tick_behavior(missed_tick_behavior);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_overrides_by_characteristic() {
+        let config: Config = toml::from_str(
+            "[retry]\nmax_retries = 5\n\n[metrics.cpu_load.retry]\nretry_delay_ms = 100\n",
+        )
+        .unwrap();
+        let policies = config.retry_policies();
+        assert_eq!(policies.get("ram_usage").max_retries, 5);
+        let cpu_load = policies.get("cpu_load");
+        assert_eq!(cpu_load.retry_delay_ms, 100);
+        // Unset fields of an override take the built-in defaults
+        assert_eq!(cpu_load.max_retries, RetryPolicy::default().max_retries);
+    }
+
+    #[test]
+    fn rejects_unbounded_backoff() {
+        let policy = RetryPolicy {
+            max_retries: 100,
+            ..Default::default()
+        };
+        assert!(policy.validate().is_err());
+        let policy = RetryPolicy {
+            backoff_multiplier: f32::NAN,
+            ..Default::default()
+        };
+        assert!(policy.validate().is_err());
+        assert!(RetryPolicy::default().validate().is_ok());
+    }
+
+    #[test]
+    fn ticks_at_the_gcd_of_all_intervals() {
+        let config: Config = toml::from_str("[metrics.uptime]\ninterval_secs = 3\n").unwrap();
+        let mut schedule = config.schedule(2);
+        assert_eq!(schedule.tick(), Duration::from_secs(1));
+        assert_eq!(schedule.interval("uptime"), Duration::from_secs(3));
+        assert_eq!(schedule.interval("cpu_load"), Duration::from_secs(2));
+
+        schedule
+            .set("cpu_load", Duration::from_millis(250))
+            .unwrap();
+        assert_eq!(schedule.tick(), Duration::from_millis(250));
+        assert!(schedule.is_due("cpu_load", 1));
+        assert!(!schedule.is_due("ram_usage", 4));
+        assert!(schedule.is_due("ram_usage", 8));
+        assert!(schedule.is_due("uptime", 12));
+
+        // Changing it back coarsens the tick again
+        schedule.set("cpu_load", Duration::from_secs(2)).unwrap();
+        assert_eq!(schedule.tick(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn rejects_invalid_intervals() {
+        let mut schedule = Config::default().schedule(1);
+        assert!(schedule.set("cpu_load", Duration::ZERO).is_err());
+        assert!(schedule.set("cpu_load", Duration::from_millis(50)).is_err());
+        assert!(schedule
+            .set("cpu_load", MAX_INTERVAL + INTERVAL_STEP)
+            .is_err());
+        assert!(schedule
+            .set("cpu_load", Duration::from_millis(120))
+            .is_err());
+        assert!(schedule.set("battery", Duration::from_secs(1)).is_err());
+        assert!(schedule.set("cpu_load", MAX_INTERVAL).is_ok());
+        // Rejected changes leave the tick alone
+        assert_eq!(schedule.tick(), Duration::from_secs(1));
+    }
+}
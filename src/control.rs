@@ -0,0 +1,183 @@
+use crate::config::Schedule;
+use bluer::Address;
+use std::time::Duration;
+use tokio::{process::Command as Process, sync::watch, time};
+
+/// Lets the reply notification go out before the system goes down
+const POWER_DELAY: Duration = Duration::from_secs(2);
+
+/// A command written to `WRITE_REQUEST_RESPONSE`: a case insensitive word,
+/// then its arguments after a space.
+#[derive(Debug)]
+pub enum Command<'a> {
+    /// `echo <payload>` notifies the payload back, for round trip times
+    Echo(&'a [u8]),
+    /// `auth`, only reaching here without a `control_token`
+    Auth,
+    Reboot,
+    Poweroff,
+    /// `interval <metric> <duration>`, e.g. `interval cpu_load 250ms`
+    Interval(String, Duration),
+}
+
+/// Parses `250ms` or `2s`, seconds without a unit. Negative, non-finite and
+/// overlong durations are `None`.
+fn parse_duration(text: &str) -> Option<Duration> {
+    if let Some(millis) = text.strip_suffix("ms") {
+        return millis.parse().ok().map(Duration::from_millis);
+    }
+    let secs = text.strip_suffix('s').unwrap_or(text);
+    Duration::try_from_secs_f64(secs.parse().ok()?).ok()
+}
+
+impl<'a> Command<'a> {
+    /// Parses a written value, or returns the error reply.
+    pub fn parse(value: &'a [u8]) -> Result<Self, String> {
+        let (word, arguments) = match value.iter().position(|&byte| byte == b' ') {
+            Some(space) => (&value[..space], &value[space + 1..]),
+            None => (value, &[][..]),
+        };
+        let word = String::from_utf8_lossy(word).trim().to_ascii_lowercase();
+        match word.as_str() {
+            "echo" => Ok(Command::Echo(arguments)),
+            "auth" => Ok(Command::Auth),
+            "reboot" => Ok(Command::Reboot),
+            "poweroff" => Ok(Command::Poweroff),
+            "interval" => {
+                let arguments = String::from_utf8_lossy(arguments);
+                let mut arguments = arguments.split_whitespace();
+                match (arguments.next(), arguments.next().and_then(parse_duration)) {
+                    (Some(metric), Some(interval)) => {
+                        Ok(Command::Interval(metric.to_string(), interval))
+                    }
+                    _ => Err("ERR usage: interval <metric> <duration>".to_string()),
+                }
+            }
+            _ => Err(format!("ERR unknown command {word}")),
+        }
+    }
+}
+
+/// Reboots or powers off through systemd after [POWER_DELAY].
+fn power(action: &'static str, device: Address) {
+    println!("{device} requested {action}");
+    crate::commands::record(action, 0, device);
+    crate::tasks::spawn(action, async move {
+        time::sleep(POWER_DELAY).await;
+        match Process::new("systemctl").arg(action).status().await {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("systemctl {action} failed with {status}"),
+            Err(err) => eprintln!("Running systemctl {action} failed: {err}"),
+        }
+    });
+}
+
+/// Runs `command` for `device` and returns the reply to notify.
+///
+/// Power commands are only run when `authenticated` writes are enforced,
+/// i.e. a `control_token` is configured. `interval` changes `schedule`, which
+/// the notifying tasks pick up right away.
+pub fn execute(
+    command: Command,
+    device: Address,
+    authenticated: bool,
+    schedule: &watch::Sender<Schedule>,
+) -> Vec<u8> {
+    let reply = match command {
+        Command::Echo(payload) => return payload.to_vec(),
+        Command::Auth => "ERR no control_token configured".to_string(),
+        Command::Reboot | Command::Poweroff if !authenticated => {
+            "ERR power commands need a control_token".to_string()
+        }
+        Command::Reboot => {
+            power("reboot", device);
+            "OK reboot".to_string()
+        }
+        Command::Poweroff => {
+            power("poweroff", device);
+            "OK poweroff".to_string()
+        }
+        Command::Interval(metric, interval) => {
+            let mut result = Ok(());
+            schedule.send_if_modified(|schedule| {
+                result = schedule.set(&metric, interval);
+                result.is_ok()
+            });
+            match result {
+                Ok(()) => {
+                    println!("{device} set the interval of {metric} to {interval:?}");
+                    format!("OK interval {metric} {}ms", interval.as_millis())
+                }
+                Err(err) => format!("ERR {err}"),
+            }
+        }
+    };
+    reply.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2s"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_duration("1.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("0"), Some(Duration::ZERO));
+        assert_eq!(parse_duration("0ms"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        assert_eq!(parse_duration("-1s"), None);
+        assert_eq!(parse_duration("-5ms"), None);
+        assert_eq!(parse_duration("inf"), None);
+        assert_eq!(parse_duration("NaN"), None);
+        assert_eq!(parse_duration("1e30"), None);
+        assert_eq!(parse_duration("18446744073709551616ms"), None);
+        assert_eq!(parse_duration("5m"), None);
+        assert_eq!(parse_duration("1h"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert!(matches!(
+            Command::parse(b"echo hi there"),
+            Ok(Command::Echo(b"hi there"))
+        ));
+        assert!(matches!(Command::parse(b"echo"), Ok(Command::Echo(b""))));
+        assert!(matches!(Command::parse(b"REBOOT"), Ok(Command::Reboot)));
+        assert!(matches!(Command::parse(b"poweroff"), Ok(Command::Poweroff)));
+        assert!(matches!(Command::parse(b"Auth"), Ok(Command::Auth)));
+        match Command::parse(b"interval cpu_load 250ms") {
+            Ok(Command::Interval(metric, interval)) => {
+                assert_eq!(metric, "cpu_load");
+                assert_eq!(interval, Duration::from_millis(250));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        assert_eq!(
+            Command::parse(b"shutdown now").unwrap_err(),
+            "ERR unknown command shutdown"
+        );
+        assert!(Command::parse(b"").is_err());
+        for usage in [
+            &b"interval"[..],
+            b"interval cpu_load",
+            b"interval cpu_load 5m",
+            b"interval cpu_load -1s",
+            b"interval cpu_load 99999999999999999999999ms",
+        ] {
+            assert_eq!(
+                Command::parse(usage).unwrap_err(),
+                "ERR usage: interval <metric> <duration>"
+            );
+        }
+    }
+}
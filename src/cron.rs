@@ -0,0 +1,212 @@
+use crate::commands;
+use bluer::gatt::local::{
+    Characteristic, CharacteristicRead, CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+};
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::{process::Stdio, sync::Arc};
+use tokio::{io::AsyncWriteExt, process::Command, sync::Mutex};
+
+/// Account whose crontab holds the managed entries
+const USER: &str = "ble-raspi";
+
+/// Trailing comment marking an entry as created over BLE, followed by its command key
+const MARKER: &str = "# ble-raspi:";
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CronJob {
+    schedule: String,
+    command: String,
+}
+
+/// Accepts five cron fields or a single `@` nickname such as `@hourly`.
+fn valid_schedule(schedule: &str) -> bool {
+    let fields: Vec<&str> = schedule.split_whitespace().collect();
+    match fields[..] {
+        [nickname] => {
+            nickname.starts_with('@') && nickname[1..].chars().all(|c| c.is_ascii_lowercase())
+        }
+        [_, _, _, _, _] => fields.iter().all(|field| {
+            field
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "*/,-".contains(c))
+        }),
+        _ => false,
+    }
+}
+
+async fn read_crontab() -> std::io::Result<String> {
+    let output = Command::new("crontab")
+        .args(["-u", USER, "-l"])
+        .output()
+        .await?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("no crontab") {
+        Ok(String::new())
+    } else {
+        Err(std::io::Error::other(stderr.trim().to_string()))
+    }
+}
+
+async fn write_crontab(crontab: &str) -> std::io::Result<()> {
+    let mut child = Command::new("crontab")
+        .args(["-u", USER, "-"])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(crontab.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Parses a crontab line created by [add], `None` for any other line.
+fn parse_managed(line: &str) -> Option<CronJob> {
+    let (entry, key) = line.rsplit_once(MARKER)?;
+    let command = commands::lookup(key.trim())?;
+    let schedule = entry.trim_end().strip_suffix(command)?.trim_end();
+    Some(CronJob {
+        schedule: schedule.to_string(),
+        command: key.trim().to_string(),
+    })
+}
+
+fn managed_jobs(crontab: &str) -> Vec<CronJob> {
+    crontab.lines().filter_map(parse_managed).collect()
+}
+
+async fn add(job: CronJob) -> std::io::Result<()> {
+    let command = commands::lookup(&job.command).expect("validated key");
+    let mut crontab = read_crontab().await?;
+    if !crontab.is_empty() && !crontab.ends_with('\n') {
+        crontab.push('\n');
+    }
+    crontab.push_str(&format!(
+        "{} {command} {MARKER}{}\n",
+        job.schedule, job.command
+    ));
+    write_crontab(&crontab).await?;
+    println!("Added cron job '{}' at '{}'", job.command, job.schedule);
+    Ok(())
+}
+
+/// Removes the managed entry at `index`, returning `false` if there is none.
+async fn delete(index: usize) -> std::io::Result<bool> {
+    let crontab = read_crontab().await?;
+    let mut managed = 0;
+    let mut found = false;
+    let mut lines = Vec::new();
+    for line in crontab.lines() {
+        if parse_managed(line).is_some() {
+            managed += 1;
+            if managed - 1 == index {
+                found = true;
+                continue;
+            }
+        }
+        lines.push(line);
+    }
+    if !found {
+        return Ok(false);
+    }
+    let mut crontab = lines.join("\n");
+    crontab.push('\n');
+    write_crontab(&crontab).await?;
+    println!("Deleted cron job {index}");
+    Ok(true)
+}
+
+fn crontab_error(err: std::io::Error) -> ReqError {
+    eprintln!("Crontab update failed: {err}");
+    ReqError::Failed
+}
+
+/// Creates the `CRON_ADD`, `CRON_LIST` and `CRON_DELETE` characteristics.
+///
+/// Only entries created here are listed or deleted; they are marked with a
+/// trailing comment and reference commands by their whitelist key.
+pub fn characteristics() -> Vec<Characteristic> {
+    // Serializes read-modify-write cycles on the crontab
+    let lock = Arc::new(Mutex::new(()));
+    let delete_lock = lock.clone();
+
+    vec![
+        // CBOR map with schedule and command key
+        Characteristic {
+            uuid: crate::CRON_ADD,
+            write: Some(CharacteristicWrite {
+                write: true,
+                method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                    let lock = lock.clone();
+                    async move {
+                        let job: CronJob = ciborium::from_reader(value.as_slice())
+                            .map_err(|_| ReqError::Failed)?;
+                        if commands::lookup(&job.command).is_none() {
+                            return Err(ReqError::NotPermitted);
+                        }
+                        if !valid_schedule(&job.schedule) {
+                            return Err(ReqError::NotSupported);
+                        }
+                        let _guard = lock.lock().await;
+                        add(job).await.map_err(crontab_error)
+                    }
+                    .boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        // CBOR array of managed entries
+        Characteristic {
+            uuid: crate::CRON_LIST,
+            read: Some(CharacteristicRead {
+                read: true,
+                fun: Box::new(|_req| {
+                    async move {
+                        let crontab = read_crontab().await.map_err(crontab_error)?;
+                        let mut value = Vec::new();
+                        ciborium::into_writer(&managed_jobs(&crontab), &mut value)
+                            .map_err(|_| ReqError::Failed)?;
+                        Ok(value)
+                    }
+                    .boxed()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        // Index into the CRON_LIST array
+        Characteristic {
+            uuid: crate::CRON_DELETE,
+            write: Some(CharacteristicWrite {
+                write: true,
+                method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                    let lock = delete_lock.clone();
+                    async move {
+                        let [index] = value[..] else {
+                            return Err(ReqError::InvalidValueLength);
+                        };
+                        let _guard = lock.lock().await;
+                        match delete(index as usize).await {
+                            Ok(true) => Ok(()),
+                            Ok(false) => Err(ReqError::InvalidOffset),
+                            Err(err) => Err(crontab_error(err)),
+                        }
+                    }
+                    .boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    ]
+}
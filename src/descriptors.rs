@@ -0,0 +1,237 @@
+use bluer::{
+    gatt::local::{Application, Descriptor, DescriptorRead},
+    id,
+};
+use futures::FutureExt;
+use uuid::Uuid;
+
+// Presentation Format types, from the Bluetooth Assigned Numbers
+const UINT8: u8 = 0x04;
+const UINT16: u8 = 0x06;
+const UINT32: u8 = 0x08;
+const UINT64: u8 = 0x0a;
+const FLOAT32: u8 = 0x14;
+const UTF8S: u8 = 0x19;
+
+// Units, from the Bluetooth Assigned Numbers
+const UNITLESS: u16 = 0x2700;
+#[cfg(feature = "door-sensor")]
+const SECOND: u16 = 0x2703;
+const HERTZ: u16 = 0x2722;
+#[cfg(feature = "environment-sensor")]
+const PASCAL: u16 = 0x2724;
+const CELSIUS: u16 = 0x272f;
+#[cfg(feature = "ambient-sensor")]
+const LUX: u16 = 0x2731;
+const MINUTE: u16 = 0x2760;
+const PERCENTAGE: u16 = 0x27ad;
+#[cfg(feature = "co2-sensor")]
+const PPM: u16 = 0x27c4;
+
+/// Namespace of the Presentation Format description field
+const BLUETOOTH_SIG: u8 = 0x01;
+
+/// Type, exponent and unit of the single value characteristics, as read
+/// without the notify header
+const FORMATS: &[(Uuid, u8, i8, u16)] = &[
+    (crate::TEMPERATURE, FLOAT32, 0, CELSIUS),
+    (crate::CPU_LOAD, FLOAT32, 0, UNITLESS),
+    (crate::UPTIME, UINT64, 0, MINUTE),
+    (crate::GPU_TEMPERATURE, FLOAT32, 0, CELSIUS),
+    (crate::GPU_CLOCK, UINT64, 0, HERTZ),
+    (crate::THROTTLED, UINT32, 0, UNITLESS),
+    (crate::CPU_FREQUENCY, UINT64, 0, HERTZ),
+    (crate::PROCESS_COUNT, UINT32, 0, UNITLESS),
+    (crate::SNAPSHOT_FORMAT, UINT8, 0, UNITLESS),
+    (crate::PROFILE_VERSION, UINT16, 0, UNITLESS),
+    (crate::DEGRADED_METRICS, UINT8, 0, UNITLESS),
+    (crate::NOTIFY_RETRIES, UINT32, 0, UNITLESS),
+    (crate::UPTIME_HUMAN, UTF8S, 0, UNITLESS),
+    (crate::SYSTEM_LOCALE, UTF8S, 0, UNITLESS),
+    (crate::DISK_WEAR, UINT8, 0, PERCENTAGE),
+    #[cfg(feature = "ambient-sensor")]
+    (crate::AMBIENT_LIGHT_LUX, UINT32, 0, LUX),
+    #[cfg(feature = "environment-sensor")]
+    (crate::PRESSURE_PA, UINT32, 0, PASCAL),
+    #[cfg(feature = "environment-sensor")]
+    (crate::HUMIDITY_PCT, UINT16, -2, PERCENTAGE),
+    #[cfg(feature = "co2-sensor")]
+    (crate::CO2_PPM, UINT16, 0, PPM),
+    #[cfg(feature = "co2-sensor")]
+    (crate::CO2_ALERT, UINT16, 0, PPM),
+    #[cfg(feature = "pir-sensor")]
+    (crate::PIR_MOTION_COUNT, UINT32, 0, UNITLESS),
+    #[cfg(feature = "door-sensor")]
+    (crate::DOOR_STATE, UINT8, 0, UNITLESS),
+    #[cfg(feature = "door-sensor")]
+    (crate::DOOR_OPEN_SECONDS, UINT32, 0, SECOND),
+];
+
+/// Names shown by generic clients such as nRF Connect instead of the bare UUID
+const DESCRIPTIONS: &[(Uuid, &str)] = &[
+    (crate::TEMPERATURE, "CPU Temperature (°C)"),
+    (crate::CPU_LOAD, "CPU Load (fraction)"),
+    (crate::RAM_USAGE, "RAM Used/Total (bytes)"),
+    (crate::UPTIME, "Uptime (min)"),
+    (crate::DISK_USAGE, "Disk Used/Total (bytes), Used (%)"),
+    (crate::CPU_CORE_LOAD, "CPU Load per Core (fraction)"),
+    (crate::LOAD_AVERAGE, "Load Average 1/5/15 min"),
+    (crate::SWAP_USAGE, "Swap Used/Total (bytes)"),
+    (crate::NETWORK_THROUGHPUT, "Network RX/TX (bytes/s)"),
+    (crate::GPU_TEMPERATURE, "GPU Temperature (°C)"),
+    (crate::GPU_CLOCK, "GPU Core Clock (Hz)"),
+    (crate::THROTTLED, "Throttling Flags"),
+    (crate::CPU_FREQUENCY, "CPU Frequency (Hz)"),
+    (crate::WIFI_STATUS, "Wi-Fi Status (CBOR)"),
+    (crate::IP_ADDRESSES, "IP Addresses (CBOR)"),
+    (crate::SYSTEM_INFO, "System Info (CBOR)"),
+    (crate::PROCESS_COUNT, "Process Count"),
+    (crate::TOP_PROCESSES, "Top Processes Request"),
+    (crate::METRICS_SNAPSHOT, "Metrics Snapshot"),
+    (crate::SNAPSHOT_FORMAT, "Snapshot Encoding"),
+    (crate::PROFILE_VERSION, "Payload Layout Version"),
+    (crate::WRITE_REQUEST_RESPONSE, "Request Responses"),
+    (crate::DISK_HEALTH, "Disk S.M.A.R.T. Health"),
+    (crate::WIFI_SCAN_TRIGGER, "Wi-Fi Scan Trigger"),
+    (crate::WIFI_SCAN_RESULTS, "Wi-Fi Scan Results"),
+    (crate::WIFI_CONNECT, "Wi-Fi Connect"),
+    (crate::BT_PAIR_REMOTE, "Pair Remote Device"),
+    (crate::EXEC_STREAM, "Run Command"),
+    (crate::TASK_STATUS, "Background Task Status"),
+    (crate::VOLTAGE_HISTORY, "Supply Voltage History (mV)"),
+    (crate::DEGRADED_METRICS, "Degraded Metrics Mask"),
+    (crate::LATENCY_HISTOGRAM, "Round Trip Latency Histogram"),
+    (crate::STATS_RESET, "Reset Statistics"),
+    (crate::NOTIFY_RETRIES, "Notify Retries"),
+    (crate::TICK_JITTER, "Metrics Tick Jitter (us)"),
+    (crate::PROTO_NEGOTIATE, "Capability Negotiation"),
+    (crate::TS_QUERY, "Metric History Query"),
+    (crate::MTU_CHANGED, "Peer MTU"),
+    (crate::QOS_PRIORITY, "Metric Notify Priority"),
+    (crate::UPTIME_HUMAN, "Uptime"),
+    (crate::SENSOR_PAIRING_CODE, "Pairing Code"),
+    (crate::SYSTEM_LOCALE, "System Locale"),
+    (crate::DISK_WEAR, "Disk Remaining Life (%)"),
+    (crate::ALERTS, "Alerts"),
+    (crate::SYSTEM_EVENTS, "System Events (CBOR)"),
+    (crate::PEER_DISCONNECT, "Disconnect Peer"),
+    (crate::TEMP_MAP, "Thermal Zone Temperatures"),
+    (crate::STRESS_TEST, "Stress Test"),
+    (crate::HW_RANDOM, "Hardware Random Bytes"),
+    (crate::COMMAND_HISTORY, "Command History"),
+    (crate::PEER_STATS, "Peer Traffic Statistics"),
+    (crate::BROADCAST, "Broadcast Message"),
+    (crate::CHAR_LOCK, "Lock Metric Value"),
+    (crate::CHAR_INJECT, "Inject Test Value"),
+    (crate::ERROR_DETAIL, "Metric Error Detail (CBOR)"),
+    #[cfg(feature = "status-led")]
+    (crate::STATUS_LED, "Status LED Mode"),
+    #[cfg(feature = "status-led")]
+    (crate::LED_PATTERN, "Status LED Pattern"),
+    #[cfg(feature = "ambient-sensor")]
+    (crate::AMBIENT_LIGHT_LUX, "Ambient Light (lx)"),
+    #[cfg(feature = "environment-sensor")]
+    (crate::PRESSURE_PA, "Pressure (Pa)"),
+    #[cfg(feature = "environment-sensor")]
+    (crate::HUMIDITY_PCT, "Humidity (0.01 %)"),
+    #[cfg(feature = "co2-sensor")]
+    (crate::CO2_PPM, "CO₂ (ppm)"),
+    #[cfg(feature = "co2-sensor")]
+    (crate::CO2_ALERT, "CO₂ Alert (ppm)"),
+    #[cfg(feature = "pir-sensor")]
+    (crate::PIR_MOTION, "Motion"),
+    #[cfg(feature = "pir-sensor")]
+    (crate::PIR_MOTION_COUNT, "Motion Count"),
+    #[cfg(feature = "door-sensor")]
+    (crate::DOOR_STATE, "Door State (0 closed, 1 open)"),
+    #[cfg(feature = "door-sensor")]
+    (crate::DOOR_OPEN_SECONDS, "Door Open Time (s)"),
+    #[cfg(feature = "audio-alert")]
+    (crate::SPEAKER_ALERT, "Buzzer Tone"),
+    #[cfg(feature = "obd2")]
+    (crate::OBD2_PID, "OBD-II PID Query"),
+    #[cfg(feature = "gpio")]
+    (crate::POWER_CYCLE, "Power Cycle Relay"),
+    #[cfg(feature = "posix-mq")]
+    (crate::MQ_NOTIFY, "Message Queue In"),
+    #[cfg(feature = "posix-mq")]
+    (crate::MQ_PUBLISH, "Message Queue Out"),
+    #[cfg(feature = "traffic-control")]
+    (crate::TC_LIMIT, "Bandwidth Limit"),
+    #[cfg(feature = "screenshot")]
+    (crate::SCREEN_CAPTURE, "Screen Capture (JPEG)"),
+    #[cfg(feature = "cert-provisioning")]
+    (crate::CERT_WRITE, "Install Certificate"),
+    #[cfg(feature = "cert-provisioning")]
+    (crate::CERT_STATUS, "Certificate Expiry"),
+    #[cfg(feature = "cron-bridge")]
+    (crate::CRON_ADD, "Add Cron Entry"),
+    #[cfg(feature = "cron-bridge")]
+    (crate::CRON_LIST, "Cron Entries"),
+    #[cfg(feature = "cron-bridge")]
+    (crate::CRON_DELETE, "Delete Cron Entry"),
+    #[cfg(feature = "kmsg")]
+    (crate::KERNEL_MESSAGES, "Kernel Messages"),
+];
+
+/// Characteristic User Description (0x2901) returning `description` as UTF-8.
+fn user_description(description: &'static str) -> Descriptor {
+    Descriptor {
+        uuid: id::Descriptor::GattCharacteristicUserDescription.into(),
+        read: Some(DescriptorRead {
+            read: true,
+            fun: Box::new(move |_req| async move { Ok(description.as_bytes().to_vec()) }.boxed()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Characteristic Presentation Format (0x2904) declaring `format`, `exponent` and `unit`.
+fn presentation_format(format: u8, exponent: i8, unit: u16) -> Descriptor {
+    let mut value = vec![format, exponent as u8];
+    value.extend(unit.to_le_bytes());
+    // Namespace and description, none within the namespace
+    value.extend([BLUETOOTH_SIG, 0x00, 0x00]);
+
+    Descriptor {
+        uuid: id::Descriptor::GattCharacteristicPresentationFormat.into(),
+        read: Some(DescriptorRead {
+            read: true,
+            fun: Box::new(move |_req| {
+                let value = value.clone();
+                async move { Ok(value) }.boxed()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Attaches a User Description to every characteristic of `app` listed in
+/// [DESCRIPTIONS], and a Presentation Format to those listed in [FORMATS].
+///
+/// Must run before characteristic UUIDs are aliased or remapped.
+pub fn attach(app: &mut Application) {
+    for service in &mut app.services {
+        for characteristic in &mut service.characteristics {
+            let description = DESCRIPTIONS
+                .iter()
+                .find(|(uuid, _)| *uuid == characteristic.uuid)
+                .map(|(_, description)| *description);
+            if let Some(description) = description {
+                characteristic
+                    .descriptors
+                    .push(user_description(description));
+            }
+            let format = FORMATS
+                .iter()
+                .find(|(uuid, ..)| *uuid == characteristic.uuid);
+            if let Some(&(_, format, exponent, unit)) = format {
+                characteristic
+                    .descriptors
+                    .push(presentation_format(format, exponent, unit));
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use bluer::{
+    gatt::local::{Characteristic, CharacteristicRead, Service},
+    id,
+};
+use futures::FutureExt;
+
+const MANUFACTURER: &str = "Raspberry Pi Ltd";
+
+/// Board model, e.g. `Raspberry Pi 4 Model B Rev 1.4`
+const MODEL_PATH: &str = "/proc/device-tree/model";
+const SERIAL_PATH: &str = "/proc/device-tree/serial-number";
+
+/// Device tree strings are NUL terminated.
+fn device_tree_string(path: &str) -> String {
+    std::fs::read_to_string(path)
+        .map(|value| value.trim_end_matches('\0').trim().to_string())
+        .unwrap_or_default()
+}
+
+fn string_characteristic(uuid: id::Characteristic, value: String) -> Characteristic {
+    Characteristic {
+        uuid: uuid.into(),
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(move |_req| {
+                let value = value.clone().into_bytes();
+                async move { Ok(value) }.boxed()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Creates the Bluetooth SIG Device Information Service (0x180A).
+///
+/// Model and serial number come from the device tree. The firmware revision
+/// is the version of this server.
+pub fn service() -> Service {
+    Service {
+        uuid: id::Service::DeviceInformation.into(),
+        primary: true,
+        characteristics: vec![
+            string_characteristic(
+                id::Characteristic::ManufacturerNameString,
+                MANUFACTURER.to_string(),
+            ),
+            string_characteristic(
+                id::Characteristic::ModelNumberString,
+                device_tree_string(MODEL_PATH),
+            ),
+            string_characteristic(
+                id::Characteristic::SerialNumberString,
+                device_tree_string(SERIAL_PATH),
+            ),
+            string_characteristic(
+                id::Characteristic::FirmwareRevisionString,
+                env!("CARGO_PKG_VERSION").to_string(),
+            ),
+        ],
+        ..Default::default()
+    }
+}
@@ -0,0 +1,181 @@
+use crate::payload::NotifySequence;
+use bluer::gatt::local::{
+    Characteristic, CharacteristicNotifier, CharacteristicNotify, CharacteristicNotifyMethod,
+    CharacteristicRead,
+};
+use futures::FutureExt;
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{fs, process::Command, sync::mpsc, time};
+
+/// Drive checked when the root device cannot be resolved
+pub const DEFAULT_DRIVE: &str = "/dev/mmcblk0";
+
+/// How long a S.M.A.R.T. assessment stays cached
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+const HEALTHY: u8 = 0x00;
+const FAILING: u8 = 0x01;
+const UNKNOWN: u8 = 0xFF;
+
+/// Resolves the whole-disk device backing the root filesystem,
+/// e.g. `/dev/mmcblk0p2` becomes `/dev/mmcblk0`.
+pub async fn root_drive() -> Option<String> {
+    let mounts = fs::read_to_string("/proc/mounts").await.ok()?;
+    let device = mounts
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|fields| fields.get(1) == Some(&"/"))?
+        .first()?
+        .strip_prefix("/dev/")?
+        .to_string();
+
+    // Partitions live below their parent disk in sysfs
+    let block = fs::canonicalize(Path::new("/sys/class/block").join(&device))
+        .await
+        .ok()?;
+    if block.join("partition").exists() {
+        let parent = block.parent()?.file_name()?.to_str()?;
+        Some(format!("/dev/{parent}"))
+    } else {
+        Some(format!("/dev/{device}"))
+    }
+}
+
+/// Runs `smartctl -H` and maps the overall assessment to a status byte.
+async fn assess(drive: &str) -> u8 {
+    let output = match Command::new("smartctl").arg("-H").arg(drive).output().await {
+        Ok(output) => output,
+        Err(err) => {
+            eprintln!("Could not run smartctl: {err}");
+            return UNKNOWN;
+        }
+    };
+    // smartctl encodes warnings in its exit status, so only the text is reliable
+    verdict(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Maps the overall assessment in `smartctl -H` output to a status byte.
+fn verdict(stdout: &str) -> u8 {
+    let verdict = stdout.lines().find_map(|line| {
+        let (label, value) = line.split_once(':')?;
+        (label.contains("overall-health") || label.contains("Health Status"))
+            .then(|| value.trim().to_string())
+    });
+    match verdict.as_deref() {
+        Some("PASSED" | "OK") => HEALTHY,
+        Some(v) if v.starts_with("FAILED") => FAILING,
+        _ => UNKNOWN,
+    }
+}
+
+/// Refreshes the cached assessment and indicates a transition to failing.
+async fn monitor(status: Arc<AtomicU8>, mut notifiers: mpsc::Receiver<CharacteristicNotifier>) {
+    let drive = root_drive()
+        .await
+        .unwrap_or_else(|| DEFAULT_DRIVE.to_string());
+    println!("Monitoring S.M.A.R.T. health of {drive}");
+
+    let mut notifier: Option<CharacteristicNotifier> = None;
+    let mut sequence = NotifySequence::new("disk health");
+    let mut interval = time::interval(REFRESH_INTERVAL);
+    loop {
+        tokio::select! {
+            Some(new_notifier) = notifiers.recv() => notifier = Some(new_notifier),
+            _ = interval.tick() => {
+                let health = assess(&drive).await;
+                let previous = status.swap(health, Ordering::Relaxed);
+                if previous == HEALTHY && health == FAILING {
+                    eprintln!("Disk {drive} reports S.M.A.R.T. failure");
+                    if let (Some(n), true) = (&mut notifier, crate::security::admits_indications().await) {
+                        if n.notify(sequence.stamp(&[health][..])).await.is_err() {
+                            notifier = None;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `DISK_HEALTH` characteristic and starts its refresh task.
+pub fn characteristic() -> Characteristic {
+    let status = Arc::new(AtomicU8::new(UNKNOWN));
+    let (notifier_tx, notifier_rx) = mpsc::channel(1);
+    crate::tasks::spawn("disk-health", monitor(status.clone(), notifier_rx));
+
+    Characteristic {
+        uuid: crate::DISK_HEALTH,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(move |_req| {
+                let health = status.load(Ordering::Relaxed);
+                async move { Ok(vec![health]) }.boxed()
+            }),
+            ..Default::default()
+        }),
+        notify: Some(CharacteristicNotify {
+            indicate: true,
+            method: CharacteristicNotifyMethod::Fun(Box::new(move |notifier| {
+                let notifier_tx = notifier_tx.clone();
+                async move {
+                    let _ = notifier_tx.send(notifier).await;
+                }
+                .boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "\
+smartctl 7.3 2022-02-28 r5338 [aarch64-linux-6.1.21-v8+] (local build)
+Copyright (C) 2002-22, Bruce Allen, Christian Franke, www.smartmontools.org
+
+=== START OF READ SMART DATA SECTION ===
+";
+
+    #[test]
+    fn passed_is_healthy() {
+        let ata = format!("{HEADER}SMART overall-health self-assessment test result: PASSED\n");
+        assert_eq!(verdict(&ata), HEALTHY);
+        let scsi = format!("{HEADER}SMART Health Status: OK\n");
+        assert_eq!(verdict(&scsi), HEALTHY);
+    }
+
+    #[test]
+    fn failed_is_failing() {
+        let output = format!(
+            "{HEADER}SMART overall-health self-assessment test result: FAILED!\n\
+Drive failure expected in less than 24 hours. SAVE ALL DATA.\n"
+        );
+        assert_eq!(verdict(&output), FAILING);
+    }
+
+    #[test]
+    fn unsupported_is_unknown() {
+        let sd_card = "\
+smartctl 7.3 2022-02-28 r5338 [aarch64-linux-6.1.21-v8+] (local build)
+Copyright (C) 2002-22, Bruce Allen, Christian Franke, www.smartmontools.org
+
+/dev/mmcblk0: Unable to detect device type
+Please specify device type with the -d option.
+";
+        assert_eq!(verdict(sd_card), UNKNOWN);
+        let no_smart =
+            format!("{HEADER}SMART support is: Unavailable - device lacks SMART capability.\n");
+        assert_eq!(verdict(&no_smart), UNKNOWN);
+        assert_eq!(verdict(""), UNKNOWN);
+    }
+}
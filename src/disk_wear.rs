@@ -0,0 +1,122 @@
+use crate::{
+    events::SystemEvent,
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotify, CharacteristicNotifyMethod,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, StreamExt};
+use std::time::Duration;
+use tokio::{process::Command, time};
+
+/// Wear changes over weeks, not seconds
+const POLL_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Reads the normalized `Wear_Leveling_Count` (100 is new) from `smartctl -A`.
+async fn read_wear(drive: &str) -> std::io::Result<Option<u8>> {
+    let output = Command::new("smartctl")
+        .arg("-A")
+        .arg(drive)
+        .output()
+        .await?;
+    // Columns: ID# ATTRIBUTE_NAME FLAG VALUE WORST THRESH TYPE UPDATED WHEN_FAILED RAW_VALUE
+    let wear = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|columns| columns.get(1) == Some(&"Wear_Leveling_Count"))
+        .and_then(|columns| columns.get(3)?.parse::<u8>().ok())
+        .map(|value| value.min(100));
+    Ok(wear)
+}
+
+async fn serve(alert_wear: u8, control: CharacteristicControl, retry: RetryPolicy) {
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut wear_sequence = NotifySequence::new("disk wear");
+    let mut below_threshold = false;
+    let mut interval = time::interval(POLL_INTERVAL);
+    let drive = crate::disk_health::root_drive()
+        .await
+        .unwrap_or_else(|| crate::disk_health::DEFAULT_DRIVE.to_string());
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting disk wear notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "disk wear");
+                        writer_opt = Some(notifier);
+                        interval.reset_immediately();
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            _ = interval.tick() => {
+                let wear = match read_wear(&drive).await {
+                    Ok(Some(wear)) => wear,
+                    Ok(None) => {
+                        eprintln!("{drive} reports no Wear_Leveling_Count");
+                        continue;
+                    }
+                    Err(err) => {
+                        eprintln!("Running smartctl failed: {err}");
+                        continue;
+                    }
+                };
+                println!("Disk wear level is: {wear}");
+
+                if let Some(writer) = &mut writer_opt {
+                    if retry.notify(writer, &wear_sequence.stamp(&[wear][..])).await.is_err() {
+                        writer_opt = None;
+                    }
+                }
+
+                // Alert once when the wear level first drops below the threshold
+                let was_below = below_threshold;
+                below_threshold = wear < alert_wear;
+                if below_threshold && !was_below {
+                    eprintln!("Disk wear level {wear} is below {alert_wear}, replace {drive}");
+                    crate::events::publish(SystemEvent::ThresholdBreached {
+                        metric: "disk_wear".to_string(),
+                        value: wear as f32,
+                    });
+                    crate::alerts::raise(crate::alerts::DISK_WEAR, wear);
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `DISK_WEAR` characteristic for the root drive.
+///
+/// It notifies the remaining life as a `u8` from 100 (new) to 0. A
+/// [DISK_WEAR](crate::alerts::DISK_WEAR) alert is raised once it drops
+/// below `alert_wear`.
+pub fn characteristic(alert_wear: u8, retry: &RetryPolicies) -> Characteristic {
+    let (control, control_handle) = characteristic_control();
+    crate::tasks::spawn(
+        "disk-wear",
+        serve(alert_wear, control, retry.get("disk_wear")),
+    );
+
+    Characteristic {
+        uuid: crate::DISK_WEAR,
+        notify: Some(CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Io,
+            ..Default::default()
+        }),
+        control_handle,
+        ..Default::default()
+    }
+}
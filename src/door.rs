@@ -0,0 +1,157 @@
+use crate::{
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotifier, CharacteristicNotify, CharacteristicNotifyMethod,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use rppal::gpio::{Gpio, InputPin, Level, Trigger};
+use std::time::{Duration, Instant};
+use tokio::{sync::mpsc, time};
+
+/// Contact bounce filter for the reed switch
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+const CLOSED: u8 = 0x00;
+const OPEN: u8 = 0x01;
+
+/// The reed switch pulls the pin low while the magnet is near, i.e. the door is closed.
+fn state(level: Level) -> u8 {
+    match level {
+        Level::Low => CLOSED,
+        Level::High => OPEN,
+    }
+}
+
+async fn serve(
+    pin: InputPin,
+    mut edges: mpsc::UnboundedReceiver<Level>,
+    mut state_notifiers: mpsc::Receiver<CharacteristicNotifier>,
+    open_seconds_control: CharacteristicControl,
+    retry: RetryPolicy,
+) {
+    let mut door_state = state(pin.read());
+    // Dropping the pin would clear its interrupt
+    let _pin = pin;
+    let mut opened_at = (door_state == OPEN).then(Instant::now);
+    let mut door_open_duration = Duration::ZERO;
+
+    let mut state_notifier_opt: Option<CharacteristicNotifier> = None;
+    let mut open_seconds_writer_opt: Option<CharacteristicWriter> = None;
+    let mut state_sequence = NotifySequence::new("door state");
+    let mut open_seconds_sequence = NotifySequence::new("door open time");
+    let mut interval = time::interval(Duration::from_secs(1));
+    pin_mut!(open_seconds_control);
+
+    loop {
+        tokio::select! {
+            evt = open_seconds_control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting door open time notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "door open time");
+                        open_seconds_writer_opt = Some(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Some(notifier) = state_notifiers.recv() => state_notifier_opt = Some(notifier),
+            Some(level) = edges.recv() => {
+                let new_state = state(level);
+                if new_state == door_state {
+                    continue;
+                }
+                door_state = new_state;
+                if door_state == OPEN {
+                    println!("Door opened");
+                    opened_at = Some(Instant::now());
+                } else if let Some(opened) = opened_at.take() {
+                    door_open_duration = opened.elapsed();
+                    println!("Door closed after {}s", door_open_duration.as_secs());
+                }
+                if let (Some(notifier), true) = (&mut state_notifier_opt, crate::security::admits_indications().await) {
+                    if notifier.notify(state_sequence.stamp(&[door_state][..])).await.is_err() {
+                        state_notifier_opt = None;
+                    }
+                }
+            },
+            _ = interval.tick() => {
+                if let Some(opened) = opened_at {
+                    door_open_duration = opened.elapsed();
+                }
+                if let Some(writer) = &mut open_seconds_writer_opt {
+                    let seconds = door_open_duration.as_secs() as u32;
+                    if retry.notify(writer, &open_seconds_sequence.stamp(&seconds.to_le_bytes()[..])).await.is_err() {
+                        open_seconds_writer_opt = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `DOOR_STATE` and `DOOR_OPEN_SECONDS` characteristics for a
+/// reed switch wired between GPIO `pin` and ground.
+pub fn characteristics(pin: u8, retry: &RetryPolicies) -> rppal::gpio::Result<Vec<Characteristic>> {
+    let mut pin = Gpio::new()?.get(pin)?.into_input_pullup();
+    let (edge_tx, edge_rx) = mpsc::unbounded_channel();
+    pin.set_async_interrupt(Trigger::Both, Some(DEBOUNCE), move |event| {
+        let level = match event.trigger {
+            Trigger::RisingEdge => Level::High,
+            _ => Level::Low,
+        };
+        let _ = edge_tx.send(level);
+    })?;
+
+    let (state_tx, state_rx) = mpsc::channel(1);
+    let (open_seconds_control, open_seconds_handle) = characteristic_control();
+    crate::tasks::spawn(
+        "door",
+        serve(
+            pin,
+            edge_rx,
+            state_rx,
+            open_seconds_control,
+            retry.get("door_open_seconds"),
+        ),
+    );
+
+    Ok(vec![
+        // Open/closed state with confirmed delivery
+        Characteristic {
+            uuid: crate::DOOR_STATE,
+            notify: Some(CharacteristicNotify {
+                indicate: true,
+                method: CharacteristicNotifyMethod::Fun(Box::new(move |notifier| {
+                    let state_tx = state_tx.clone();
+                    async move {
+                        let _ = state_tx.send(notifier).await;
+                    }
+                    .boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        // Seconds the door is (or last was) open
+        Characteristic {
+            uuid: crate::DOOR_OPEN_SECONDS,
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle: open_seconds_handle,
+            ..Default::default()
+        },
+    ])
+}
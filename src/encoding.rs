@@ -0,0 +1,205 @@
+//! Binary layout of characteristic values.
+//!
+//! Every number is little endian. Notify payloads start with the header
+//! from [payload](crate::payload): sequence number `u16`, then Unix
+//! timestamp in seconds `u32`. Reads return the value alone, which is, per
+//! characteristic:
+//!
+//! | Characteristic       | Value                                                   |
+//! |----------------------|---------------------------------------------------------|
+//! | `TEMPERATURE`        | `f32` °C                                                |
+//! | `CPU_LOAD`           | `f32` fraction of time busy                             |
+//! | `RAM_USAGE`          | `u64` used bytes, `u64` total bytes                     |
+//! | `UPTIME`             | `u64` minutes                                           |
+//! | `DISK_USAGE`         | `u64` used bytes, `u64` total bytes, `f32` percent used |
+//! | `CPU_CORE_LOAD`      | `u8` core count, then `f32` busy fraction per core      |
+//! | `LOAD_AVERAGE`       | `f32` 1, 5 and 15 minute averages                       |
+//! | `SWAP_USAGE`         | `u64` used bytes, `u64` total bytes                     |
+//! | `NETWORK_THROUGHPUT` | `u64` received, `u64` transmitted bytes per second      |
+//! | `GPU_TEMPERATURE`    | `f32` °C                                                |
+//! | `GPU_CLOCK`          | `u64` Hz                                                |
+//! | `THROTTLED`          | `u32` `get_throttled` bitfield                          |
+//! | `CPU_FREQUENCY`      | `u64` Hz                                                |
+//!
+//! Structured values (Wi-Fi, addresses, process lists, ...) are CBOR, as
+//! documented on the function creating their characteristic.
+
+use std::time::Duration;
+
+/// Encoding of a value sent in a notification or indication
+pub trait Encode {
+    fn encode(&self) -> Vec<u8>;
+}
+
+impl Encode for [u8] {
+    fn encode(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl Encode for Vec<u8> {
+    fn encode(&self) -> Vec<u8> {
+        self.clone()
+    }
+}
+
+impl Encode for str {
+    fn encode(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+macro_rules! encode_le {
+    ($($number:ty),*) => {
+        $(
+            impl Encode for $number {
+                fn encode(&self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+            }
+        )*
+    };
+}
+
+encode_le!(u8, u16, u32, u64, i16, f32);
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode(&self) -> Vec<u8> {
+        (**self).encode()
+    }
+}
+
+/// Wire size of `RAM_USAGE` and `SWAP_USAGE`
+pub const USAGE_LEN: usize = 16;
+const _: () = assert!(USAGE_LEN == 2 * size_of::<u64>());
+
+/// Wire size of `DISK_USAGE`
+pub const DISK_USAGE_LEN: usize = 20;
+const _: () = assert!(DISK_USAGE_LEN == USAGE_LEN + size_of::<f32>());
+
+/// Wire size of `LOAD_AVERAGE`
+pub const LOAD_AVERAGE_LEN: usize = 12;
+const _: () = assert!(LOAD_AVERAGE_LEN == 3 * size_of::<f32>());
+
+/// Wire size of `NETWORK_THROUGHPUT`
+pub const THROUGHPUT_LEN: usize = 16;
+const _: () = assert!(THROUGHPUT_LEN == 2 * size_of::<u64>());
+
+/// Concatenates the encoded `fields` of a fixed size value.
+///
+/// Panics when they do not fill exactly `N` bytes.
+pub fn fixed<const N: usize>(fields: &[&[u8]]) -> [u8; N] {
+    fields
+        .concat()
+        .try_into()
+        .unwrap_or_else(|fields: Vec<u8>| panic!("{} bytes of fields for {N}", fields.len()))
+}
+
+/// `RAM_USAGE` and `SWAP_USAGE`: used and total bytes.
+pub fn usage(used: u64, total: u64) -> [u8; USAGE_LEN] {
+    fixed(&[&used.to_le_bytes(), &total.to_le_bytes()])
+}
+
+/// `DISK_USAGE`: used and total bytes, then the percentage used.
+pub fn disk_usage(used: u64, total: u64, percent: f32) -> [u8; DISK_USAGE_LEN] {
+    fixed(&[&usage(used, total), &percent.to_le_bytes()])
+}
+
+/// `CPU_CORE_LOAD`: core count, then the busy fraction of each core.
+pub fn core_loads(loads: &[f32]) -> Vec<u8> {
+    let mut encoded = vec![loads.len() as u8];
+    for load in loads {
+        encoded.extend(load.encode());
+    }
+    encoded
+}
+
+/// `LOAD_AVERAGE`: 1, 5 and 15 minute averages.
+pub fn load_averages(one: f32, five: f32, fifteen: f32) -> [u8; LOAD_AVERAGE_LEN] {
+    fixed(&[
+        &one.to_le_bytes(),
+        &five.to_le_bytes(),
+        &fifteen.to_le_bytes(),
+    ])
+}
+
+/// `NETWORK_THROUGHPUT`: received and transmitted bytes per second.
+pub fn throughput(rx_per_sec: u64, tx_per_sec: u64) -> [u8; THROUGHPUT_LEN] {
+    fixed(&[&rx_per_sec.to_le_bytes(), &tx_per_sec.to_le_bytes()])
+}
+
+/// `UPTIME`: whole minutes.
+pub fn uptime(uptime: Duration) -> Vec<u8> {
+    (uptime.as_secs() / 60).encode()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scalars_are_little_endian() {
+        // TEMPERATURE, CPU_LOAD and GPU_TEMPERATURE
+        assert_eq!(1.5f32.encode(), [0x00, 0x00, 0xc0, 0x3f]);
+        // THROTTLED
+        assert_eq!(0x0005_0001u32.encode(), [0x01, 0x00, 0x05, 0x00]);
+        // GPU_CLOCK and CPU_FREQUENCY
+        assert_eq!(
+            1_500_000_000u64.encode(),
+            [0x00, 0x2f, 0x68, 0x59, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn memory_usage_is_used_then_total() {
+        assert_eq!(
+            usage(0x0102, 0x0304),
+            [0x02, 0x01, 0, 0, 0, 0, 0, 0, 0x04, 0x03, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn uptime_is_whole_minutes() {
+        assert_eq!(uptime(Duration::from_secs(59)), [0; 8]);
+        assert_eq!(
+            uptime(Duration::from_secs(3 * 60 + 59)),
+            [3, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            uptime(Duration::from_secs(0x0100 * 60)),
+            [0x00, 0x01, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn disk_usage_appends_percent() {
+        let encoded = disk_usage(1, 2, 50.0);
+        assert_eq!(encoded[..16], usage(1, 2));
+        assert_eq!(encoded[16..], [0x00, 0x00, 0x48, 0x42]);
+    }
+
+    #[test]
+    fn core_loads_lead_with_count() {
+        assert_eq!(
+            core_loads(&[0.5, 1.0]),
+            [2, 0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x80, 0x3f]
+        );
+        assert_eq!(core_loads(&[]), [0]);
+    }
+
+    #[test]
+    fn load_averages_in_order() {
+        assert_eq!(
+            load_averages(0.5, 1.0, 1.5),
+            [0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x80, 0x3f, 0x00, 0x00, 0xc0, 0x3f]
+        );
+    }
+
+    #[test]
+    fn throughput_is_rx_then_tx() {
+        assert_eq!(
+            throughput(1, 0x0200),
+            [1, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x02, 0, 0, 0, 0, 0, 0]
+        );
+    }
+}
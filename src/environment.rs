@@ -0,0 +1,243 @@
+use crate::retry::RetryPolicies;
+use bluer::gatt::local::{
+    characteristic_control, Characteristic, CharacteristicNotify, CharacteristicNotifyMethod,
+};
+use rppal::i2c::I2c;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use systemstat::{Platform, System};
+
+/// SDO pin low and high
+const ADDRESSES: [u16; 2] = [0x76, 0x77];
+
+const REG_CHIP_ID: u8 = 0xD0;
+const REG_CALIB_TP: u8 = 0x88;
+const REG_CALIB_H1: u8 = 0xA1;
+const REG_CALIB_H2: u8 = 0xE1;
+const REG_CTRL_HUM: u8 = 0xF2;
+const REG_CTRL_MEAS: u8 = 0xF4;
+const REG_DATA: u8 = 0xF7;
+
+const CHIP_ID_BMP280: u8 = 0x58;
+const CHIP_ID_BME280: u8 = 0x60;
+
+/// Humidity oversampling x1
+const CTRL_HUM_X1: u8 = 0x01;
+/// Temperature and pressure oversampling x1, normal mode
+const CTRL_MEAS_NORMAL: u8 = 0x27;
+
+/// Factory calibration, see section 4.2.2 of the BME280 datasheet
+struct Calibration {
+    t1: f64,
+    t2: f64,
+    t3: f64,
+    p: [f64; 9],
+    /// Only present on the BME280
+    h: Option<[f64; 6]>,
+}
+
+struct Reading {
+    temperature: f64,
+    pressure_pa: f64,
+    humidity_pct: Option<f64>,
+}
+
+struct Bmx280 {
+    i2c: I2c,
+    calibration: Calibration,
+}
+
+impl Bmx280 {
+    /// Probes both addresses for a BMP280 or BME280 and configures it.
+    fn detect() -> rppal::i2c::Result<Option<Bmx280>> {
+        let mut i2c = I2c::new()?;
+        for address in ADDRESSES {
+            i2c.set_slave_address(address)?;
+            let Ok(chip_id) = i2c.smbus_read_byte(REG_CHIP_ID) else {
+                continue;
+            };
+            if chip_id != CHIP_ID_BMP280 && chip_id != CHIP_ID_BME280 {
+                continue;
+            }
+
+            let mut tp = [0u8; 24];
+            i2c.block_read(REG_CALIB_TP, &mut tp)?;
+            let u = |i: usize| u16::from_le_bytes([tp[i], tp[i + 1]]) as f64;
+            let s = |i: usize| i16::from_le_bytes([tp[i], tp[i + 1]]) as f64;
+            let mut p = [u(6), 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+            for (n, p) in p.iter_mut().enumerate().skip(1) {
+                *p = s(6 + 2 * n);
+            }
+
+            let h = if chip_id == CHIP_ID_BME280 {
+                let h1 = i2c.smbus_read_byte(REG_CALIB_H1)?;
+                let mut e = [0u8; 7];
+                i2c.block_read(REG_CALIB_H2, &mut e)?;
+                let h4 = ((e[3] as i8 as i16) << 4) | (e[4] & 0x0F) as i16;
+                let h5 = ((e[5] as i8 as i16) << 4) | (e[4] >> 4) as i16;
+                i2c.smbus_write_byte(REG_CTRL_HUM, CTRL_HUM_X1)?;
+                Some([
+                    h1 as f64,
+                    i16::from_le_bytes([e[0], e[1]]) as f64,
+                    e[2] as f64,
+                    h4 as f64,
+                    h5 as f64,
+                    e[6] as i8 as f64,
+                ])
+            } else {
+                None
+            };
+            i2c.smbus_write_byte(REG_CTRL_MEAS, CTRL_MEAS_NORMAL)?;
+
+            return Ok(Some(Bmx280 {
+                i2c,
+                calibration: Calibration {
+                    t1: u(0),
+                    t2: s(2),
+                    t3: s(4),
+                    p,
+                    h,
+                },
+            }));
+        }
+        Ok(None)
+    }
+
+    fn is_bme280(&self) -> bool {
+        self.calibration.h.is_some()
+    }
+
+    /// Reads and compensates a measurement using the datasheet's floating point formulas.
+    fn read(&mut self) -> rppal::i2c::Result<Reading> {
+        let mut data = [0u8; 8];
+        self.i2c.block_read(REG_DATA, &mut data)?;
+        let adc_p = ((data[0] as u32) << 12 | (data[1] as u32) << 4 | (data[2] as u32) >> 4) as f64;
+        let adc_t = ((data[3] as u32) << 12 | (data[4] as u32) << 4 | (data[5] as u32) >> 4) as f64;
+        let adc_h = u16::from_be_bytes([data[6], data[7]]) as f64;
+        let c = &self.calibration;
+
+        let var1 = (adc_t / 16384.0 - c.t1 / 1024.0) * c.t2;
+        let var2 = (adc_t / 131072.0 - c.t1 / 8192.0).powi(2) * c.t3;
+        let t_fine = var1 + var2;
+        let temperature = t_fine / 5120.0;
+
+        let p = &c.p;
+        let var1 = t_fine / 2.0 - 64000.0;
+        let var2 = var1 * var1 * p[5] / 32768.0 + var1 * p[4] * 2.0;
+        let var2 = var2 / 4.0 + p[3] * 65536.0;
+        let var1 = (p[2] * var1 * var1 / 524288.0 + p[1] * var1) / 524288.0;
+        let var1 = (1.0 + var1 / 32768.0) * p[0];
+        let pressure_pa = if var1 == 0.0 {
+            0.0
+        } else {
+            let pressure = (1048576.0 - adc_p - var2 / 4096.0) * 6250.0 / var1;
+            let var1 = p[8] * pressure * pressure / 2147483648.0;
+            let var2 = pressure * p[7] / 32768.0;
+            pressure + (var1 + var2 + p[6]) / 16.0
+        };
+
+        let humidity_pct = c.h.map(|h| {
+            let var = t_fine - 76800.0;
+            let var = (adc_h - (h[3] * 64.0 + h[4] / 16384.0 * var))
+                * (h[1] / 65536.0
+                    * (1.0 + h[5] / 67108864.0 * var * (1.0 + h[2] / 67108864.0 * var)));
+            (var * (1.0 - h[0] * var / 524288.0)).clamp(0.0, 100.0)
+        });
+
+        Ok(Reading {
+            temperature,
+            pressure_pa,
+            humidity_pct,
+        })
+    }
+}
+
+/// Creates `PRESSURE_PA` and, on a BME280, `HUMIDITY_PCT`.
+///
+/// Returns no characteristics when no sensor is connected.
+pub fn characteristics(retry: &RetryPolicies) -> Vec<Characteristic> {
+    let mut sensor = match Bmx280::detect() {
+        Ok(Some(sensor)) => sensor,
+        Ok(None) => {
+            eprintln!("No BMP280/BME280 found, skipping environment characteristics");
+            return Vec::new();
+        }
+        Err(err) => {
+            eprintln!("Environment sensor unavailable: {err}");
+            return Vec::new();
+        }
+    };
+    let is_bme280 = sensor.is_bme280();
+    let humidity = Arc::new(Mutex::new(None::<u16>));
+    let sys = System::new();
+
+    let (pressure_control, pressure_handle) = characteristic_control();
+    let latest_humidity = humidity.clone();
+    crate::tasks::spawn(
+        "pressure",
+        crate::periodic::notify(
+            pressure_control,
+            "pressure",
+            Duration::from_secs(1),
+            retry.get("pressure_pa"),
+            move || {
+                let reading = match sensor.read() {
+                    Ok(reading) => reading,
+                    Err(err) => {
+                        eprintln!("Reading environment sensor failed: {err}");
+                        return None;
+                    }
+                };
+                // Cross-check against the SoC, a sensor close to the board reads warm
+                if let Ok(cpu_temperature) = sys.cpu_temp() {
+                    println!(
+                        "Sensor TEMP is: {:.2}C (CPU {cpu_temperature:.2}C)",
+                        reading.temperature
+                    );
+                }
+                *latest_humidity.lock().unwrap() =
+                    reading.humidity_pct.map(|pct| (pct * 100.0).round() as u16);
+                Some((reading.pressure_pa.round() as u32).to_le_bytes().to_vec())
+            },
+        ),
+    );
+
+    let mut characteristics = vec![Characteristic {
+        uuid: crate::PRESSURE_PA,
+        notify: Some(CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Io,
+            ..Default::default()
+        }),
+        control_handle: pressure_handle,
+        ..Default::default()
+    }];
+
+    if is_bme280 {
+        let (humidity_control, humidity_handle) = characteristic_control();
+        crate::tasks::spawn(
+            "humidity",
+            crate::periodic::notify(
+                humidity_control,
+                "humidity",
+                Duration::from_secs(1),
+                retry.get("humidity_pct"),
+                move || Some(humidity.lock().unwrap().as_ref()?.to_le_bytes().to_vec()),
+            ),
+        );
+        characteristics.push(Characteristic {
+            uuid: crate::HUMIDITY_PCT,
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle: humidity_handle,
+            ..Default::default()
+        });
+    }
+
+    characteristics
+}
@@ -0,0 +1,109 @@
+use crate::{
+    config::{self, Schedule},
+    metrics::InterpolatedMetrics,
+    retry::RetryPolicy,
+};
+use bluer::{
+    gatt::{
+        local::{
+            characteristic_control, Characteristic, CharacteristicControl,
+            CharacteristicControlEvent, CharacteristicNotify, CharacteristicNotifyMethod,
+            CharacteristicRead, ReqError, Service,
+        },
+        CharacteristicWriter,
+    },
+    id,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use std::sync::{Arc, Mutex};
+use tokio::{sync::watch, time};
+
+/// Latest CPU temperature as SIG Temperature, `sint16` LE in 0.01 °C.
+fn temperature(metrics: &Mutex<InterpolatedMetrics>) -> Option<Vec<u8>> {
+    let [_, cpu_temp, _, _] = metrics.lock().unwrap().latest();
+    let hundredths = (cpu_temp? * 100.0).round() as i16;
+    Some(hundredths.to_le_bytes().to_vec())
+}
+
+async fn serve(
+    control: CharacteristicControl,
+    metrics: Arc<Mutex<InterpolatedMetrics>>,
+    mut schedule: watch::Receiver<Schedule>,
+    retry: RetryPolicy,
+) {
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut interval = time::interval(schedule.borrow_and_update().interval("temperature"));
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting ESS temperature notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "ESS temperature");
+                        writer_opt = Some(notifier);
+                        // Notify the current temperature without waiting for the period
+                        interval.reset_immediately();
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Ok(()) = schedule.changed() => {
+                config::retime(&mut interval, schedule.borrow_and_update().interval("temperature"));
+            },
+            _ = interval.tick() => {
+                let (Some(writer), Some(value)) = (&mut writer_opt, temperature(&metrics)) else {
+                    continue;
+                };
+                // SIG format, without the sequence header of custom characteristics
+                if retry.write_all(writer, &value).await.is_err() {
+                    writer_opt = None;
+                }
+            }
+        }
+    }
+}
+
+/// Creates the Bluetooth SIG Environmental Sensing Service (0x181A) with the
+/// CPU temperature as its Temperature characteristic, notified on the
+/// `schedule` interval of `temperature`.
+pub fn service(
+    metrics: Arc<Mutex<InterpolatedMetrics>>,
+    schedule: watch::Receiver<Schedule>,
+    retry: RetryPolicy,
+) -> Service {
+    let (control, control_handle) = characteristic_control();
+    crate::tasks::spawn(
+        "environmental-sensing",
+        serve(control, metrics.clone(), schedule, retry),
+    );
+
+    Service {
+        uuid: id::Service::EnvironmentalSensing.into(),
+        primary: true,
+        characteristics: vec![Characteristic {
+            uuid: id::Characteristic::Temperature.into(),
+            read: Some(CharacteristicRead {
+                read: true,
+                fun: Box::new(move |_req| {
+                    let result = temperature(&metrics).ok_or(ReqError::Failed);
+                    async move { result }.boxed()
+                }),
+                ..Default::default()
+            }),
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle,
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::{
+    gatt::{
+        local::{
+            characteristic_control, Characteristic, CharacteristicControl,
+            CharacteristicControlEvent, CharacteristicNotify, CharacteristicNotifyMethod,
+            CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+        },
+        CharacteristicWriter,
+    },
+    Adapter, Address,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use systemstat::{Platform, System};
+use tokio::{process::Command, sync::mpsc, time};
+
+/// Longest `BROADCAST` message in bytes
+const MAX_BROADCAST_LEN: usize = 100;
+
+/// Minimum time between two broadcasts
+const BROADCAST_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Events kept while nobody is subscribed
+const QUEUE_LEN: usize = 10;
+
+/// How often the Wi-Fi connection is checked
+const NETWORK_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// An uptime below this at startup means the server came up with the system
+const BOOT_UPTIME: Duration = Duration::from_secs(300);
+
+/// Significant state change, encoded as a CBOR map tagged by `event`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event")]
+pub enum SystemEvent {
+    NetworkConnected {
+        ssid: String,
+    },
+    NetworkDisconnected,
+    /// `mtu` is the ATT MTU the central negotiated, 23 when it did not
+    /// subscribe to anything shortly after connecting
+    BleClientConnected {
+        addr: Address,
+        mtu: u16,
+    },
+    BleClientDisconnected {
+        addr: Address,
+    },
+    ThresholdBreached {
+        metric: String,
+        value: f32,
+    },
+    Rebooted,
+    Broadcast {
+        message: String,
+    },
+    // No `OtaCompleted` until the server can install updates: there is no
+    // `FIRMWARE_OTA` characteristic that could publish it
+}
+
+static PUBLISHER: OnceLock<mpsc::UnboundedSender<SystemEvent>> = OnceLock::new();
+
+/// Notifies `event` on `SYSTEM_EVENTS` right away, or once a client subscribes.
+pub fn publish(event: SystemEvent) {
+    if let Some(publisher) = PUBLISHER.get() {
+        let _ = publisher.send(event);
+    }
+}
+
+/// SSID of the active Wi-Fi connection, if any.
+async fn active_ssid() -> std::io::Result<Option<String>> {
+    let output = Command::new("nmcli")
+        .args(["-t", "-f", "ACTIVE,SSID", "device", "wifi"])
+        .output()
+        .await?;
+    let ssid = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("yes:"))
+        .map(|ssid| ssid.replace("\\:", ":"));
+    Ok(ssid)
+}
+
+async fn serve(
+    control: CharacteristicControl,
+    mut events: mpsc::UnboundedReceiver<SystemEvent>,
+    retry: RetryPolicy,
+) {
+    let mut writers: Vec<CharacteristicWriter> = Vec::new();
+    let mut queue: VecDeque<SystemEvent> = VecDeque::with_capacity(QUEUE_LEN);
+    let mut sequence = NotifySequence::new("system events");
+    let mut ssid: Option<String> = None;
+    let mut interval = time::interval(NETWORK_POLL_INTERVAL);
+    pin_mut!(control);
+
+    if System::new()
+        .uptime()
+        .is_ok_and(|uptime| uptime < BOOT_UPTIME)
+    {
+        queue.push_back(SystemEvent::Rebooted);
+    }
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting system event notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "system event");
+                        writers.push(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Some(event) = events.recv() => {
+                println!("System event: {event:?}");
+                if queue.len() == QUEUE_LEN {
+                    queue.pop_front();
+                }
+                queue.push_back(event);
+            },
+            _ = interval.tick() => {
+                let active = match active_ssid().await {
+                    Ok(active) => active,
+                    Err(err) => {
+                        eprintln!("Checking Wi-Fi connection failed: {err}");
+                        continue;
+                    }
+                };
+                if active != ssid {
+                    let event = match &active {
+                        Some(active) => SystemEvent::NetworkConnected { ssid: active.clone() },
+                        None => SystemEvent::NetworkDisconnected,
+                    };
+                    ssid = active;
+                    publish(event);
+                }
+            }
+        }
+
+        // Delivered in order to every subscriber; an event no subscriber
+        // received stays queued for the next one
+        while let (false, Some(event)) = (writers.is_empty(), queue.front()) {
+            let mut payload = Vec::new();
+            if let Err(err) = ciborium::into_writer(event, &mut payload) {
+                eprintln!("Encoding system event failed: {err}");
+                queue.pop_front();
+                continue;
+            }
+            let payload = sequence.stamp(&payload);
+            let mut remaining = Vec::with_capacity(writers.len());
+            for mut writer in writers.drain(..) {
+                if retry.notify(&mut writer, &payload).await.is_ok() {
+                    remaining.push(writer);
+                }
+            }
+            writers = remaining;
+            if !writers.is_empty() {
+                queue.pop_front();
+            }
+        }
+    }
+}
+
+/// Creates the `SYSTEM_EVENTS` characteristic.
+///
+/// Each event published by other modules is notified as a CBOR map with an
+/// `event` name and its fields. Up to ten events are kept while no client is
+/// subscribed.
+pub fn characteristic(retry: &RetryPolicies) -> Characteristic {
+    let (control, control_handle) = characteristic_control();
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+    let _ = PUBLISHER.set(events_tx);
+    crate::tasks::spawn(
+        "system-events",
+        serve(control, events_rx, retry.get("system_events")),
+    );
+
+    Characteristic {
+        uuid: crate::SYSTEM_EVENTS,
+        notify: Some(CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Io,
+            ..Default::default()
+        }),
+        control_handle,
+        ..Default::default()
+    }
+}
+
+/// Creates the `BROADCAST` characteristic.
+///
+/// Paired clients write a UTF-8 message of up to 100 bytes, which is sent as
+/// a `Broadcast` event to every `SYSTEM_EVENTS` subscriber. One broadcast is
+/// accepted per minute.
+pub fn broadcast_characteristic(adapter: Adapter) -> Characteristic {
+    let last_broadcast: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(None));
+
+    Characteristic {
+        uuid: crate::BROADCAST,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, req| {
+                let adapter = adapter.clone();
+                let last_broadcast = last_broadcast.clone();
+                async move {
+                    if value.len() > MAX_BROADCAST_LEN {
+                        return Err(ReqError::InvalidValueLength);
+                    }
+                    let message = String::from_utf8(value).map_err(|_| ReqError::NotSupported)?;
+                    let paired = match adapter.device(req.device_address) {
+                        Ok(device) => device.is_paired().await.unwrap_or(false),
+                        Err(_) => false,
+                    };
+                    if !paired {
+                        return Err(ReqError::NotAuthorized);
+                    }
+                    {
+                        let mut last_broadcast = last_broadcast.lock().unwrap();
+                        if last_broadcast.is_some_and(|last| last.elapsed() < BROADCAST_INTERVAL) {
+                            return Err(ReqError::InProgress);
+                        }
+                        *last_broadcast = Some(Instant::now());
+                    }
+                    println!("Broadcast from {}: {message}", req.device_address);
+                    publish(SystemEvent::Broadcast { message });
+                    Ok(())
+                }
+                .boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,111 @@
+use crate::response::Responder;
+use bluer::{
+    gatt::local::{Characteristic, CharacteristicWrite, CharacteristicWriteMethod, ReqError},
+    Address,
+};
+use futures::FutureExt;
+use std::process::Stdio;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+    sync::mpsc,
+};
+
+const MORE_DATA: u8 = 0x01;
+const DONE: u8 = 0x00;
+
+/// Bytes of the `MORE_DATA` or `DONE` flag leading each frame
+const FLAG_LEN: usize = 1;
+
+/// Builds a frame of `flag` and `line`, cut to fit a single notification.
+fn frame(flag: u8, line: &str) -> Vec<u8> {
+    let mut end = line
+        .len()
+        .min(crate::response::max_payload_len() - FLAG_LEN);
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut frame = vec![flag];
+    frame.extend_from_slice(&line.as_bytes()[..end]);
+    frame
+}
+
+/// Runs `command` and streams its stdout line by line, returning the exit code.
+///
+/// Each line is held back until the next one arrives, so the last frame can
+/// carry the `DONE` flag.
+async fn run(command: &str, responder: &Responder) -> std::io::Result<i32> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut lines = BufReader::new(stdout).lines();
+
+    let mut pending: Option<String> = None;
+    let result = async {
+        while let Some(line) = lines.next_line().await? {
+            if let Some(previous) = pending.replace(line) {
+                let _ = responder.send(frame(MORE_DATA, &previous)).await;
+            }
+        }
+        std::io::Result::Ok(())
+    }
+    .await;
+    let _ = responder
+        .send(frame(DONE, pending.as_deref().unwrap_or_default()))
+        .await;
+    result?;
+
+    let status = child.wait().await?;
+    println!("Command `{command}` finished with {status}");
+    Ok(status.code().unwrap_or(-1))
+}
+
+/// A whitelisted command key, its command line and the requesting peer
+type Request = (String, &'static str, Address);
+
+async fn serve(mut requests: mpsc::Receiver<Request>, responder: Responder) {
+    while let Some((key, command, peer)) = requests.recv().await {
+        let exit_code = match run(command, &responder).await {
+            Ok(exit_code) => exit_code,
+            Err(err) => {
+                eprintln!("Command `{command}` failed: {err}");
+                -1
+            }
+        };
+        crate::commands::record(&key, exit_code, peer);
+        crate::peer_stats::record_command(peer);
+    }
+}
+
+/// Creates the `EXEC_STREAM` characteristic.
+///
+/// Accepts a whitelisted command key and streams the command's output on
+/// `WRITE_REQUEST_RESPONSE`, one line per frame behind a `MORE_DATA` or
+/// `DONE` flag byte.
+pub fn characteristic(responder: Responder) -> Characteristic {
+    let (request_tx, request_rx) = mpsc::channel(4);
+    crate::tasks::spawn("exec-stream", serve(request_rx, responder));
+
+    Characteristic {
+        uuid: crate::EXEC_STREAM,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, req| {
+                let key = std::str::from_utf8(&value).unwrap_or_default().trim();
+                let result = match crate::commands::lookup(key) {
+                    Some(command) => request_tx
+                        .try_send((key.to_string(), command, req.device_address))
+                        .map_err(|_| ReqError::InProgress),
+                    None => Err(ReqError::NotPermitted),
+                };
+                async move { result }.boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,294 @@
+//! Construction of the GATT application served by [run](crate::run).
+
+use crate::{
+    addresses, alerts, allowlist, auth, bt_pair,
+    cli::{self, Args},
+    commands,
+    config::Config,
+    descriptors, device_info, disk_health, disk_wear, environmental_sensing, events, exec_stream,
+    hw_random, instance, locale, metrics,
+    metrics::InterpolatedMetrics,
+    negotiate, pairing_code, passkey_agent, payload, peer_stats, peers, processes, provider, raspi,
+    response, security, snapshot, stats, stress, system_info, system_metrics, tasks, temp_map,
+    timezone, uptime, voltage, wifi_connect, wifi_scan, wifi_status,
+};
+use bluer::{
+    agent::AgentHandle,
+    gatt::local::{Application, Characteristic, Service},
+    Adapter, Session,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+/// Handles to keep until the application is removed
+pub struct Handles {
+    _agent: Option<AgentHandle>,
+    /// Characteristics taken out by [Config::remove_disabled]
+    _disabled: Vec<Characteristic>,
+}
+
+/// Creates the primary service with uuid `service_uuid` and the standard
+/// services, starting the tasks behind their characteristics.
+///
+/// The application is ready to serve: disabled characteristics are removed,
+/// then descriptors, security, write restrictions, aliases and instance
+/// remapping are applied in that order.
+pub async fn application(
+    args: &Args,
+    config: &Config,
+    session: &Session,
+    adapter: &Adapter,
+    service_uuid: Uuid,
+) -> bluer::Result<(Application, Handles)> {
+    #[cfg(not(feature = "pipe-bridge"))]
+    let client_writes = None;
+    #[cfg(feature = "pipe-bridge")]
+    let (client_writes, pipe_writes) = match &args.pipe_path {
+        Some(_) => {
+            let (client_writes, pipe_writes) = tokio::sync::mpsc::channel(16);
+            (Some(client_writes), Some(pipe_writes))
+        }
+        None => (None, None),
+    };
+    let latencies = stats::Latencies::default();
+    let gate = config
+        .control_token
+        .as_deref()
+        .map(|token| Arc::new(auth::Gate::new(token)));
+    let (schedule_tx, schedule_changes) = watch::channel(config.schedule(args.update_interval));
+    let retry = config.retry_policies();
+    let (response_characteristic, responder) = response::characteristic(
+        client_writes,
+        latencies.clone(),
+        gate.clone(),
+        schedule_tx,
+        &retry,
+    );
+    #[cfg(feature = "pipe-bridge")]
+    if let (Some(path), Some(pipe_writes)) = (&args.pipe_path, pipe_writes) {
+        if let Err(err) = crate::pipe_bridge::start(path, pipe_writes, responder.clone()) {
+            eprintln!("Pipe bridge unavailable: {err}");
+        }
+    }
+    let metrics = Arc::new(Mutex::new(InterpolatedMetrics::default()));
+    let mut providers = provider::Registry::new();
+    providers
+        .register(system_metrics::CpuLoad::new(metrics.clone()))
+        .register(system_metrics::CpuTemperature::new(
+            metrics.clone(),
+            args.temperature_alert,
+        ))
+        .register(system_metrics::MemoryUsage::new(metrics.clone()))
+        .register(system_metrics::Uptime::new(metrics.clone()))
+        .register(system_metrics::DiskUsage)
+        .register(system_metrics::CpuCoreLoad::default())
+        .register(system_metrics::LoadAverage)
+        .register(system_metrics::SwapUsage)
+        .register(system_metrics::NetworkThroughput::new(
+            args.network_interface
+                .clone()
+                .unwrap_or_else(cli::default_network_interface),
+        ))
+        .register(system_metrics::CpuFrequency);
+    let mut characteristics = providers.characteristics(config, &schedule_changes);
+    characteristics.extend([
+        // Notify payload layout version
+        payload::version_characteristic(),
+        // Capability exchange
+        negotiate::characteristic(adapter.clone(), &retry),
+        // Peer MTU changes
+        peers::characteristic(adapter.clone(), &retry),
+        // Server-initiated disconnection of a peer
+        peers::disconnect_characteristic(adapter.clone()),
+        // Message to all subscribed clients
+        events::broadcast_characteristic(adapter.clone()),
+        // Echo and write request results
+        response_characteristic,
+        // Root drive health
+        disk_health::characteristic(),
+        // Background task states
+        tasks::characteristic(),
+        // Stale system metrics
+        metrics::error_detail_characteristic(metrics.clone()),
+        // Metrics whose source keeps failing
+        metrics::degraded_characteristic(metrics.clone()),
+        // Freezes metric values for client testing
+        metrics::lock_characteristic(metrics.clone()),
+        // Notify order of the metrics
+        metrics::priority_characteristic(metrics.clone()),
+        // Uptime for display without client side formatting
+        uptime::characteristic(),
+        locale::characteristic(),
+        events::characteristic(&retry),
+        temp_map::characteristic(&retry),
+        stress::characteristic(),
+        hw_random::characteristic(),
+        commands::history_characteristic(),
+        peer_stats::characteristic(),
+    ]);
+    characteristics.extend(snapshot::characteristics(
+        metrics.clone(),
+        config.snapshot_format,
+        schedule_changes.clone(),
+        &retry,
+    ));
+    characteristics.extend(stats::characteristics(latencies));
+    characteristics.extend(raspi::characteristics(&retry));
+    characteristics.push(metrics::query_characteristic(
+        metrics.clone(),
+        responder.clone(),
+    ));
+    if args.test_mode {
+        characteristics.push(metrics::inject_characteristic(metrics.clone()));
+    }
+    characteristics.extend(wifi_scan::characteristics(&retry));
+    characteristics.push(wifi_status::characteristic(&retry));
+    characteristics.push(addresses::characteristic(&retry));
+    characteristics.push(system_info::characteristic());
+    let (connected_ssid, ssid_changes) = watch::channel(None);
+    if args.auto_timezone {
+        tasks::spawn_restarting("timezone", move || {
+            timezone::auto_detect(ssid_changes.clone())
+        });
+    }
+    characteristics.push(wifi_connect::characteristic(
+        responder.clone(),
+        connected_ssid,
+    ));
+    characteristics.push(bt_pair::characteristic(adapter.clone(), responder.clone()));
+    let agent_handle = if args.require_pairing {
+        let (agent_handle, characteristic) = pairing_code::register(session).await?;
+        characteristics.push(characteristic);
+        Some(agent_handle)
+    } else if args.pairing_agent {
+        Some(passkey_agent::register(session, args.passkey).await?)
+    } else {
+        None
+    };
+    characteristics.push(exec_stream::characteristic(responder.clone()));
+    characteristics.extend(processes::characteristics(responder.clone()));
+    characteristics.push(alerts::characteristic());
+    characteristics.push(disk_wear::characteristic(args.disk_wear_alert, &retry));
+    match voltage::characteristic() {
+        Ok(characteristic) => characteristics.push(characteristic),
+        Err(err) => eprintln!("Voltage history unavailable: {err}"),
+    }
+    #[cfg(feature = "status-led")]
+    characteristics.extend(crate::status_led::characteristics().await);
+    #[cfg(feature = "ambient-sensor")]
+    characteristics.push(crate::ambient_light::characteristic(&retry));
+    #[cfg(feature = "environment-sensor")]
+    characteristics.extend(crate::environment::characteristics(&retry));
+    #[cfg(feature = "co2-sensor")]
+    characteristics.extend(crate::co2::characteristics(args.co2_alert_ppm, &retry));
+    #[cfg(feature = "pir-sensor")]
+    if let Some(pin) = args.pir_gpio_pin {
+        match crate::pir::characteristics(pin, &retry) {
+            Ok(pir_characteristics) => characteristics.extend(pir_characteristics),
+            Err(err) => eprintln!("PIR sensor unavailable: {err}"),
+        }
+    }
+    #[cfg(feature = "door-sensor")]
+    if let Some(pin) = args.door_gpio_pin {
+        match crate::door::characteristics(pin, &retry) {
+            Ok(door_characteristics) => characteristics.extend(door_characteristics),
+            Err(err) => eprintln!("Door sensor unavailable: {err}"),
+        }
+    }
+    #[cfg(feature = "audio-alert")]
+    if let Some(pin) = args.buzzer_gpio_pin {
+        match crate::buzzer::characteristic(pin) {
+            Ok(characteristic) => characteristics.push(characteristic),
+            Err(err) => eprintln!("Buzzer unavailable: {err}"),
+        }
+    }
+    #[cfg(feature = "gpio")]
+    if !args.power_cycle_gpio_pins.is_empty() {
+        match crate::power_cycle::characteristic(&args.power_cycle_gpio_pins) {
+            Ok(characteristic) => characteristics.push(characteristic),
+            Err(err) => eprintln!("Power cycle relays unavailable: {err}"),
+        }
+    }
+    #[cfg(feature = "obd2")]
+    match crate::obd2::characteristic(responder.clone()).await {
+        Ok(characteristic) => characteristics.push(characteristic),
+        Err(err) => eprintln!("OBD-II adapter unavailable: {err}"),
+    }
+    #[cfg(feature = "posix-mq")]
+    match crate::mqueue::characteristics(&retry) {
+        Ok(mq_characteristics) => characteristics.extend(mq_characteristics),
+        Err(err) => eprintln!("POSIX message queues unavailable: {err}"),
+    }
+    #[cfg(feature = "traffic-control")]
+    characteristics.push(crate::traffic_control::characteristic());
+    #[cfg(feature = "cron-bridge")]
+    characteristics.extend(crate::cron::characteristics());
+    #[cfg(feature = "screenshot")]
+    characteristics.push(crate::screenshot::characteristic(responder.clone(), &retry));
+    #[cfg(feature = "cert-provisioning")]
+    characteristics.extend(crate::cert::characteristics(&retry));
+    #[cfg(feature = "kmsg")]
+    match crate::kmsg::characteristic(&retry) {
+        Ok(characteristic) => characteristics.push(characteristic),
+        Err(err) => eprintln!("Kernel messages unavailable: {err}"),
+    }
+
+    let mut app = Application {
+        services: vec![
+            Service {
+                uuid: service_uuid,
+                primary: true,
+                characteristics,
+                ..Default::default()
+            },
+            device_info::service(),
+            environmental_sensing::service(
+                metrics.clone(),
+                schedule_changes.clone(),
+                retry.default.clone(),
+            ),
+            #[cfg(feature = "ups-battery")]
+            crate::battery::service(retry.default.clone()),
+        ],
+        ..Default::default()
+    };
+    let disabled = config.remove_disabled(&mut app);
+    descriptors::attach(&mut app);
+    security::apply(&mut app, args.security);
+    if let Some(gate) = gate {
+        auth::restrict_writes(&mut app, gate.clone());
+        tasks::spawn(
+            "auth-disconnects",
+            auth::forget_disconnected(adapter.clone(), gate.clone()),
+        );
+        if args.auth_timeout_secs > 0 {
+            let timeout = Duration::from_secs(args.auth_timeout_secs);
+            tasks::spawn(
+                "auth-timeout",
+                auth::enforce_timeout(adapter.clone(), gate, timeout),
+            );
+        }
+    }
+    let aliases = config.apply_aliases(&mut app);
+    instance::remap_characteristics(&mut app, args.instance_id, &aliases);
+    peer_stats::instrument(&mut app);
+    if let Some(allowed_centrals) = &config.allowed_centrals {
+        let allowed_centrals = Arc::new(allowed_centrals.clone());
+        allowlist::restrict_writes(&mut app, allowed_centrals.clone());
+        tasks::spawn(
+            "allowlist",
+            allowlist::enforce(adapter.clone(), allowed_centrals),
+        );
+    }
+    Ok((
+        app,
+        Handles {
+            _agent: agent_handle,
+            _disabled: disabled,
+        },
+    ))
+}
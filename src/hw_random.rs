@@ -0,0 +1,52 @@
+use bluer::gatt::local::{Characteristic, CharacteristicRead, ReqError};
+use futures::FutureExt;
+use tokio::{fs::File, io::AsyncReadExt};
+
+/// Raspberry Pi hardware random number generator
+const HWRNG: &str = "/dev/hwrng";
+const URANDOM: &str = "/dev/urandom";
+
+const SOFTWARE: u8 = 0x00;
+const HARDWARE: u8 = 0x01;
+
+const RANDOM_LEN: usize = 16;
+
+/// Reads exactly [RANDOM_LEN] bytes; both devices are endless, so never to the end.
+async fn read_random(path: &str) -> std::io::Result<[u8; RANDOM_LEN]> {
+    let mut random = [0u8; RANDOM_LEN];
+    File::open(path).await?.read_exact(&mut random).await?;
+    Ok(random)
+}
+
+/// Creates the `HW_RANDOM` characteristic.
+///
+/// Reads return the source (`0x01` hardware, `0x00` software) followed by 16
+/// random bytes from `/dev/hwrng`, or `/dev/urandom` when it is unavailable.
+pub fn characteristic() -> Characteristic {
+    Characteristic {
+        uuid: crate::HW_RANDOM,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(|_req| {
+                async move {
+                    let (source, random) = match read_random(HWRNG).await {
+                        Ok(random) => (HARDWARE, random),
+                        Err(_) => match read_random(URANDOM).await {
+                            Ok(random) => (SOFTWARE, random),
+                            Err(err) => {
+                                eprintln!("Reading {URANDOM} failed: {err}");
+                                return Err(ReqError::Failed);
+                            }
+                        },
+                    };
+                    let mut value = vec![source];
+                    value.extend_from_slice(&random);
+                    Ok(value)
+                }
+                .boxed()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,32 @@
+use bluer::gatt::local::Application;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Prefix shared by the characteristic UUIDs of this server
+const CHARACTERISTIC_PREFIX: u128 = 0xfd2bcccb;
+
+/// Moves `uuid` to instance `instance` by setting the upper nibble of its
+/// last 16 bits, e.g. `0xfd2bcccb0001` becomes `0xfd2bcccb3001` for instance 3.
+pub fn remap(uuid: Uuid, instance: u8) -> Uuid {
+    let value = uuid.as_u128();
+    Uuid::from_u128((value & !0xf000) | ((instance as u128 & 0xf) << 12))
+}
+
+/// Remaps the characteristic UUIDs of this server to `instance`. Instance 0
+/// keeps all UUIDs. Configured `aliases` are served as is, even when they
+/// share the prefix.
+pub fn remap_characteristics(app: &mut Application, instance: u8, aliases: &HashSet<Uuid>) {
+    if instance == 0 {
+        return;
+    }
+    for service in &mut app.services {
+        for characteristic in &mut service.characteristics {
+            if aliases.contains(&characteristic.uuid) {
+                continue;
+            }
+            if characteristic.uuid.as_u128() >> 16 == CHARACTERISTIC_PREFIX {
+                characteristic.uuid = remap(characteristic.uuid, instance);
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotify, CharacteristicNotifyMethod,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, StreamExt};
+use std::{
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Read, Seek, SeekFrom},
+    os::unix::fs::OpenOptionsExt,
+    time::Duration,
+};
+use tokio::time;
+
+/// Messages at this level or more severe are forwarded
+const KERN_WARNING: u8 = 4;
+
+/// How often `/dev/kmsg` is drained
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Opens `/dev/kmsg` without blocking, positioned after the existing backlog.
+fn open() -> std::io::Result<File> {
+    let mut kmsg = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open("/dev/kmsg")?;
+    kmsg.seek(SeekFrom::End(0))?;
+    Ok(kmsg)
+}
+
+/// Splits a `/dev/kmsg` record (`prio,seq,ts,flags;message`) into
+/// severity and message text.
+fn parse_record(record: &str) -> Option<(u8, &str)> {
+    let (prefix, message) = record.split_once(';')?;
+    let priority: u32 = prefix.split(',').next()?.parse().ok()?;
+    // Continuation lines (dictionary entries) follow the first newline
+    let message = message.lines().next().unwrap_or_default();
+    Some(((priority & 0x7) as u8, message))
+}
+
+/// Cuts `message` to fit `len` bytes without splitting a character.
+fn truncate(message: &str, len: usize) -> &str {
+    let mut end = message.len().min(len);
+    while !message.is_char_boundary(end) {
+        end -= 1;
+    }
+    &message[..end]
+}
+
+async fn serve(mut kmsg: File, control: CharacteristicControl, retry: RetryPolicy) {
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut sequence = NotifySequence::new("kernel messages");
+    let mut record = vec![0u8; 8192];
+    let mut interval = time::interval(POLL_INTERVAL);
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting kernel message notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "kernel message");
+                        writer_opt = Some(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            _ = interval.tick() => {
+                loop {
+                    let len = match kmsg.read(&mut record) {
+                        Ok(len) => len,
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                        // Records were overwritten before we read them, carry on
+                        Err(err) if err.raw_os_error() == Some(libc::EPIPE) => continue,
+                        Err(err) => {
+                            eprintln!("Stopping kernel message stream: {err}");
+                            return;
+                        }
+                    };
+                    let record = String::from_utf8_lossy(&record[..len]);
+                    let Some((severity, message)) = parse_record(&record) else {
+                        continue;
+                    };
+                    if severity > KERN_WARNING {
+                        continue;
+                    }
+                    if let Some(writer) = &mut writer_opt {
+                        let message = truncate(message, writer.mtu() - crate::payload::HEADER_LEN);
+                        if retry.notify(writer, &sequence.stamp(message)).await.is_err() {
+                            writer_opt = None;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `KERNEL_MESSAGES` characteristic and starts streaming `/dev/kmsg`.
+pub fn characteristic(retry: &RetryPolicies) -> std::io::Result<Characteristic> {
+    let kmsg = open()?;
+    let (control, control_handle) = characteristic_control();
+    crate::tasks::spawn("kmsg", serve(kmsg, control, retry.get("kernel_messages")));
+
+    Ok(Characteristic {
+        uuid: crate::KERNEL_MESSAGES,
+        notify: Some(CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Io,
+            ..Default::default()
+        }),
+        control_handle,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_continuation_lines() {
+        let record =
+            "6,339,5140900,-;usb 1-1: new high-speed USB device\n SUBSYSTEM=usb\n DEVICE=c189:1\n";
+        assert_eq!(
+            parse_record(record),
+            Some((6, "usb 1-1: new high-speed USB device"))
+        );
+    }
+
+    #[test]
+    fn decodes_the_level_below_the_facility() {
+        // Facility 0 (kernel), level 3 (error)
+        assert_eq!(parse_record("3,1,2,-;oops\n"), Some((3, "oops")));
+        // Facility 3 (daemon), level 4 (warning)
+        assert_eq!(parse_record("28,1,2,-;daemon\n"), Some((4, "daemon")));
+        // Facility 1 (user), level 6 (info)
+        assert_eq!(parse_record("14,1,2,c;user\n"), Some((6, "user")));
+    }
+
+    #[test]
+    fn keeps_semicolons_in_messages() {
+        assert_eq!(parse_record("4,1,2,-;a;b\n"), Some((4, "a;b")));
+        assert_eq!(parse_record("4,1,2,-;\n"), Some((4, "")));
+    }
+
+    #[test]
+    fn rejects_malformed_records() {
+        assert_eq!(parse_record(""), None);
+        assert_eq!(parse_record("no prefix here\n"), None);
+        assert_eq!(parse_record("warn,1,2,-;message\n"), None);
+        assert_eq!(parse_record(",1,2,-;message\n"), None);
+        assert_eq!(parse_record("-4,1,2,-;message\n"), None);
+    }
+
+    #[test]
+    fn truncates_on_char_boundaries() {
+        assert_eq!(truncate("short", 18), "short");
+        assert_eq!(truncate("abcdef", 3), "abc");
+        assert_eq!(truncate("aé", 2), "a");
+    }
+}
@@ -0,0 +1,429 @@
+//! BLE GATT server exposing Raspberry Pi system metrics, see [run].
+
+mod addresses;
+mod alerts;
+mod allowlist;
+#[cfg(feature = "ambient-sensor")]
+mod ambient_light;
+mod auth;
+#[cfg(feature = "ups-battery")]
+mod battery;
+mod bt_pair;
+#[cfg(feature = "audio-alert")]
+mod buzzer;
+#[cfg(feature = "cert-provisioning")]
+mod cert;
+pub mod cli;
+#[cfg(feature = "co2-sensor")]
+mod co2;
+mod commands;
+pub mod config;
+mod control;
+#[cfg(feature = "cron-bridge")]
+mod cron;
+mod descriptors;
+mod device_info;
+mod disk_health;
+mod disk_wear;
+#[cfg(feature = "door-sensor")]
+mod door;
+pub mod encoding;
+#[cfg(feature = "environment-sensor")]
+mod environment;
+mod environmental_sensing;
+mod events;
+mod exec_stream;
+pub mod gatt;
+mod hw_random;
+mod instance;
+#[cfg(feature = "kmsg")]
+mod kmsg;
+mod locale;
+pub mod metrics;
+#[cfg(feature = "posix-mq")]
+mod mqueue;
+mod negotiate;
+#[cfg(feature = "obd2")]
+mod obd2;
+mod pairing_code;
+mod passkey_agent;
+pub mod payload;
+mod peer_stats;
+mod peers;
+#[cfg(any(feature = "ambient-sensor", feature = "environment-sensor"))]
+mod periodic;
+#[cfg(feature = "pipe-bridge")]
+mod pipe_bridge;
+#[cfg(feature = "pir-sensor")]
+mod pir;
+#[cfg(feature = "gpio")]
+mod power_cycle;
+mod processes;
+pub mod proto;
+pub mod provider;
+mod raspi;
+mod response;
+mod retry;
+mod ring_buffer;
+#[cfg(feature = "screenshot")]
+mod screenshot;
+mod security;
+mod snapshot;
+mod stats;
+#[cfg(feature = "status-led")]
+mod status_led;
+mod stress;
+mod system_info;
+mod system_metrics;
+mod tasks;
+mod temp_map;
+mod timezone;
+#[cfg(feature = "traffic-control")]
+mod traffic_control;
+mod transport;
+mod uptime;
+mod voltage;
+mod wifi_connect;
+mod wifi_scan;
+mod wifi_status;
+
+const SERVICE_ID: &str = "FD2B4448-AA0F-4A15-A62F-EB0BE77A0000";
+
+/// CPU temperature in °C as `f32`
+const TEMPERATURE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0001);
+
+/// CPU load as `f32`
+const CPU_LOAD: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0002);
+
+/// Memory usage: used and total bytes as `u64`
+const RAM_USAGE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0003);
+
+/// Uptime in minutes as `u64`
+const UPTIME: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0004);
+
+/// Root filesystem usage: used and total bytes as `u64`, percent used as `f32`
+const DISK_USAGE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0006);
+
+/// Per core CPU load: core count as `u8`, then the busy fraction of each core as `f32`
+const CPU_CORE_LOAD: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0007);
+
+/// 1, 5 and 15 minute load averages as `f32`
+const LOAD_AVERAGE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0008);
+
+/// Swap usage: used and total bytes as `u64`
+const SWAP_USAGE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0009);
+
+/// Network throughput: received and transmitted bytes per second as `u64`
+const NETWORK_THROUGHPUT: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb000a);
+
+/// GPU temperature in °C as `f32`
+const GPU_TEMPERATURE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb000b);
+
+/// GPU core clock in Hz as `u64`
+const GPU_CLOCK: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb000c);
+
+/// `get_throttled` undervoltage and throttling bitfield as `u32`
+const THROTTLED: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb000d);
+
+/// Current CPU frequency in Hz as `u64`
+const CPU_FREQUENCY: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb000e);
+
+/// SSID and signal strength of the Wi-Fi connection
+const WIFI_STATUS: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb000f);
+
+/// IPv4 and IPv6 addresses per interface
+const IP_ADDRESSES: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0010);
+
+/// Hostname, kernel release and OS name
+const SYSTEM_INFO: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0011);
+
+/// Number of running processes
+const PROCESS_COUNT: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0012);
+
+/// Lists the top N processes by CPU or memory on `WRITE_REQUEST_RESPONSE`
+const TOP_PROCESSES: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0013);
+
+/// All metrics as one JSON document
+const METRICS_SNAPSHOT: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0014);
+
+/// Encoding of `METRICS_SNAPSHOT`, JSON, CBOR or protobuf
+const SNAPSHOT_FORMAT: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0015);
+
+/// Layout version of notify payloads
+const PROFILE_VERSION: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00c8);
+
+/// Runs written commands such as `echo` or `reboot` and reports results of write requests
+const WRITE_REQUEST_RESPONSE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0005);
+
+/// Root drive S.M.A.R.T. health
+const DISK_HEALTH: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb008f);
+
+/// Starts a Wi-Fi access point scan
+const WIFI_SCAN_TRIGGER: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0092);
+
+/// Wi-Fi access point scan results
+const WIFI_SCAN_RESULTS: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0093);
+
+/// Connects to a Wi-Fi network
+const WIFI_CONNECT: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0094);
+
+/// Pairs the adapter with a remote BLE device
+const BT_PAIR_REMOTE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0095);
+
+/// Runs a whitelisted command and streams its output
+const EXEC_STREAM: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb009f);
+
+/// State of every background task
+const TASK_STATUS: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00a3);
+
+/// Last 60 supply voltage samples
+const VOLTAGE_HISTORY: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00a4);
+
+/// Bitmask of metrics that are not notified because their source fails
+const DEGRADED_METRICS: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00a5);
+
+/// Histogram of write to notify round trip times
+const LATENCY_HISTOGRAM: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00ac);
+
+/// Clears the collected statistics
+const STATS_RESET: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00ca);
+
+/// Cumulative count of notifications resent after a failed write
+const NOTIFY_RETRIES: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00cb);
+
+/// Minimum, maximum and mean lateness of the metric ticks
+const TICK_JITTER: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00cd);
+
+/// Client and server capability exchange
+const PROTO_NEGOTIATE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00ad);
+
+/// Queries metric samples by time range
+const TS_QUERY: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00ae);
+
+/// MTU of each connected peer, notified on change
+const MTU_CHANGED: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00b1);
+
+/// Notify priority of a metric
+const QOS_PRIORITY: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00b2);
+
+/// Uptime as readable text, e.g. `3 days, 4 hours, 12 minutes`
+const UPTIME_HUMAN: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00b5);
+
+/// Out-of-band code a client writes before it may pair
+const SENSOR_PAIRING_CODE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00b6);
+
+/// System locale, e.g. `en_US.UTF-8`
+const SYSTEM_LOCALE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00b7);
+
+/// Remaining life of the SMART capable disk, 100 when new
+const DISK_WEAR: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00b8);
+
+/// Critical alerts such as over-temperature, with confirmed delivery
+const ALERTS: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00cc);
+
+/// Significant state changes such as network or client connections
+const SYSTEM_EVENTS: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00b9);
+
+/// Disconnects a peer by address, for bonded admin clients
+const PEER_DISCONNECT: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00ba);
+
+/// Temperatures of all thermal zones
+const TEMP_MAP: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00bb);
+
+/// Runs a CPU and/or memory stress test
+const STRESS_TEST: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00bc);
+
+/// Random bytes from the hardware random number generator
+const HW_RANDOM: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00be);
+
+/// Recently executed commands for auditing
+const COMMAND_HISTORY: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00bf);
+
+/// Cumulative traffic statistics per client
+const PEER_STATS: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00c0);
+
+/// Message sent to all `SYSTEM_EVENTS` subscribers
+const BROADCAST: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00c1);
+
+/// Freezes the notified value of a metric for a while
+const CHAR_LOCK: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00a6);
+
+/// Overrides a metric with a test value
+const CHAR_INJECT: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00a7);
+
+/// Stale state of the system metrics
+const ERROR_DETAIL: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00c7);
+
+/// Onboard status LED mode
+#[cfg(feature = "status-led")]
+const STATUS_LED: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb008c);
+
+/// Onboard status LED blink pattern
+#[cfg(feature = "status-led")]
+const LED_PATTERN: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb008d);
+
+/// Ambient light in lux
+#[cfg(feature = "ambient-sensor")]
+const AMBIENT_LIGHT_LUX: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0096);
+
+/// Barometric pressure in Pa
+#[cfg(feature = "environment-sensor")]
+const PRESSURE_PA: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0097);
+
+/// Relative humidity in 0.01 %
+#[cfg(feature = "environment-sensor")]
+const HUMIDITY_PCT: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0098);
+
+/// CO₂ concentration in ppm
+#[cfg(feature = "co2-sensor")]
+const CO2_PPM: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0099);
+
+/// CO₂ threshold alert
+#[cfg(feature = "co2-sensor")]
+const CO2_ALERT: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00c2);
+
+/// Motion detected by a PIR sensor
+#[cfg(feature = "pir-sensor")]
+const PIR_MOTION: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb009a);
+
+/// Motion events since boot
+#[cfg(feature = "pir-sensor")]
+const PIR_MOTION_COUNT: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00c3);
+
+/// Door open/closed state from a reed switch
+#[cfg(feature = "door-sensor")]
+const DOOR_STATE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb009b);
+
+/// Seconds the door has been open
+#[cfg(feature = "door-sensor")]
+const DOOR_OPEN_SECONDS: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb009c);
+
+/// Plays a tone on a buzzer
+#[cfg(feature = "audio-alert")]
+const SPEAKER_ALERT: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00c4);
+
+/// Queries an OBD-II PID through an ELM327 adapter
+#[cfg(feature = "obd2")]
+const OBD2_PID: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00c5);
+
+/// Power cycles a peripheral through a relay
+#[cfg(feature = "gpio")]
+const POWER_CYCLE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00bd);
+
+/// Messages received on the `/ble_raspi_in` POSIX message queue
+#[cfg(feature = "posix-mq")]
+const MQ_NOTIFY: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb009d);
+
+/// Posts messages to the `/ble_raspi_out` POSIX message queue
+#[cfg(feature = "posix-mq")]
+const MQ_PUBLISH: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb009e);
+
+/// Bandwidth limits of a network interface
+#[cfg(feature = "traffic-control")]
+const TC_LIMIT: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00a0);
+
+/// Captures the display and streams it as JPEG
+#[cfg(feature = "screenshot")]
+const SCREEN_CAPTURE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00c9);
+
+/// Framed PEM certificate chain to install
+#[cfg(feature = "cert-provisioning")]
+const CERT_WRITE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00a9);
+
+/// Expiry of the installed certificate
+#[cfg(feature = "cert-provisioning")]
+const CERT_STATUS: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00aa);
+
+/// Creates a cron entry for a whitelisted command
+#[cfg(feature = "cron-bridge")]
+const CRON_ADD: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00a1);
+
+/// Cron entries created over BLE
+#[cfg(feature = "cron-bridge")]
+const CRON_LIST: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00a2);
+
+/// Deletes a cron entry by its `CRON_LIST` index
+#[cfg(feature = "cron-bridge")]
+const CRON_DELETE: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb00c6);
+
+/// Kernel warnings and errors from `/dev/kmsg`
+#[cfg(feature = "kmsg")]
+const KERNEL_MESSAGES: uuid::Uuid = uuid::Uuid::from_u128(0xfd2bcccb0091);
+
+use bluer::adv::Advertisement;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::{signal, time::sleep};
+
+/// Runs the GATT server until interrupted.
+pub async fn run(args: cli::Args) -> bluer::Result<()> {
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = args.log_level {
+        logger.filter_level(level);
+    }
+    logger.init();
+    let config = config::Config::load(&args.config)?;
+    peer_stats::restore(&args.state_file);
+    tasks::spawn("peer-stats", peer_stats::persist(args.state_file.clone()));
+    // A configured service UUID is used as is, only the built-in one moves to the instance
+    let service_uuid = config.service_uuid.unwrap_or_else(|| {
+        instance::remap(
+            uuid::Uuid::from_str(&SERVICE_ID.to_lowercase()).unwrap(),
+            args.instance_id,
+        )
+    });
+    let session = bluer::Session::new().await?;
+    let adapter = match &args.adapter {
+        Some(name) => session.adapter(name)?,
+        None => session.default_adapter().await?,
+    };
+    adapter.set_powered(true).await?;
+
+    let adv_handle = if args.transport.le() {
+        println!(
+            "Advertising on Bluetooth adapter {} with address {}",
+            adapter.name(),
+            adapter.address().await?
+        );
+        let le_advertisement = Advertisement {
+            service_uuids: vec![service_uuid].into_iter().collect(),
+            discoverable: Some(true),
+            local_name: Some(config.device_name(args.local_name.as_deref())),
+            ..Default::default()
+        };
+        Some(adapter.advertise(le_advertisement).await?)
+    } else {
+        None
+    };
+    if args.transport.bredr() {
+        transport::enable_bredr(&adapter).await?;
+    }
+    if args.security != security::Security::Open {
+        // Centrals bond when a request fails for insufficient security
+        adapter.set_pairable(true).await?;
+    }
+    security::require_for_subscriptions(adapter.clone(), args.security);
+
+    println!(
+        "Serving GATT echo service on Bluetooth adapter {}",
+        adapter.name()
+    );
+    let (app, handles) =
+        gatt::application(&args, &config, &session, &adapter, service_uuid).await?;
+    let app_handle = adapter.serve_gatt_application(app).await?;
+
+    println!("GATT Service Ready - Serving");
+
+    signal::ctrl_c().await?;
+
+    println!("Removing service and advertisement");
+    drop(app_handle);
+    drop(handles);
+    if let Err(err) = peer_stats::save(&args.state_file).await {
+        eprintln!("Saving peer statistics failed: {err}");
+    }
+    drop(adv_handle);
+    sleep(Duration::from_secs(1)).await;
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use bluer::gatt::local::{
+    Characteristic, CharacteristicRead, CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+};
+use futures::FutureExt;
+use std::{path::Path, sync::RwLock};
+use tokio::fs;
+
+const LOCALE_CONF: &str = "/etc/locale.conf";
+
+/// Translations installed per language, e.g. `de` or `pt_BR`
+const LOCALE_DIR: &str = "/usr/share/locale";
+
+/// Locale of the server, kept here rather than in `$LANG` since the
+/// environment must not change while other threads may read it
+static LOCALE: RwLock<String> = RwLock::new(String::new());
+
+/// Locale at startup from `$LANG`, falling back to `LANG=` in `/etc/locale.conf`.
+fn initial() -> Option<String> {
+    if let Some(lang) = std::env::var("LANG").ok().filter(|lang| !lang.is_empty()) {
+        return Some(lang);
+    }
+    let conf = std::fs::read_to_string(LOCALE_CONF).ok()?;
+    conf.lines()
+        .find_map(|line| line.trim().strip_prefix("LANG="))
+        .map(|lang| lang.trim_matches('"').to_string())
+}
+
+/// Current locale, e.g. `en_US.UTF-8`, empty when unknown.
+pub fn current() -> String {
+    LOCALE.read().unwrap().clone()
+}
+
+/// Language of the current locale, e.g. `de` for `de_DE.UTF-8`.
+pub fn language() -> String {
+    let locale = current();
+    let language = locale.split(['_', '.', '@']).next().unwrap_or_default();
+    language.to_ascii_lowercase()
+}
+
+/// Checks that `locale` is of the form `ll[_CC][.codeset]` and its language is installed.
+fn is_available(locale: &str) -> bool {
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    let language = name.split('_').next().unwrap_or_default();
+    !language.is_empty()
+        && locale
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.@-".contains(c))
+        && (Path::new(LOCALE_DIR).join(name).is_dir()
+            || Path::new(LOCALE_DIR).join(language).is_dir())
+}
+
+/// Replaces or adds the `LANG=` line in `/etc/locale.conf` and switches the
+/// locale of the server.
+async fn set(locale: &str) -> std::io::Result<()> {
+    let conf = fs::read_to_string(LOCALE_CONF).await.unwrap_or_default();
+    let mut lines = conf
+        .lines()
+        .filter(|line| !line.trim().starts_with("LANG="))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    lines.push(format!("LANG={locale}"));
+    fs::write(LOCALE_CONF, lines.join("\n") + "\n").await?;
+    *LOCALE.write().unwrap() = locale.to_string();
+    Ok(())
+}
+
+/// Creates the `SYSTEM_LOCALE` characteristic.
+///
+/// Reads return the locale as UTF-8, e.g. `en_US.UTF-8`. Writes of a locale
+/// whose language is installed under `/usr/share/locale` change it, which
+/// needs an authenticated link as it rewrites `/etc/locale.conf`.
+/// `UPTIME_HUMAN` and `SYSTEM_INFO` are formatted for the locale.
+pub fn characteristic() -> Characteristic {
+    *LOCALE.write().unwrap() = initial().unwrap_or_default();
+    Characteristic {
+        uuid: crate::SYSTEM_LOCALE,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(|_req| {
+                let locale = current();
+                async move { Ok(locale.into_bytes()) }.boxed()
+            }),
+            ..Default::default()
+        }),
+        write: Some(CharacteristicWrite {
+            write: true,
+            encrypt_authenticated_write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(|value, _req| {
+                async move {
+                    let locale = String::from_utf8(value).map_err(|_| ReqError::NotSupported)?;
+                    if !is_available(&locale) {
+                        return Err(ReqError::NotSupported);
+                    }
+                    set(&locale).await.map_err(|err| {
+                        eprintln!("Setting locale {locale} failed: {err}");
+                        ReqError::Failed
+                    })?;
+                    println!("Locale set to {locale}");
+                    Ok(())
+                }
+                .boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,596 @@
+use crate::{payload::unix_timestamp, response::Responder, ring_buffer::RingBuffer};
+use bluer::gatt::local::{
+    Characteristic, CharacteristicRead, CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+};
+use futures::FutureExt;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use systemstat::Memory;
+
+/// Errors in a row that are bridged with the last good value
+const MAX_CONSECUTIVE_ERRORS: u32 = 5;
+
+/// Samples kept per metric for `TS_QUERY`
+const HISTORY_LEN: usize = 60;
+
+/// Bytes of the `MORE_DATA` or `DONE` flag leading each reply frame
+const FLAG_LEN: usize = 1;
+
+const MORE_DATA: u8 = 0x01;
+const DONE: u8 = 0x00;
+
+const BEST_EFFORT: u8 = 0;
+/// Highest priority accepted. Real-time (2) is refused: the metrics share
+/// one task, so no priority gets a scheduler of its own.
+const ELEVATED: u8 = 1;
+
+/// Notifications an injected value replaces
+const INJECTED_NOTIFICATIONS: u32 = 5;
+
+/// How often a degraded metric is logged
+const DEGRADED_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A metric that keeps serving its last good value on transient read errors
+/// and is marked degraded when its source keeps failing.
+#[derive(Debug)]
+pub struct Interpolated<T> {
+    name: &'static str,
+    last_good: Option<T>,
+    consecutive_errors: u32,
+    /// Readings served from `last_good` since startup
+    stale_count: u32,
+    last_warning: Option<Instant>,
+    /// Value notified instead of readings until the deadline
+    locked: Option<(Instant, T)>,
+    /// Test value and the number of notifications it still replaces
+    injected: Option<(T, u32)>,
+}
+
+impl<T: Clone> Interpolated<T> {
+    fn new(name: &'static str) -> Self {
+        Interpolated {
+            name,
+            last_good: None,
+            consecutive_errors: 0,
+            stale_count: 0,
+            last_warning: None,
+            locked: None,
+            injected: None,
+        }
+    }
+
+    /// Replaces readings with `value` for the next [INJECTED_NOTIFICATIONS] notifications.
+    fn inject(&mut self, value: T) {
+        self.injected = Some((value, INJECTED_NOTIFICATIONS));
+    }
+
+    /// Freezes the notified value at the current one for `duration`, or
+    /// unlocks for a zero duration. Returns `false` without a value to freeze.
+    fn lock(&mut self, duration: Duration) -> bool {
+        if duration.is_zero() {
+            self.locked = None;
+            return true;
+        }
+        let Some(value) = self.last_good.clone() else {
+            return false;
+        };
+        println!("Locking {} for {}s", self.name, duration.as_secs());
+        self.locked = Some((Instant::now() + duration, value));
+        true
+    }
+
+    /// Returns a fresh reading, or the last good one for up to
+    /// [MAX_CONSECUTIVE_ERRORS] errors in a row, or `None` while degraded.
+    pub fn update(&mut self, reading: std::io::Result<T>) -> Option<T> {
+        if let Some((value, _)) = &self.injected {
+            return Some(value.clone());
+        }
+        if let Some((until, value)) = &self.locked {
+            if Instant::now() < *until {
+                return Some(value.clone());
+            }
+            println!("Unlocking {}", self.name);
+            self.locked = None;
+        }
+        match reading {
+            Ok(value) => {
+                if self.is_degraded() {
+                    log::info!("Metric {} recovered", self.name);
+                    self.last_warning = None;
+                }
+                self.consecutive_errors = 0;
+                self.last_good = Some(value.clone());
+                Some(value)
+            }
+            Err(err) => {
+                self.consecutive_errors += 1;
+                if !self.is_degraded() {
+                    eprintln!("Serving stale {}: {err}", self.name);
+                    self.stale_count += 1;
+                    return self.last_good.clone();
+                }
+                let warn = self
+                    .last_warning
+                    .is_none_or(|warned| warned.elapsed() >= DEGRADED_WARNING_INTERVAL);
+                if warn {
+                    log::warn!("Metric {} degraded, not notifying it: {err}", self.name);
+                    self.last_warning = Some(Instant::now());
+                }
+                None
+            }
+        }
+    }
+
+    /// Counts a notification of the served value against the injected one.
+    fn notified(&mut self) {
+        if let Some((_, remaining)) = &mut self.injected {
+            *remaining -= 1;
+            if *remaining == 0 {
+                self.injected = None;
+            }
+        }
+    }
+
+    fn is_degraded(&self) -> bool {
+        self.consecutive_errors > 0
+            && (self.last_good.is_none() || self.consecutive_errors > MAX_CONSECUTIVE_ERRORS)
+    }
+
+    fn is_stale(&self) -> bool {
+        self.consecutive_errors > 0 && !self.is_degraded()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorDetail {
+    stale: bool,
+    stale_count: u32,
+}
+
+impl<T: Clone> From<&Interpolated<T>> for ErrorDetail {
+    fn from(metric: &Interpolated<T>) -> Self {
+        ErrorDetail {
+            stale: metric.is_stale(),
+            stale_count: metric.stale_count,
+        }
+    }
+}
+
+/// Index of each metric in [InterpolatedMetrics], and its bit in the `DEGRADED_METRICS` mask
+pub const CPU_LOAD_INDEX: usize = 0;
+pub const CPU_TEMP_INDEX: usize = 1;
+pub const MEMORY_INDEX: usize = 2;
+pub const UPTIME_INDEX: usize = 3;
+
+/// The system metrics served by the core [providers](crate::system_metrics)
+#[derive(Debug)]
+pub struct InterpolatedMetrics {
+    pub cpu_load: Interpolated<f32>,
+    pub cpu_temp: Interpolated<f32>,
+    pub memory: Interpolated<Memory>,
+    pub uptime: Interpolated<Duration>,
+    /// Timestamped samples per metric, indexed like [degraded_mask](Self::degraded_mask)
+    history: [RingBuffer<(u32, f32), HISTORY_LEN>; 4],
+    /// QoS priority per metric, indexed the same way
+    priorities: [u8; 4],
+}
+
+impl Default for InterpolatedMetrics {
+    fn default() -> Self {
+        InterpolatedMetrics {
+            cpu_load: Interpolated::new("cpu_load"),
+            cpu_temp: Interpolated::new("cpu_temp"),
+            memory: Interpolated::new("memory"),
+            uptime: Interpolated::new("uptime"),
+            history: Default::default(),
+            priorities: [BEST_EFFORT; 4],
+        }
+    }
+}
+
+impl InterpolatedMetrics {
+    /// Records a served value of the metric at `index` for `TS_QUERY`;
+    /// memory as used MB and uptime in minutes.
+    pub fn record(&mut self, index: usize, value: f32) {
+        self.history[index].push((unix_timestamp(), value));
+    }
+
+    /// Latest recorded CPU load, CPU temperature, used memory in MB and uptime in minutes.
+    pub fn latest(&self) -> [Option<f32>; 4] {
+        self.history
+            .each_ref()
+            .map(|history| history.iter().last().map(|(_, value)| value))
+    }
+
+    /// QoS priority of the metric at `index`.
+    pub fn priority(&self, index: usize) -> u8 {
+        self.priorities[index]
+    }
+
+    fn set_priority(&mut self, index: u8, priority: u8) -> Result<(), ReqError> {
+        if priority > ELEVATED {
+            return Err(ReqError::NotSupported);
+        }
+        let slot = self
+            .priorities
+            .get_mut(index as usize)
+            .ok_or(ReqError::NotSupported)?;
+        *slot = priority;
+        println!("Metric {index} notify priority is now {priority}");
+        Ok(())
+    }
+
+    /// Samples of the metric at `index` taken between `start` and `end`, inclusive.
+    fn query(&self, index: u8, start: u32, end: u32) -> Option<Vec<(u32, f32)>> {
+        let history = self.history.get(index as usize)?;
+        Some(
+            history
+                .iter()
+                .filter(|(timestamp, _)| (start..=end).contains(timestamp))
+                .collect(),
+        )
+    }
+
+    fn error_details(&self) -> BTreeMap<&'static str, ErrorDetail> {
+        BTreeMap::from([
+            (self.cpu_load.name, (&self.cpu_load).into()),
+            (self.cpu_temp.name, (&self.cpu_temp).into()),
+            (self.memory.name, (&self.memory).into()),
+            (self.uptime.name, (&self.uptime).into()),
+        ])
+    }
+
+    /// Locks the metric at `index`, numbered like the [degraded_mask](Self::degraded_mask) bits.
+    fn lock(&mut self, index: u8, duration: Duration) -> Result<(), ReqError> {
+        let locked = match index {
+            0 => self.cpu_load.lock(duration),
+            1 => self.cpu_temp.lock(duration),
+            2 => self.memory.lock(duration),
+            3 => self.uptime.lock(duration),
+            _ => return Err(ReqError::NotSupported),
+        };
+        if locked {
+            Ok(())
+        } else {
+            Err(ReqError::Failed)
+        }
+    }
+
+    /// Counts a notification of the metric at `index`, see [Interpolated::notified].
+    pub fn notified(&mut self, index: usize) {
+        match index {
+            0 => self.cpu_load.notified(),
+            1 => self.cpu_temp.notified(),
+            2 => self.memory.notified(),
+            3 => self.uptime.notified(),
+            _ => {}
+        }
+    }
+
+    /// Injects a test value into the `f32` metric at `index`.
+    fn inject(&mut self, index: u8, value: f32) -> Result<(), ReqError> {
+        match index {
+            0 => self.cpu_load.inject(value),
+            1 => self.cpu_temp.inject(value),
+            _ => return Err(ReqError::NotSupported),
+        }
+        println!("Injected {value} into metric {index}");
+        Ok(())
+    }
+
+    /// Bit 0 CPU load, bit 1 CPU temperature, bit 2 memory, bit 3 uptime
+    fn degraded_mask(&self) -> u8 {
+        [
+            self.cpu_load.is_degraded(),
+            self.cpu_temp.is_degraded(),
+            self.memory.is_degraded(),
+            self.uptime.is_degraded(),
+        ]
+        .into_iter()
+        .enumerate()
+        .fold(0, |mask, (bit, degraded)| mask | ((degraded as u8) << bit))
+    }
+}
+
+/// Creates the `ERROR_DETAIL` characteristic.
+///
+/// Reads return a CBOR map of metric names to `stale`, set while a metric is
+/// served from its last good value, and `stale_count`.
+pub fn error_detail_characteristic(metrics: Arc<Mutex<InterpolatedMetrics>>) -> Characteristic {
+    Characteristic {
+        uuid: crate::ERROR_DETAIL,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(move |_req| {
+                let details = metrics.lock().unwrap().error_details();
+                let mut value = Vec::new();
+                let result = ciborium::into_writer(&details, &mut value)
+                    .map(|()| value)
+                    .map_err(|_| ReqError::Failed);
+                async move { result }.boxed()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Creates the `DEGRADED_METRICS` characteristic, a one byte bitmask of metrics
+/// whose source keeps failing and which are not notified.
+pub fn degraded_characteristic(metrics: Arc<Mutex<InterpolatedMetrics>>) -> Characteristic {
+    Characteristic {
+        uuid: crate::DEGRADED_METRICS,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(move |_req| {
+                let mask = metrics.lock().unwrap().degraded_mask();
+                async move { Ok(vec![mask]) }.boxed()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Creates the `CHAR_LOCK` characteristic.
+///
+/// Writes are a metric index and a `u32` LE duration in seconds during which
+/// the metric keeps notifying the value current at lock time. Zero unlocks.
+pub fn lock_characteristic(metrics: Arc<Mutex<InterpolatedMetrics>>) -> Characteristic {
+    Characteristic {
+        uuid: crate::CHAR_LOCK,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                let result = match value[..] {
+                    [index, s0, s1, s2, s3] => {
+                        let seconds = u32::from_le_bytes([s0, s1, s2, s3]);
+                        metrics
+                            .lock()
+                            .unwrap()
+                            .lock(index, Duration::from_secs(seconds as u64))
+                    }
+                    _ => Err(ReqError::InvalidValueLength),
+                };
+                async move { result }.boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Creates the `CHAR_INJECT` characteristic, only served in test mode.
+///
+/// Writes are a metric index and an `f32` LE value that replaces the readings
+/// of the next five notifications. Only the CPU load and temperature metrics take values.
+pub fn inject_characteristic(metrics: Arc<Mutex<InterpolatedMetrics>>) -> Characteristic {
+    Characteristic {
+        uuid: crate::CHAR_INJECT,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                let result = match value[..] {
+                    [index, v0, v1, v2, v3] => metrics
+                        .lock()
+                        .unwrap()
+                        .inject(index, f32::from_le_bytes([v0, v1, v2, v3])),
+                    _ => Err(ReqError::InvalidValueLength),
+                };
+                async move { result }.boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Creates the `TS_QUERY` characteristic.
+///
+/// Writes are a metric index and `u32` LE start and end Unix timestamps. The
+/// matching samples, at most the last 60, are sent on `WRITE_REQUEST_RESPONSE`
+/// as a CBOR array of `[timestamp, value]` pairs, split into frames behind a
+/// `MORE_DATA` or `DONE` flag byte.
+pub fn query_characteristic(
+    metrics: Arc<Mutex<InterpolatedMetrics>>,
+    responder: Responder,
+) -> Characteristic {
+    Characteristic {
+        uuid: crate::TS_QUERY,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                let samples = match value[..] {
+                    [index, s0, s1, s2, s3, e0, e1, e2, e3] => {
+                        let start = u32::from_le_bytes([s0, s1, s2, s3]);
+                        let end = u32::from_le_bytes([e0, e1, e2, e3]);
+                        metrics
+                            .lock()
+                            .unwrap()
+                            .query(index, start, end)
+                            .ok_or(ReqError::NotSupported)
+                    }
+                    _ => Err(ReqError::InvalidValueLength),
+                };
+                let responder = responder.clone();
+                async move {
+                    let mut reply = Vec::new();
+                    ciborium::into_writer(&samples?, &mut reply).map_err(|_| ReqError::Failed)?;
+                    let frame_len = crate::response::max_payload_len() - FLAG_LEN;
+                    let frames = reply.chunks(frame_len).count();
+                    for (i, chunk) in reply.chunks(frame_len).enumerate() {
+                        let flag = if i + 1 == frames { DONE } else { MORE_DATA };
+                        let mut frame = vec![flag];
+                        frame.extend_from_slice(chunk);
+                        let _ = responder.send(frame).await;
+                    }
+                    Ok(())
+                }
+                .boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Creates the `QOS_PRIORITY` characteristic.
+///
+/// Writes are a metric index and a priority: 0 best effort or 1 elevated.
+/// Each tick notifies the metrics in order of priority. Real-time (2) is
+/// rejected as not supported.
+pub fn priority_characteristic(metrics: Arc<Mutex<InterpolatedMetrics>>) -> Characteristic {
+    Characteristic {
+        uuid: crate::QOS_PRIORITY,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                let result = match value[..] {
+                    [index, priority] => metrics.lock().unwrap().set_priority(index, priority),
+                    _ => Err(ReqError::InvalidValueLength),
+                };
+                async move { result }.boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure() -> std::io::Result<f32> {
+        Err(std::io::Error::other("sensor gone"))
+    }
+
+    #[test]
+    fn bridges_errors_until_degraded() {
+        let mut metric = Interpolated::new("test");
+        assert_eq!(metric.update(Ok(1.0)), Some(1.0));
+        for _ in 0..MAX_CONSECUTIVE_ERRORS {
+            assert_eq!(metric.update(failure()), Some(1.0));
+            assert!(metric.is_stale());
+            assert!(!metric.is_degraded());
+        }
+        assert_eq!(metric.update(failure()), None);
+        assert!(metric.is_degraded());
+        assert!(!metric.is_stale());
+        assert_eq!(metric.stale_count, MAX_CONSECUTIVE_ERRORS);
+
+        assert_eq!(metric.update(Ok(2.0)), Some(2.0));
+        assert!(!metric.is_degraded());
+        assert!(!metric.is_stale());
+    }
+
+    #[test]
+    fn degrades_without_a_good_value() {
+        let mut metric = Interpolated::<f32>::new("test");
+        assert_eq!(metric.update(failure()), None);
+        assert!(metric.is_degraded());
+        assert_eq!(metric.stale_count, 0);
+    }
+
+    #[test]
+    fn degraded_mask_sets_each_metric_bit() {
+        let mut metrics = InterpolatedMetrics::default();
+        assert_eq!(metrics.degraded_mask(), 0);
+        metrics.cpu_temp.update(failure());
+        metrics
+            .uptime
+            .update(Err(std::io::Error::other("no uptime")));
+        assert_eq!(metrics.degraded_mask(), 0b1010);
+    }
+
+    #[test]
+    fn lock_holds_the_value_until_unlocked() {
+        let mut metrics = InterpolatedMetrics::default();
+        assert_eq!(
+            metrics.lock(CPU_LOAD_INDEX as u8, Duration::from_secs(60)),
+            Err(ReqError::Failed)
+        );
+        metrics.cpu_load.update(Ok(0.5));
+        metrics
+            .lock(CPU_LOAD_INDEX as u8, Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(metrics.cpu_load.update(Ok(0.9)), Some(0.5));
+        assert_eq!(metrics.cpu_load.update(failure()), Some(0.5));
+
+        metrics.lock(CPU_LOAD_INDEX as u8, Duration::ZERO).unwrap();
+        assert_eq!(metrics.cpu_load.update(Ok(0.9)), Some(0.9));
+        assert_eq!(
+            metrics.lock(4, Duration::from_secs(60)),
+            Err(ReqError::NotSupported)
+        );
+    }
+
+    #[test]
+    fn lock_expires() {
+        let mut metric = Interpolated::new("test");
+        metric.update(Ok(1.0));
+        metric.locked = Some((Instant::now(), 1.0));
+        assert_eq!(metric.update(Ok(2.0)), Some(2.0));
+        assert!(metric.locked.is_none());
+    }
+
+    #[test]
+    fn injected_values_override_locks_and_readings() {
+        let mut metrics = InterpolatedMetrics::default();
+        metrics.cpu_temp.update(Ok(40.0));
+        metrics
+            .lock(CPU_TEMP_INDEX as u8, Duration::from_secs(60))
+            .unwrap();
+        metrics.inject(CPU_TEMP_INDEX as u8, 85.0).unwrap();
+        for _ in 0..INJECTED_NOTIFICATIONS {
+            assert_eq!(metrics.cpu_temp.update(failure()), Some(85.0));
+            metrics.notified(CPU_TEMP_INDEX);
+        }
+        // Back to the lock once the injected notifications are used up
+        assert_eq!(metrics.cpu_temp.update(Ok(50.0)), Some(40.0));
+        assert_eq!(
+            metrics.inject(MEMORY_INDEX as u8, 1.0),
+            Err(ReqError::NotSupported)
+        );
+    }
+
+    #[test]
+    fn history_keeps_the_latest_samples() {
+        let mut metrics = InterpolatedMetrics::default();
+        assert_eq!(metrics.latest(), [None; 4]);
+        for i in 0..HISTORY_LEN + 5 {
+            metrics.record(UPTIME_INDEX, i as f32);
+        }
+        let samples = metrics.query(UPTIME_INDEX as u8, 0, u32::MAX).unwrap();
+        assert_eq!(samples.len(), HISTORY_LEN);
+        assert_eq!(samples[0].1, 5.0);
+        assert_eq!(
+            metrics.latest()[UPTIME_INDEX],
+            Some((HISTORY_LEN + 4) as f32)
+        );
+        assert_eq!(metrics.latest()[CPU_LOAD_INDEX], None);
+        assert!(metrics.query(UPTIME_INDEX as u8, 0, 0).unwrap().is_empty());
+        assert!(metrics.query(4, 0, u32::MAX).is_none());
+    }
+
+    #[test]
+    fn rejects_priorities_above_elevated() {
+        let mut metrics = InterpolatedMetrics::default();
+        metrics
+            .set_priority(CPU_LOAD_INDEX as u8, ELEVATED)
+            .unwrap();
+        assert_eq!(metrics.priority(CPU_LOAD_INDEX), ELEVATED);
+        assert_eq!(
+            metrics.set_priority(CPU_LOAD_INDEX as u8, ELEVATED + 1),
+            Err(ReqError::NotSupported)
+        );
+        assert_eq!(
+            metrics.set_priority(4, BEST_EFFORT),
+            Err(ReqError::NotSupported)
+        );
+    }
+}
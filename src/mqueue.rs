@@ -0,0 +1,145 @@
+use crate::{
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotify, CharacteristicNotifyMethod, CharacteristicWrite,
+        CharacteristicWriteMethod, ReqError,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use nix::{
+    errno::Errno,
+    mqueue::{mq_open, mq_receive, mq_send, MQ_OFlag, MqAttr, MqdT},
+    sys::stat::Mode,
+};
+use tokio::sync::mpsc;
+
+/// Queue other processes post to, forwarded as `MQ_NOTIFY`
+const INBOUND: &str = "/ble_raspi_in";
+
+/// Queue `MQ_PUBLISH` writes are posted to
+const OUTBOUND: &str = "/ble_raspi_out";
+
+/// Messages above one BLE notification make little sense
+const MAX_MESSAGE_SIZE: usize = 512;
+const MAX_MESSAGES: usize = 10;
+
+fn open(name: &str, flags: MQ_OFlag) -> nix::Result<MqdT> {
+    let attr = MqAttr::new(0, MAX_MESSAGES as _, MAX_MESSAGE_SIZE as _, 0);
+    mq_open(
+        name,
+        flags | MQ_OFlag::O_CREAT,
+        Mode::from_bits_truncate(0o660),
+        Some(&attr),
+    )
+}
+
+/// Blocks on the inbound queue and hands each message to the async side.
+fn receive(inbound: MqdT, messages: mpsc::Sender<Vec<u8>>) {
+    let mut buffer = [0u8; MAX_MESSAGE_SIZE];
+    let mut priority = 0;
+    loop {
+        match mq_receive(&inbound, &mut buffer, &mut priority) {
+            Ok(len) => {
+                if messages.blocking_send(buffer[..len].to_vec()).is_err() {
+                    break;
+                }
+            }
+            Err(Errno::EINTR) => continue,
+            Err(err) => {
+                eprintln!("Stopping message queue {INBOUND}: {err}");
+                break;
+            }
+        }
+    }
+}
+
+async fn serve(
+    control: CharacteristicControl,
+    mut messages: mpsc::Receiver<Vec<u8>>,
+    retry: RetryPolicy,
+) {
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut sequence = NotifySequence::new("message queue");
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting message queue notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "message queue");
+                        writer_opt = Some(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Some(message) = messages.recv() => {
+                if let Some(writer) = &mut writer_opt {
+                    if retry.notify(writer, &sequence.stamp(&message)).await.is_err() {
+                        writer_opt = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Opens both message queues and creates the `MQ_NOTIFY` and `MQ_PUBLISH` characteristics.
+pub fn characteristics(retry: &RetryPolicies) -> nix::Result<Vec<Characteristic>> {
+    let inbound = open(INBOUND, MQ_OFlag::O_RDONLY)?;
+    // Non-blocking, so a full queue without reader rejects the write instead of stalling
+    let outbound = open(OUTBOUND, MQ_OFlag::O_WRONLY | MQ_OFlag::O_NONBLOCK)?;
+
+    let (message_tx, message_rx) = mpsc::channel(MAX_MESSAGES);
+    tokio::task::spawn_blocking(move || receive(inbound, message_tx));
+    let (control, control_handle) = characteristic_control();
+    crate::tasks::spawn(
+        "mq-notify",
+        serve(control, message_rx, retry.get("mq_notify")),
+    );
+
+    Ok(vec![
+        // Messages from /ble_raspi_in
+        Characteristic {
+            uuid: crate::MQ_NOTIFY,
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle,
+            ..Default::default()
+        },
+        // Messages to /ble_raspi_out
+        Characteristic {
+            uuid: crate::MQ_PUBLISH,
+            write: Some(CharacteristicWrite {
+                write: true,
+                method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                    let result = match mq_send(&outbound, &value, 0) {
+                        Ok(()) => Ok(()),
+                        Err(Errno::EAGAIN) => Err(ReqError::InProgress),
+                        Err(Errno::EMSGSIZE) => Err(ReqError::InvalidValueLength),
+                        Err(err) => {
+                            eprintln!("Posting to {OUTBOUND} failed: {err}");
+                            Err(ReqError::Failed)
+                        }
+                    };
+                    async move { result }.boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    ])
+}
@@ -0,0 +1,258 @@
+use crate::{
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::{
+    gatt::{
+        local::{
+            characteristic_control, Characteristic, CharacteristicControl,
+            CharacteristicControlEvent, CharacteristicNotify, CharacteristicNotifyMethod,
+            CharacteristicRead, CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+        },
+        CharacteristicWriter,
+    },
+    Adapter, Address, DeviceEvent, DeviceProperty,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::mpsc;
+
+/// Payload encodings this server produces
+const ENCODINGS: [&str; 2] = ["cbor", "binary"];
+
+/// Oldest payload layout version this server can still produce
+const MIN_VERSION: u16 = crate::payload::VERSION;
+
+/// Optional capabilities this server offers
+const FEATURES: [&str; 1] = ["history"];
+
+#[derive(Debug, Deserialize)]
+struct Offer {
+    /// In order of client preference
+    encoding: Vec<String>,
+    min_version: u16,
+    max_version: u16,
+    #[serde(default)]
+    features: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Agreement {
+    encoding: String,
+    version: u16,
+    features: Vec<String>,
+}
+
+/// Picks the client's most preferred encoding and highest version both sides support.
+fn agree(offer: Offer) -> Option<Agreement> {
+    let encoding = offer
+        .encoding
+        .into_iter()
+        .find(|encoding| ENCODINGS.contains(&encoding.as_str()))?;
+    let version = offer.max_version.min(crate::payload::VERSION);
+    if version < offer.min_version.max(MIN_VERSION) {
+        return None;
+    }
+    let features = offer
+        .features
+        .into_iter()
+        .filter(|feature| FEATURES.contains(&feature.as_str()))
+        .collect();
+    Some(Agreement {
+        encoding,
+        version,
+        features,
+    })
+}
+
+/// Per central agreement, readable until the central disconnects
+type Agreed = Arc<Mutex<HashMap<Address, Vec<u8>>>>;
+
+/// Drops the agreement of `address` once its connection drops.
+async fn forget_on_disconnect(adapter: Adapter, address: Address, agreed: Agreed) {
+    let device_events = match adapter.device(address) {
+        Ok(device) => device.events().await,
+        Err(err) => Err(err),
+    };
+    match device_events {
+        Ok(device_events) => {
+            pin_mut!(device_events);
+            while let Some(event) = device_events.next().await {
+                if let DeviceEvent::PropertyChanged(DeviceProperty::Connected(false)) = event {
+                    break;
+                }
+            }
+        }
+        Err(err) => eprintln!("Cannot watch {address}, dropping its agreement: {err}"),
+    }
+    agreed.lock().unwrap().remove(&address);
+}
+
+/// Notifies each agreement only to the central that negotiated it.
+async fn serve(
+    control: CharacteristicControl,
+    mut agreements: mpsc::Receiver<(Address, Vec<u8>)>,
+    retry: RetryPolicy,
+) {
+    let mut writers: HashMap<Address, CharacteristicWriter> = HashMap::new();
+    let mut sequence = NotifySequence::new("protocol negotiation");
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting protocol negotiation notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "protocol negotiation");
+                        writers.insert(notifier.device_address(), notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Some((device, agreement)) = agreements.recv() => {
+                if let Some(writer) = writers.get_mut(&device) {
+                    if retry.notify(writer, &sequence.stamp(&agreement)).await.is_err() {
+                        writers.remove(&device);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `PROTO_NEGOTIATE` characteristic.
+///
+/// Clients write a CBOR map with `encoding`, `min_version`, `max_version` and
+/// `features`. The agreed CBOR map of `encoding`, `version` and `features` is
+/// notified to the writing central and can be read back by it until it
+/// disconnects.
+/// Writes without a common encoding or version are rejected.
+///
+/// The agreement is advisory: every encoding and version offered is one the
+/// server already produces for all centrals, so nothing is switched per
+/// central. Clients use it to detect a server they cannot talk to.
+pub fn characteristic(adapter: Adapter, retry: &RetryPolicies) -> Characteristic {
+    let (control, control_handle) = characteristic_control();
+    let (agreement_tx, agreement_rx) = mpsc::channel(1);
+    crate::tasks::spawn(
+        "negotiate",
+        serve(control, agreement_rx, retry.get("proto_negotiate")),
+    );
+    let agreed: Agreed = Arc::default();
+    let read_agreed = agreed.clone();
+
+    Characteristic {
+        uuid: crate::PROTO_NEGOTIATE,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(move |req| {
+                let value = read_agreed
+                    .lock()
+                    .unwrap()
+                    .get(&req.device_address)
+                    .cloned()
+                    .unwrap_or_default();
+                async move { Ok(value) }.boxed()
+            }),
+            ..Default::default()
+        }),
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, req| {
+                let device = req.device_address;
+                let adapter = adapter.clone();
+                let agreed = agreed.clone();
+                let agreement_tx = agreement_tx.clone();
+                async move {
+                    let offer: Offer =
+                        ciborium::from_reader(value.as_slice()).map_err(|_| ReqError::Failed)?;
+                    let agreement = agree(offer).ok_or(ReqError::NotSupported)?;
+                    println!("Negotiated {agreement:?} with {device}");
+                    let mut encoded = Vec::new();
+                    ciborium::into_writer(&agreement, &mut encoded)
+                        .map_err(|_| ReqError::Failed)?;
+                    let newly = agreed
+                        .lock()
+                        .unwrap()
+                        .insert(device, encoded.clone())
+                        .is_none();
+                    if newly {
+                        crate::tasks::spawn(
+                            &format!("negotiate-{device}"),
+                            forget_on_disconnect(adapter, device, agreed),
+                        );
+                    }
+                    let _ = agreement_tx.send((device, encoded)).await;
+                    Ok(())
+                }
+                .boxed()
+            })),
+            ..Default::default()
+        }),
+        notify: Some(CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Io,
+            ..Default::default()
+        }),
+        control_handle,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer(encoding: &[&str], min_version: u16, max_version: u16, features: &[&str]) -> Offer {
+        Offer {
+            encoding: encoding.iter().map(|e| e.to_string()).collect(),
+            min_version,
+            max_version,
+            features: features.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn agrees_on_the_preferred_encoding_and_highest_version() {
+        let agreement = agree(offer(&["msgpack", "binary", "cbor"], 1, u16::MAX, &[])).unwrap();
+        assert_eq!(agreement.encoding, "binary");
+        assert_eq!(agreement.version, crate::payload::VERSION);
+        assert!(agreement.features.is_empty());
+    }
+
+    #[test]
+    fn rejects_offers_without_a_common_encoding() {
+        assert!(agree(offer(&["msgpack", "json"], 1, u16::MAX, &[])).is_none());
+        assert!(agree(offer(&[], 1, u16::MAX, &[])).is_none());
+    }
+
+    #[test]
+    fn rejects_offers_without_a_common_version() {
+        let newer = crate::payload::VERSION + 1;
+        assert!(agree(offer(&["cbor"], newer, u16::MAX, &[])).is_none());
+        assert!(agree(offer(&["cbor"], 0, MIN_VERSION - 1, &[])).is_none());
+        // Inverted ranges have no version either
+        assert!(agree(offer(&["cbor"], MIN_VERSION, MIN_VERSION - 1, &[])).is_none());
+    }
+
+    #[test]
+    fn keeps_only_offered_features_the_server_has() {
+        let agreement = agree(offer(
+            &["cbor"],
+            1,
+            u16::MAX,
+            &["predictions", "history", "alerts"],
+        ))
+        .unwrap();
+        assert_eq!(agreement.features, ["history"]);
+    }
+}
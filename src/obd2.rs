@@ -0,0 +1,120 @@
+use crate::response::Responder;
+use bluer::gatt::local::{
+    Characteristic, CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+};
+use futures::FutureExt;
+use std::time::Duration;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::mpsc,
+    time,
+};
+use tokio_serial::{SerialPortBuilderExt, SerialStream};
+
+const PORT: &str = "/dev/ttyUSB0";
+const BAUD_RATE: u32 = 38400;
+
+/// Reset, then echo, linefeeds, spaces and headers off, automatic protocol
+const INIT_COMMANDS: [&str; 6] = ["ATZ", "ATE0", "ATL0", "ATS0", "ATH0", "ATSP0"];
+
+/// Upper bound for a reply; the first query after `ATSP0` searches protocols
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Leading byte of the reply sent when a PID could not be read
+const NEGATIVE_RESPONSE: u8 = 0x7F;
+
+struct Elm327 {
+    port: SerialStream,
+}
+
+impl Elm327 {
+    async fn open() -> std::io::Result<Elm327> {
+        let port = tokio_serial::new(PORT, BAUD_RATE).open_native_async()?;
+        let mut elm = Elm327 { port };
+        for command in INIT_COMMANDS {
+            let reply = elm.send(command).await?;
+            println!("ELM327 {command}: {reply}");
+        }
+        Ok(elm)
+    }
+
+    /// Sends a command and returns the reply up to the `>` prompt.
+    async fn send(&mut self, command: &str) -> std::io::Result<String> {
+        self.port
+            .write_all(format!("{command}\r").as_bytes())
+            .await?;
+        let mut reply = Vec::new();
+        let mut byte = [0u8; 1];
+        time::timeout(REPLY_TIMEOUT, async {
+            loop {
+                self.port.read_exact(&mut byte).await?;
+                if byte[0] == b'>' {
+                    return std::io::Result::Ok(());
+                }
+                reply.push(byte[0]);
+            }
+        })
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "ELM327 timed out"))??;
+        Ok(String::from_utf8_lossy(&reply).trim().to_string())
+    }
+
+    /// Queries a PID and returns the raw response bytes, e.g. `41 0C 1A F8`.
+    async fn query(&mut self, mode: u8, pid: u8) -> std::io::Result<Option<Vec<u8>>> {
+        let reply = self.send(&format!("{mode:02X}{pid:02X}")).await?;
+        // Skip status lines such as "SEARCHING..." and keep the first data line
+        let Some(line) = reply.lines().map(str::trim).find(|line| {
+            !line.is_empty() && line.chars().all(|c| c.is_ascii_hexdigit() || c == ' ')
+        }) else {
+            return Ok(None);
+        };
+        let hex: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+        let bytes = (0..hex.len() / 2)
+            .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .ok();
+        Ok(bytes)
+    }
+}
+
+async fn serve(mut elm: Elm327, mut requests: mpsc::Receiver<(u8, u8)>, responder: Responder) {
+    while let Some((mode, pid)) = requests.recv().await {
+        let response = match elm.query(mode, pid).await {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => vec![NEGATIVE_RESPONSE, mode, pid],
+            Err(err) => {
+                eprintln!("OBD-II query {mode:02X}{pid:02X} failed: {err}");
+                vec![NEGATIVE_RESPONSE, mode, pid]
+            }
+        };
+        let _ = responder.send(response).await;
+    }
+}
+
+/// Connects to the ELM327 adapter and creates the `OBD2_PID` characteristic.
+///
+/// Writes are two bytes, mode and PID. The raw reply, or `0x7F mode pid`
+/// on failure, is notified on `WRITE_REQUEST_RESPONSE`.
+pub async fn characteristic(responder: Responder) -> std::io::Result<Characteristic> {
+    let elm = Elm327::open().await?;
+    let (request_tx, request_rx) = mpsc::channel(4);
+    crate::tasks::spawn("obd2", serve(elm, request_rx, responder));
+
+    Ok(Characteristic {
+        uuid: crate::OBD2_PID,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                let result = match value[..] {
+                    [mode, pid] => request_tx
+                        .try_send((mode, pid))
+                        .map_err(|_| ReqError::InProgress),
+                    _ => Err(ReqError::InvalidValueLength),
+                };
+                async move { result }.boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,89 @@
+use bluer::{
+    agent::{Agent, AgentHandle, ReqError as AgentError},
+    gatt::local::{Characteristic, CharacteristicWrite, CharacteristicWriteMethod, ReqError},
+    Address, Session,
+};
+use futures::FutureExt;
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::Read,
+    sync::{Arc, Mutex},
+};
+
+/// Digits in the pairing code
+const CODE_LEN: usize = 6;
+
+/// Draws a random six digit code from the kernel's entropy pool.
+fn generate_code() -> std::io::Result<String> {
+    let mut bytes = [0u8; 4];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(format!("{:06}", u32::from_le_bytes(bytes) % 1_000_000))
+}
+
+/// Accepts a pairing request only from a device that wrote the code.
+fn check(verified: &Mutex<HashSet<Address>>, device: Address) -> Result<(), AgentError> {
+    if verified.lock().unwrap().contains(&device) {
+        println!("Accepting pairing request from {device}");
+        Ok(())
+    } else {
+        println!("Rejecting pairing request from {device} without pairing code");
+        Err(AgentError::Rejected)
+    }
+}
+
+/// Registers the pairing agent and creates the `SENSOR_PAIRING_CODE` characteristic.
+///
+/// A six digit code is generated and logged on startup. Clients write it as
+/// ASCII digits before pairing; the agent rejects pairing and authorization
+/// requests from devices that have not. The returned handle keeps the agent
+/// registered.
+pub async fn register(session: &Session) -> bluer::Result<(AgentHandle, Characteristic)> {
+    let code = generate_code()?;
+    println!("Pairing code is {code}");
+    let verified = Arc::new(Mutex::new(HashSet::new()));
+
+    let confirm_verified = verified.clone();
+    let authorize_verified = verified.clone();
+    let service_verified = verified.clone();
+    let agent = Agent {
+        request_default: true,
+        request_confirmation: Some(Box::new(move |req| {
+            let result = check(&confirm_verified, req.device);
+            async move { result }.boxed()
+        })),
+        request_authorization: Some(Box::new(move |req| {
+            let result = check(&authorize_verified, req.device);
+            async move { result }.boxed()
+        })),
+        authorize_service: Some(Box::new(move |req| {
+            let result = check(&service_verified, req.device);
+            async move { result }.boxed()
+        })),
+        ..Default::default()
+    };
+    let handle = session.register_agent(agent).await?;
+
+    let characteristic = Characteristic {
+        uuid: crate::SENSOR_PAIRING_CODE,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, req| {
+                let result = if value.len() != CODE_LEN {
+                    Err(ReqError::InvalidValueLength)
+                } else if value == code.as_bytes() {
+                    println!("Pairing code accepted from {}", req.device_address);
+                    verified.lock().unwrap().insert(req.device_address);
+                    Ok(())
+                } else {
+                    println!("Wrong pairing code from {}", req.device_address);
+                    Err(ReqError::NotAuthorized)
+                };
+                async move { result }.boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    Ok((handle, characteristic))
+}
@@ -0,0 +1,46 @@
+use bluer::{
+    agent::{Agent, AgentHandle},
+    Session,
+};
+use futures::FutureExt;
+
+/// Registers the default pairing agent.
+///
+/// Without `passkey` the agent is display only: BlueZ generates a passkey
+/// per pairing, which is printed for the user to type on the central. This
+/// is what phones expect. With a fixed `passkey` the agent is keyboard only
+/// and answers passkey and PIN code requests with it, for centrals that ask
+/// their user to enter the same passkey. The returned handle keeps the agent
+/// registered.
+pub async fn register(session: &Session, passkey: Option<u32>) -> bluer::Result<AgentHandle> {
+    let agent = match passkey {
+        None => Agent {
+            request_default: true,
+            display_passkey: Some(Box::new(|req| {
+                println!("Passkey for pairing {} is {:06}", req.device, req.passkey);
+                async { Ok(()) }.boxed()
+            })),
+            display_pin_code: Some(Box::new(|req| {
+                println!("PIN code for pairing {} is {}", req.device, req.pincode);
+                async { Ok(()) }.boxed()
+            })),
+            ..Default::default()
+        },
+        Some(passkey) => {
+            println!("Pairing with fixed passkey {passkey:06}");
+            Agent {
+                request_default: true,
+                request_passkey: Some(Box::new(move |req| {
+                    println!("Answering passkey request from {}", req.device);
+                    async move { Ok(passkey) }.boxed()
+                })),
+                request_pin_code: Some(Box::new(move |req| {
+                    println!("Answering PIN code request from {}", req.device);
+                    async move { Ok(format!("{passkey:06}")) }.boxed()
+                })),
+                ..Default::default()
+            }
+        }
+    };
+    session.register_agent(agent).await
+}
@@ -0,0 +1,193 @@
+use crate::encoding::Encode;
+use bluer::gatt::local::{Characteristic, CharacteristicRead};
+use futures::FutureExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Layout version of notify payloads, 2 added the timestamp, 3 the sequence number,
+/// 4 made all values little endian as specified in [encoding](crate::encoding),
+/// 5 split payloads exceeding the MTU into [fragments]
+pub const VERSION: u16 = 0x0005;
+
+/// Bytes the sequence number and timestamp add in front of every notify payload
+pub const HEADER_LEN: usize = 6;
+
+/// Set in the sequence number of every fragment but the last, sequence
+/// numbers themselves wrap around below it
+pub const MORE_FRAGMENTS: u16 = 0x8000;
+
+/// Current time in seconds since the Unix epoch
+pub fn unix_timestamp() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as u32)
+        .unwrap_or_default()
+}
+
+/// A value annotated with its measurement time, encoded as a `u32` LE Unix
+/// timestamp in seconds followed by the value's own encoding.
+#[derive(Debug, Clone)]
+pub struct TimestampedPayload<T> {
+    timestamp: u32,
+    inner: T,
+}
+
+impl<T: Encode> TimestampedPayload<T> {
+    /// Stamps `inner` with the current time.
+    pub fn new(inner: T) -> Self {
+        TimestampedPayload {
+            timestamp: unix_timestamp(),
+            inner,
+        }
+    }
+}
+
+impl<T: Encode> Encode for TimestampedPayload<T> {
+    fn encode(&self) -> Vec<u8> {
+        let mut payload = self.timestamp.to_le_bytes().to_vec();
+        payload.extend(self.inner.encode());
+        payload
+    }
+}
+
+/// A payload led by a `u16` LE sequence number.
+#[derive(Debug, Clone)]
+pub struct SequencedPayload<T> {
+    sequence: u16,
+    inner: T,
+}
+
+impl<T: Encode> Encode for SequencedPayload<T> {
+    fn encode(&self) -> Vec<u8> {
+        let mut payload = self.sequence.to_le_bytes().to_vec();
+        payload.extend(self.inner.encode());
+        payload
+    }
+}
+
+/// Numbers the notifications of one characteristic, so clients can detect
+/// duplicate and out of order deliveries.
+#[derive(Debug)]
+pub struct NotifySequence {
+    name: String,
+    next: u16,
+}
+
+impl NotifySequence {
+    pub fn new(name: &str) -> Self {
+        NotifySequence {
+            name: name.to_string(),
+            next: 0,
+        }
+    }
+
+    /// Encodes `value` behind the next sequence number and the current time.
+    pub fn stamp<T: Encode>(&mut self, value: T) -> Vec<u8> {
+        let sequence = self.next;
+        self.next = (self.next + 1) % MORE_FRAGMENTS;
+        if self.next == 0 {
+            log::info!("Notify sequence of {} wrapped around", self.name);
+        }
+        SequencedPayload {
+            sequence,
+            inner: TimestampedPayload::new(value),
+        }
+        .encode()
+    }
+}
+
+/// Splits a `stamped` payload into notifications of at most `mtu` bytes.
+///
+/// Each fragment repeats the sequence number and timestamp in front of its
+/// part of the value, with [MORE_FRAGMENTS] set on all but the last, so
+/// clients reassemble by sequence number. Payloads that fit are returned as is.
+pub fn fragments(stamped: &[u8], mtu: usize) -> Vec<Vec<u8>> {
+    if stamped.len() <= mtu {
+        return vec![stamped.to_vec()];
+    }
+    let (header, value) = stamped.split_at(HEADER_LEN);
+    let sequence = u16::from_le_bytes([header[0], header[1]]);
+    let parts: Vec<&[u8]> = value.chunks(mtu - HEADER_LEN).collect();
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let flagged = if i + 1 == parts.len() {
+                sequence
+            } else {
+                sequence | MORE_FRAGMENTS
+            };
+            [&flagged.to_le_bytes()[..], &header[2..], part].concat()
+        })
+        .collect()
+}
+
+/// Creates the `PROFILE_VERSION` characteristic, the payload layout version as `u16` LE.
+pub fn version_characteristic() -> Characteristic {
+    Characteristic {
+        uuid: crate::PROFILE_VERSION,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(|_req| async move { Ok(VERSION.to_le_bytes().to_vec()) }.boxed()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequence_of(payload: &[u8]) -> u16 {
+        u16::from_le_bytes([payload[0], payload[1]])
+    }
+
+    #[test]
+    fn sequence_increments_per_notification() {
+        let mut sequence = NotifySequence::new("test");
+        for expected in 0..100u16 {
+            let payload = sequence.stamp(0x2au8);
+            assert_eq!(payload.len(), HEADER_LEN + 1);
+            assert_eq!(sequence_of(&payload), expected);
+            assert_eq!(payload[HEADER_LEN], 0x2a);
+        }
+    }
+
+    #[test]
+    fn sequence_wraps_around() {
+        let mut sequence = NotifySequence::new("test");
+        sequence.next = MORE_FRAGMENTS - 1;
+        assert_eq!(sequence_of(&sequence.stamp(0u8)), MORE_FRAGMENTS - 1);
+        assert_eq!(sequence_of(&sequence.stamp(0u8)), 0);
+        assert_eq!(sequence_of(&sequence.stamp(0u8)), 1);
+    }
+
+    #[test]
+    fn fitting_payloads_are_not_fragmented() {
+        let stamped = NotifySequence::new("test").stamp(&[7u8; 12][..]);
+        assert_eq!(fragments(&stamped, 18), [stamped]);
+    }
+
+    #[test]
+    fn fragments_repeat_the_header() {
+        let mut sequence = NotifySequence::new("test");
+        sequence.next = 42;
+        let value: Vec<u8> = (0..30).collect();
+        let stamped = sequence.stamp(&value[..]);
+        let fragments = fragments(&stamped, 18);
+
+        assert_eq!(fragments.len(), 3);
+        assert!(fragments.iter().all(|fragment| fragment.len() <= 18));
+        assert_eq!(sequence_of(&fragments[0]), 42 | MORE_FRAGMENTS);
+        assert_eq!(sequence_of(&fragments[1]), 42 | MORE_FRAGMENTS);
+        assert_eq!(sequence_of(&fragments[2]), 42);
+        assert!(fragments
+            .iter()
+            .all(|fragment| fragment[2..HEADER_LEN] == stamped[2..HEADER_LEN]));
+        let reassembled: Vec<u8> = fragments
+            .iter()
+            .flat_map(|fragment| fragment[HEADER_LEN..].to_vec())
+            .collect();
+        assert_eq!(reassembled, value);
+    }
+}
@@ -0,0 +1,221 @@
+use bluer::{
+    gatt::local::{
+        Application, Characteristic, CharacteristicRead, CharacteristicWriteMethod, ReqError,
+    },
+    Address,
+};
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+use tokio::{fs, time};
+
+/// Largest `PEER_STATS` value, fits one long ATT read
+const MAX_STATS_SIZE: usize = 512;
+
+/// How often the statistics are written to the state file
+const SAVE_INTERVAL: Duration = Duration::from_secs(300);
+
+static STATS: LazyLock<Mutex<HashMap<Address, PeerStats>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Cumulative traffic of one client, kept across its reconnections
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct PeerStats {
+    bytes_received: u64,
+    bytes_sent: u64,
+    command_count: u32,
+    error_count: u32,
+    /// Seconds since the Unix epoch
+    last_seen: u32,
+    /// Names of the characteristics the client subscribed to
+    subscriptions: BTreeSet<String>,
+}
+
+/// Contents of the state file
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    #[serde(default)]
+    peer_stats: BTreeMap<String, PeerStats>,
+}
+
+/// Loads the statistics saved in the state file at `path`, if there is one.
+pub fn restore(path: &Path) {
+    let state = match std::fs::read(path) {
+        Ok(state) => state,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+        Err(err) => {
+            eprintln!("Reading state file {} failed: {err}", path.display());
+            return;
+        }
+    };
+    let state: State = match serde_json::from_slice(&state) {
+        Ok(state) => state,
+        Err(err) => {
+            eprintln!("Parsing state file {} failed: {err}", path.display());
+            return;
+        }
+    };
+    let mut stats = STATS.lock().unwrap();
+    for (address, peer_stats) in state.peer_stats {
+        match address.parse() {
+            Ok(address) => {
+                stats.insert(address, peer_stats);
+            }
+            Err(_) => eprintln!("Ignoring statistics of invalid peer address {address}"),
+        }
+    }
+    println!("Restored statistics of {} peers", stats.len());
+}
+
+/// Writes the statistics to the state file at `path`, replacing it atomically.
+pub async fn save(path: &Path) -> io::Result<()> {
+    let state = {
+        let stats = STATS.lock().unwrap();
+        let state = State {
+            peer_stats: stats
+                .iter()
+                .map(|(address, stats)| (address.to_string(), stats.clone()))
+                .collect(),
+        };
+        serde_json::to_vec_pretty(&state)?
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, state).await?;
+    fs::rename(&partial, path).await
+}
+
+/// Saves the statistics to `path` every [SAVE_INTERVAL].
+pub async fn persist(path: PathBuf) {
+    let mut interval = time::interval(SAVE_INTERVAL);
+    // The first tick completes immediately, there is nothing new to save yet
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        if let Err(err) = save(&path).await {
+            eprintln!("Saving peer statistics failed: {err}");
+        }
+    }
+}
+
+fn update(peer: Address, f: impl FnOnce(&mut PeerStats)) {
+    let mut stats = STATS.lock().unwrap();
+    let peer_stats = stats.entry(peer).or_default();
+    peer_stats.last_seen = crate::payload::unix_timestamp();
+    f(peer_stats);
+}
+
+pub fn record_sent(peer: Address, len: usize) {
+    update(peer, |stats| stats.bytes_sent += len as u64);
+}
+
+pub fn record_error(peer: Address) {
+    update(peer, |stats| stats.error_count += 1);
+}
+
+pub fn record_command(peer: Address) {
+    update(peer, |stats| stats.command_count += 1);
+}
+
+pub fn record_subscription(peer: Address, name: &str) {
+    update(peer, |stats| {
+        stats.subscriptions.insert(name.to_string());
+    });
+}
+
+/// Counts the bytes each client reads and writes, and failed requests, on all
+/// characteristics of `app`. Notifications are counted where they are sent.
+pub fn instrument(app: &mut Application) {
+    for service in &mut app.services {
+        for characteristic in &mut service.characteristics {
+            if let Some(read) = &mut characteristic.read {
+                let inner = std::mem::replace(&mut read.fun, Box::new(|_| unreachable!()));
+                read.fun = Box::new(move |req| {
+                    let peer = req.device_address;
+                    let offset = req.offset as usize;
+                    let value = inner(req);
+                    async move {
+                        let value = value.await;
+                        match &value {
+                            Ok(value) => record_sent(peer, value.len().saturating_sub(offset)),
+                            Err(_) => record_error(peer),
+                        }
+                        value
+                    }
+                    .boxed()
+                });
+            }
+            if let Some(write) = &mut characteristic.write {
+                if let CharacteristicWriteMethod::Fun(inner) = &mut write.method {
+                    let inner = std::mem::replace(inner, Box::new(|_, _| unreachable!()));
+                    write.method = CharacteristicWriteMethod::Fun(Box::new(move |value, req| {
+                        let peer = req.device_address;
+                        let len = value.len() as u64;
+                        update(peer, |stats| stats.bytes_received += len);
+                        let result = inner(value, req);
+                        async move {
+                            let result = result.await;
+                            if result.is_err() {
+                                record_error(peer);
+                            }
+                            result
+                        }
+                        .boxed()
+                    }));
+                }
+            }
+        }
+    }
+}
+
+/// Encodes the most recently seen peers that fit in [MAX_STATS_SIZE] as a CBOR map.
+fn encode() -> Result<Vec<u8>, ciborium::ser::Error<io::Error>> {
+    let stats = STATS.lock().unwrap();
+    let mut by_last_seen: Vec<_> = stats.iter().collect();
+    by_last_seen.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.last_seen));
+    let mut len = by_last_seen.len();
+    loop {
+        let by_address = by_last_seen[..len]
+            .iter()
+            .map(|(address, stats)| (address.to_string(), stats))
+            .collect::<BTreeMap<_, _>>();
+        let mut value = Vec::new();
+        ciborium::into_writer(&by_address, &mut value)?;
+        if value.len() <= MAX_STATS_SIZE || len == 0 {
+            return Ok(value);
+        }
+        len -= 1;
+    }
+}
+
+/// Creates the `PEER_STATS` characteristic.
+///
+/// Reads return a CBOR map from peer address to `bytes_received`,
+/// `bytes_sent`, `command_count`, `error_count`, `last_seen` and
+/// `subscriptions`, keeping the most recently seen peers that fit 512 bytes.
+/// The statistics are kept in the state file across restarts.
+pub fn characteristic() -> Characteristic {
+    Characteristic {
+        uuid: crate::PEER_STATS,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(|_req| {
+                let result = encode().map_err(|err| {
+                    eprintln!("Encoding peer statistics failed: {err}");
+                    ReqError::Failed
+                });
+                async move { result }.boxed()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
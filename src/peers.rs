@@ -0,0 +1,320 @@
+use crate::{
+    events::SystemEvent,
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::{
+    gatt::{
+        local::{
+            characteristic_control, Characteristic, CharacteristicControl,
+            CharacteristicControlEvent, CharacteristicNotify, CharacteristicNotifyMethod,
+            CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+        },
+        CharacteristicWriter,
+    },
+    Adapter, AdapterEvent, Address, DeviceEvent, DeviceProperty,
+};
+use futures::{pin_mut, stream::FuturesUnordered, FutureExt, StreamExt};
+use std::{collections::HashSet, sync::OnceLock, time::Duration};
+use tokio::{sync::mpsc, time};
+
+/// MTU reported for a peer that disconnected
+const DISCONNECTED: u16 = 0;
+
+/// ATT MTU of a connection until the central exchanges a larger one
+const DEFAULT_ATT_MTU: u16 = 23;
+
+/// Bytes BlueZ withholds from [CharacteristicWriter::mtu]
+const BLUEZ_RESERVED: u16 = 5;
+
+/// How long after connecting a central gets to reveal its negotiated MTU
+/// before [SystemEvent::BleClientConnected] goes out with [DEFAULT_ATT_MTU]
+const MTU_SETTLE: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+enum PeerEvent {
+    Connected(Address),
+    /// ATT MTU seen on a subscription of the peer
+    Mtu(Address, u16),
+    Disconnected(Address),
+}
+
+static OBSERVER: OnceLock<mpsc::UnboundedSender<PeerEvent>> = OnceLock::new();
+
+/// Reports the MTU of a peer subscribing to any notify characteristic, and the
+/// subscription to `name` in its statistics.
+pub fn observe(writer: &CharacteristicWriter, name: &str) {
+    crate::peer_stats::record_subscription(writer.device_address(), name);
+    if let Some(observer) = OBSERVER.get() {
+        let mtu = (writer.mtu() as u16).saturating_add(BLUEZ_RESERVED);
+        let _ = observer.send(PeerEvent::Mtu(writer.device_address(), mtu));
+    }
+}
+
+/// Reports every connection and disconnection of `address`.
+async fn watch_connection(
+    adapter: Adapter,
+    address: Address,
+    events: mpsc::UnboundedSender<PeerEvent>,
+) {
+    let device = match adapter.device(address) {
+        Ok(device) => device,
+        Err(err) => {
+            eprintln!("Cannot watch peer {address}: {err}");
+            return;
+        }
+    };
+    let device_events = match device.events().await {
+        Ok(device_events) => device_events,
+        Err(err) => {
+            eprintln!("Cannot watch peer {address}: {err}");
+            return;
+        }
+    };
+    if device.is_connected().await.unwrap_or(false) {
+        let _ = events.send(PeerEvent::Connected(address));
+    }
+    pin_mut!(device_events);
+    while let Some(event) = device_events.next().await {
+        let event = match event {
+            DeviceEvent::PropertyChanged(DeviceProperty::Connected(true)) => {
+                PeerEvent::Connected(address)
+            }
+            DeviceEvent::PropertyChanged(DeviceProperty::Connected(false)) => {
+                PeerEvent::Disconnected(address)
+            }
+            _ => continue,
+        };
+        let _ = events.send(event);
+    }
+}
+
+/// Watches the connections of every device BlueZ knows of.
+async fn watch_connections(adapter: Adapter, events: mpsc::UnboundedSender<PeerEvent>) {
+    let adapter_events = match adapter.events().await {
+        Ok(adapter_events) => adapter_events,
+        Err(err) => {
+            eprintln!("Cannot watch peer connections: {err}");
+            return;
+        }
+    };
+    let known = adapter.device_addresses().await.unwrap_or_default();
+    let added = adapter_events.filter_map(|event| async move {
+        match event {
+            AdapterEvent::DeviceAdded(address) => Some(address),
+            _ => None,
+        }
+    });
+    let addresses = futures::stream::iter(known).chain(added);
+    pin_mut!(addresses);
+
+    // Watched within this task, BlueZ may know of many devices
+    let mut watches = FuturesUnordered::new();
+    loop {
+        tokio::select! {
+            Some(address) = addresses.next() => {
+                watches.push(watch_connection(adapter.clone(), address, events.clone()));
+            },
+            Some(()) = watches.next() => {},
+            else => break,
+        }
+    }
+}
+
+async fn serve(
+    control: CharacteristicControl,
+    mut events: mpsc::UnboundedReceiver<PeerEvent>,
+    retry: RetryPolicy,
+) {
+    // Slot index is the peer index, freed on disconnect
+    let mut peers: Vec<Option<(Address, u16)>> = Vec::new();
+    // Connected peers whose connection was not published yet
+    let mut unannounced: HashSet<Address> = HashSet::new();
+    let mut settles = FuturesUnordered::new();
+    let mut writers: Vec<CharacteristicWriter> = Vec::new();
+    let mut sequence = NotifySequence::new("MTU changes");
+    pin_mut!(control);
+
+    loop {
+        let (address, mtu) = tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting MTU change notify request with MTU {}", notifier.mtu());
+                        observe(&notifier, "MTU change");
+                        writers.push(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+                continue;
+            },
+            Some(address) = settles.next() => {
+                if unannounced.remove(&address) {
+                    crate::events::publish(SystemEvent::BleClientConnected {
+                        addr: address,
+                        mtu: DEFAULT_ATT_MTU,
+                    });
+                }
+                continue;
+            },
+            Some(event) = events.recv() => match event {
+                PeerEvent::Connected(address) => {
+                    // It may have subscribed before BlueZ reported the connection
+                    let known_mtu = peers.iter().flatten().find(|(a, _)| *a == address);
+                    if let Some(&(_, mtu)) = known_mtu {
+                        crate::events::publish(SystemEvent::BleClientConnected { addr: address, mtu });
+                    } else if unannounced.insert(address) {
+                        settles.push(async move {
+                            time::sleep(MTU_SETTLE).await;
+                            address
+                        });
+                    }
+                    continue;
+                }
+                PeerEvent::Mtu(address, mtu) => {
+                    if unannounced.remove(&address) {
+                        crate::events::publish(SystemEvent::BleClientConnected { addr: address, mtu });
+                    }
+                    (address, mtu)
+                }
+                PeerEvent::Disconnected(address) => {
+                    // Unannounced connections are not announced as closed either
+                    if !unannounced.remove(&address) {
+                        crate::events::publish(SystemEvent::BleClientDisconnected { addr: address });
+                    }
+                    (address, DISCONNECTED)
+                }
+            },
+        };
+
+        let known = peers
+            .iter()
+            .position(|peer| matches!(peer, Some((a, _)) if *a == address));
+        let index = match (known, mtu) {
+            (Some(index), DISCONNECTED) => {
+                peers[index] = None;
+                index
+            }
+            (Some(index), _) => {
+                if peers[index].is_some_and(|(_, known_mtu)| known_mtu == mtu) {
+                    continue;
+                }
+                peers[index] = Some((address, mtu));
+                index
+            }
+            (None, DISCONNECTED) => continue,
+            (None, _) => {
+                let index = peers
+                    .iter()
+                    .position(Option::is_none)
+                    .unwrap_or(peers.len());
+                if index == peers.len() {
+                    peers.push(None);
+                }
+                peers[index] = Some((address, mtu));
+                index
+            }
+        };
+        println!("Peer {index} ({address}) MTU is now {mtu}");
+        // One byte each, so larger MTUs are reported as 255
+        let payload = sequence.stamp(&[index as u8, mtu.min(u8::MAX as u16) as u8][..]);
+        let mut remaining = Vec::with_capacity(writers.len());
+        for mut writer in writers.drain(..) {
+            if retry.notify(&mut writer, &payload).await.is_ok() {
+                remaining.push(writer);
+            }
+        }
+        writers = remaining;
+    }
+}
+
+/// Creates the `MTU_CHANGED` characteristic.
+///
+/// Every subscriber is notified with a peer index and the ATT MTU that peer
+/// negotiated, seen on its subscriptions, or `0x00` when it disconnects.
+/// Connections and disconnections are also published as [SystemEvent]s.
+pub fn characteristic(adapter: Adapter, retry: &RetryPolicies) -> Characteristic {
+    let (control, control_handle) = characteristic_control();
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+    let _ = OBSERVER.set(events_tx.clone());
+    crate::tasks::spawn("peers", serve(control, events_rx, retry.get("mtu_changed")));
+    crate::tasks::spawn("peer-connections", watch_connections(adapter, events_tx));
+
+    Characteristic {
+        uuid: crate::MTU_CHANGED,
+        notify: Some(CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Io,
+            ..Default::default()
+        }),
+        control_handle,
+        ..Default::default()
+    }
+}
+
+/// Gives the ATT write response time to reach a client that disconnects itself
+const SELF_DISCONNECT_DELAY: Duration = Duration::from_millis(200);
+
+async fn disconnect(adapter: &Adapter, address: Address) -> bluer::Result<()> {
+    adapter.device(address)?.disconnect().await?;
+    println!("Disconnected {address}: server-initiated");
+    Ok(())
+}
+
+/// Creates the `PEER_DISCONNECT` characteristic.
+///
+/// Accepts the 6-byte address of a connected peer and disconnects it. Only
+/// bonded clients may write; a client naming itself is disconnected after
+/// the write is acknowledged.
+pub fn disconnect_characteristic(adapter: Adapter) -> Characteristic {
+    Characteristic {
+        uuid: crate::PEER_DISCONNECT,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, req| {
+                let adapter = adapter.clone();
+                async move {
+                    let address = Address::new(
+                        <[u8; 6]>::try_from(value).map_err(|_| ReqError::InvalidValueLength)?,
+                    );
+                    let bonded = match adapter.device(req.device_address) {
+                        Ok(device) => device.is_paired().await.unwrap_or(false),
+                        Err(_) => false,
+                    };
+                    if !bonded {
+                        return Err(ReqError::NotAuthorized);
+                    }
+                    if !adapter
+                        .device_addresses()
+                        .await
+                        .map_err(|_| ReqError::Failed)?
+                        .contains(&address)
+                    {
+                        return Err(ReqError::NotSupported);
+                    }
+                    println!("{} requested disconnecting {address}", req.device_address);
+                    if address == req.device_address {
+                        crate::tasks::spawn(&format!("disconnect-{address}"), async move {
+                            time::sleep(SELF_DISCONNECT_DELAY).await;
+                            if let Err(err) = disconnect(&adapter, address).await {
+                                eprintln!("Disconnecting {address} failed: {err}");
+                            }
+                        });
+                        return Ok(());
+                    }
+                    disconnect(&adapter, address).await.map_err(|err| {
+                        eprintln!("Disconnecting {address} failed: {err}");
+                        ReqError::Failed
+                    })
+                }
+                .boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
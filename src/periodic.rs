@@ -0,0 +1,56 @@
+use crate::{payload::NotifySequence, retry::RetryPolicy};
+use bluer::gatt::{
+    local::{CharacteristicControl, CharacteristicControlEvent},
+    CharacteristicWriter,
+};
+use futures::{pin_mut, StreamExt};
+use std::time::Duration;
+use tokio::time;
+
+/// Serves an IO notify characteristic with a freshly sampled value every `period`.
+///
+/// `sample` is called on every tick, also without subscribers, so sensors
+/// keep their state up to date. Ticks returning `None` send nothing.
+pub async fn notify<F>(
+    control: CharacteristicControl,
+    name: &str,
+    period: Duration,
+    retry: RetryPolicy,
+    mut sample: F,
+) where
+    F: FnMut() -> Option<Vec<u8>>,
+{
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut sequence = NotifySequence::new(name);
+    let mut interval = time::interval(period);
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting {name} notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, name);
+                        writer_opt = Some(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            _ = interval.tick() => {
+                let Some(value) = sample() else {
+                    continue;
+                };
+                if let Some(writer) = &mut writer_opt {
+                    if retry.notify(writer, &sequence.stamp(&value)).await.is_err() {
+                        writer_opt = None;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::response::Responder;
+use nix::{errno::Errno, sys::stat::Mode, unistd::mkfifo};
+use std::path::{Path, PathBuf};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::mpsc,
+};
+
+/// Bytes read from the FIFO at once
+const READ_LEN: usize = 512;
+
+/// Creates the FIFO unless it already exists.
+fn create_fifo(path: &Path) -> nix::Result<()> {
+    match mkfifo(path, Mode::from_bits_truncate(0o660)) {
+        Ok(()) | Err(Errno::EEXIST) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Forwards everything written to the FIFO to `WRITE_REQUEST_RESPONSE`.
+async fn forward_from_pipe(path: PathBuf, responder: Responder) {
+    let mut buffer = [0u8; READ_LEN];
+    loop {
+        // Opening blocks until a writer shows up, reopen after each writer leaves
+        let mut pipe = match File::open(&path).await {
+            Ok(pipe) => pipe,
+            Err(err) => {
+                eprintln!(
+                    "Stopping pipe bridge, cannot open {}: {err}",
+                    path.display()
+                );
+                return;
+            }
+        };
+        loop {
+            match pipe.read(&mut buffer).await {
+                Ok(0) => break,
+                Ok(len) => {
+                    // One notification per chunk, so none is fragmented
+                    for chunk in buffer[..len].chunks(crate::response::max_payload_len()) {
+                        if responder.send(chunk.to_vec()).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(err) => {
+                    eprintln!("Reading {} failed: {err}", path.display());
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Writes client writes to the outbound FIFO, waiting for a reader as needed.
+async fn forward_to_pipe(path: PathBuf, mut client_writes: mpsc::Receiver<Vec<u8>>) {
+    let mut pipe: Option<File> = None;
+    while let Some(value) = client_writes.recv().await {
+        if pipe.is_none() {
+            pipe = match OpenOptions::new().write(true).open(&path).await {
+                Ok(pipe) => Some(pipe),
+                Err(err) => {
+                    eprintln!("Cannot open {}: {err}", path.display());
+                    continue;
+                }
+            };
+        }
+        if let Some(writer) = &mut pipe {
+            if let Err(err) = writer.write_all(&value).await {
+                // The reader went away, wait for the next one
+                eprintln!("Writing {} failed: {err}", path.display());
+                pipe = None;
+            }
+        }
+    }
+}
+
+/// Bridges `WRITE_REQUEST_RESPONSE` to a pair of named pipes.
+///
+/// Data written to the FIFO at `path` is notified to clients, and client
+/// writes go to the FIFO at `path` with an `.out` suffix. Two pipes keep
+/// the server from reading back its own writes.
+pub fn start(
+    path: &Path,
+    client_writes: mpsc::Receiver<Vec<u8>>,
+    responder: Responder,
+) -> nix::Result<()> {
+    let mut out_path = path.as_os_str().to_owned();
+    out_path.push(".out");
+    let out_path = PathBuf::from(out_path);
+    create_fifo(path)?;
+    create_fifo(&out_path)?;
+
+    println!(
+        "Bridging {} and {} to BLE",
+        path.display(),
+        out_path.display()
+    );
+    let path = path.to_path_buf();
+    crate::tasks::spawn_restarting("pipe-from", move || {
+        forward_from_pipe(path.clone(), responder.clone())
+    });
+    crate::tasks::spawn("pipe-to", forward_to_pipe(out_path, client_writes));
+    Ok(())
+}
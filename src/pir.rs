@@ -0,0 +1,128 @@
+use crate::{
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotify, CharacteristicNotifyMethod, CharacteristicRead,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use rppal::gpio::{Gpio, InputPin, Trigger};
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::mpsc;
+
+/// Rising edges within this window after a detection are ignored
+const LOCKOUT: Duration = Duration::from_millis(500);
+
+async fn serve(
+    pin: InputPin,
+    control: CharacteristicControl,
+    mut edges: mpsc::UnboundedReceiver<()>,
+    count: Arc<AtomicU32>,
+    retry: RetryPolicy,
+) {
+    // Dropping the pin would clear its interrupt
+    let _pin = pin;
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut sequence = NotifySequence::new("motion");
+    let mut last_motion: Option<Instant> = None;
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting motion notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "motion");
+                        writer_opt = Some(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Some(()) = edges.recv() => {
+                if last_motion.is_some_and(|last| last.elapsed() < LOCKOUT) {
+                    continue;
+                }
+                last_motion = Some(Instant::now());
+                let count = count.fetch_add(1, Ordering::Relaxed) + 1;
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+                println!("Motion detected ({count} since boot)");
+
+                if let Some(writer) = &mut writer_opt {
+                    let mut payload = timestamp.to_le_bytes().to_vec();
+                    payload.extend_from_slice(&count.to_le_bytes());
+                    if retry.notify(writer, &sequence.stamp(&payload)).await.is_err() {
+                        writer_opt = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `PIR_MOTION` and `PIR_MOTION_COUNT` characteristics for a
+/// sensor wired to GPIO `pin`.
+pub fn characteristics(pin: u8, retry: &RetryPolicies) -> rppal::gpio::Result<Vec<Characteristic>> {
+    let mut pin = Gpio::new()?.get(pin)?.into_input_pulldown();
+    let (edge_tx, edge_rx) = mpsc::unbounded_channel();
+    pin.set_async_interrupt(Trigger::RisingEdge, None, move |_event| {
+        let _ = edge_tx.send(());
+    })?;
+
+    let count = Arc::new(AtomicU32::new(0));
+    let (control, control_handle) = characteristic_control();
+    crate::tasks::spawn(
+        "pir",
+        serve(
+            pin,
+            control,
+            edge_rx,
+            count.clone(),
+            retry.get("pir_motion"),
+        ),
+    );
+
+    Ok(vec![
+        // Motion events as timestamp and count
+        Characteristic {
+            uuid: crate::PIR_MOTION,
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle,
+            ..Default::default()
+        },
+        // Motion events since boot
+        Characteristic {
+            uuid: crate::PIR_MOTION_COUNT,
+            read: Some(CharacteristicRead {
+                read: true,
+                fun: Box::new(move |_req| {
+                    let count = count.load(Ordering::Relaxed);
+                    async move { Ok(count.to_le_bytes().to_vec()) }.boxed()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    ])
+}
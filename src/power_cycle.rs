@@ -0,0 +1,59 @@
+use bluer::gatt::local::{
+    Characteristic, CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+};
+use futures::FutureExt;
+use rppal::gpio::{Gpio, OutputPin};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::Mutex, time};
+
+/// Drives `pin` low for `off`, then high again.
+async fn cycle(pin: &mut OutputPin, off: Duration) {
+    let number = pin.pin();
+    println!("Power cycling GPIO {number} for {}ms", off.as_millis());
+    pin.set_low();
+    time::sleep(off).await;
+    pin.set_high();
+}
+
+/// Creates the `POWER_CYCLE` characteristic for relays on the whitelisted GPIO `pins`.
+///
+/// Writes are a pin number and a `u16` LE off time in milliseconds. The
+/// pins are driven high on startup, i.e. the peripherals are powered.
+pub fn characteristic(pins: &[u8]) -> rppal::gpio::Result<Characteristic> {
+    let gpio = Gpio::new()?;
+    let mut outputs = HashMap::new();
+    for &pin in pins {
+        let output = gpio.get(pin)?.into_output_high();
+        outputs.insert(pin, Arc::new(Mutex::new(output)));
+    }
+
+    Ok(Characteristic {
+        uuid: crate::POWER_CYCLE,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                let result = match value[..] {
+                    [pin, low, high] => match outputs.get(&pin) {
+                        Some(output) => match output.clone().try_lock_owned() {
+                            Ok(mut output) => {
+                                let off =
+                                    Duration::from_millis(u16::from_le_bytes([low, high]) as u64);
+                                crate::tasks::spawn(&format!("power-cycle-{pin}"), async move {
+                                    cycle(&mut output, off).await
+                                });
+                                Ok(())
+                            }
+                            // Still cycling
+                            Err(_) => Err(ReqError::InProgress),
+                        },
+                        None => Err(ReqError::NotPermitted),
+                    },
+                    _ => Err(ReqError::InvalidValueLength),
+                };
+                async move { result }.boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,142 @@
+use crate::response::Responder;
+use bluer::gatt::local::{
+    Characteristic, CharacteristicRead, CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+};
+use futures::FutureExt;
+use serde::Serialize;
+use tokio::{fs, process::Command};
+
+/// Upper bound for N, keeps the reply to a few dozen frames at the default MTU
+const MAX_TOP: u8 = 20;
+
+/// Bytes of the `MORE_DATA` or `DONE` flag leading each reply frame
+const FLAG_LEN: usize = 1;
+
+const MORE_DATA: u8 = 0x01;
+const DONE: u8 = 0x00;
+
+const BY_CPU: u8 = 0x00;
+const BY_MEMORY: u8 = 0x01;
+
+#[derive(Debug, Serialize)]
+struct Process {
+    pid: u32,
+    name: String,
+    cpu_percent: f32,
+    memory_percent: f32,
+}
+
+/// Counts the numeric entries of `/proc`, one per process.
+async fn count() -> std::io::Result<u32> {
+    let mut entries = fs::read_dir("/proc").await?;
+    let mut count = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+        {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Parses `ps -eo pid=,comm=,%cpu=,%mem=` lines.
+fn parse_ps(output: &str) -> Vec<Process> {
+    output
+        .lines()
+        .filter_map(|line| {
+            // The command name may contain spaces, the numbers never do
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let mut rest: Vec<&str> = fields.collect();
+            let memory_percent = rest.pop()?.parse().ok()?;
+            let cpu_percent = rest.pop()?.parse().ok()?;
+            Some(Process {
+                pid,
+                name: rest.join(" "),
+                cpu_percent,
+                memory_percent,
+            })
+        })
+        .collect()
+}
+
+async fn top(n: u8, sort: &str) -> std::io::Result<Vec<Process>> {
+    let output = Command::new("ps")
+        .args(["-eo", "pid=,comm=,%cpu=,%mem=", &format!("--sort=-{sort}")])
+        .output()
+        .await?;
+    let mut processes = parse_ps(&String::from_utf8_lossy(&output.stdout));
+    processes.truncate(n as usize);
+    Ok(processes)
+}
+
+/// Creates the `PROCESS_COUNT` and `TOP_PROCESSES` characteristics.
+///
+/// `PROCESS_COUNT` reads return the number of processes as `u32` LE. Writes
+/// to `TOP_PROCESSES` are N and a sort key, `0x00` CPU or `0x01` memory; the
+/// top N processes are notified on `WRITE_REQUEST_RESPONSE` as CBOR, split
+/// into frames behind a `MORE_DATA` or `DONE` flag byte.
+pub fn characteristics(responder: Responder) -> Vec<Characteristic> {
+    vec![
+        // Number of running processes
+        Characteristic {
+            uuid: crate::PROCESS_COUNT,
+            read: Some(CharacteristicRead {
+                read: true,
+                fun: Box::new(|_req| {
+                    async move {
+                        let count = count().await.map_err(|err| {
+                            eprintln!("Counting processes failed: {err}");
+                            ReqError::Failed
+                        })?;
+                        Ok(count.to_le_bytes().to_vec())
+                    }
+                    .boxed()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        // Top N processes by CPU or memory
+        Characteristic {
+            uuid: crate::TOP_PROCESSES,
+            write: Some(CharacteristicWrite {
+                write: true,
+                method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                    let responder = responder.clone();
+                    async move {
+                        let (n, sort) = match value[..] {
+                            [n @ 1..=MAX_TOP, BY_CPU] => (n, "pcpu"),
+                            [n @ 1..=MAX_TOP, BY_MEMORY] => (n, "pmem"),
+                            [_, _] => return Err(ReqError::NotSupported),
+                            _ => return Err(ReqError::InvalidValueLength),
+                        };
+                        let processes = top(n, sort).await.map_err(|err| {
+                            eprintln!("Listing processes failed: {err}");
+                            ReqError::Failed
+                        })?;
+                        let mut reply = Vec::new();
+                        ciborium::into_writer(&processes, &mut reply)
+                            .map_err(|_| ReqError::Failed)?;
+                        let frame_len = crate::response::max_payload_len() - FLAG_LEN;
+                        let frames = reply.chunks(frame_len).count();
+                        for (i, chunk) in reply.chunks(frame_len).enumerate() {
+                            let flag = if i + 1 == frames { DONE } else { MORE_DATA };
+                            let mut frame = vec![flag];
+                            frame.extend_from_slice(chunk);
+                            responder.send(frame).await.map_err(|_| ReqError::Failed)?;
+                        }
+                        Ok(())
+                    }
+                    .boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    ]
+}
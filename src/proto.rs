@@ -0,0 +1,36 @@
+// Messages of `proto/metrics.proto`, written out instead of generated so
+// the build does not need `protoc`.
+
+/// Schema version carried in every [MetricFrame]
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum MetricId {
+    Unspecified = 0,
+    CpuLoad = 1,
+    CpuTemperature = 2,
+    MemoryUsed = 3,
+    Uptime = 4,
+    DiskUsage = 5,
+    LoadAverage = 6,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MetricUpdate {
+    #[prost(enumeration = "MetricId", tag = "1")]
+    pub metric_id: i32,
+    /// Unix time in seconds
+    #[prost(uint32, tag = "2")]
+    pub timestamp: u32,
+    #[prost(float, tag = "3")]
+    pub value: f32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MetricFrame {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub updates: Vec<MetricUpdate>,
+}
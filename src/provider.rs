@@ -0,0 +1,237 @@
+use crate::{
+    config::{self, Config, Schedule},
+    payload::NotifySequence,
+    retry::RetryPolicy,
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotify, CharacteristicNotifyMethod, CharacteristicRead, ReqError,
+    },
+    CharacteristicWriter,
+};
+use futures::{future::BoxFuture, stream, FutureExt, StreamExt};
+use std::{
+    cmp::Reverse,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    sync::watch,
+    time::{self, MissedTickBehavior},
+};
+use uuid::Uuid;
+
+/// A metric notified on its own characteristic.
+pub trait MetricProvider: Send + 'static {
+    /// UUID of the notify characteristic
+    fn uuid(&self) -> Uuid;
+
+    /// Name used in logs and as `[metrics.<name>]` in the config file
+    fn name(&self) -> &'static str;
+
+    /// Samples the metric and encodes it, `None` when there is nothing to notify.
+    fn sample(&mut self) -> BoxFuture<'_, Option<Vec<u8>>>;
+
+    /// QoS priority, providers due on the same tick are sampled and notified highest first.
+    fn priority(&self) -> u8 {
+        0
+    }
+
+    /// Called after the latest sample was notified to a subscriber.
+    fn notified(&mut self) {}
+}
+
+/// Metric providers served as notify characteristics
+#[derive(Default)]
+pub struct Registry {
+    providers: Vec<Box<dyn MetricProvider>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    pub fn register(&mut self, provider: impl MetricProvider) -> &mut Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    /// Starts the task sampling each provider enabled in `config` on its
+    /// interval from `schedule`, and creates the characteristics. Reads return
+    /// the latest sample without the notify header.
+    ///
+    /// Disabled providers are dropped without a characteristic.
+    pub fn characteristics(
+        self,
+        config: &Config,
+        schedule: &watch::Receiver<Schedule>,
+    ) -> Vec<Characteristic> {
+        let mut controls = Vec::new();
+        let mut served = Vec::new();
+        let characteristics = self
+            .providers
+            .into_iter()
+            .filter(|provider| {
+                let enabled = config.is_enabled(provider.name());
+                if !enabled {
+                    println!("Not serving disabled metric {}", provider.name());
+                }
+                enabled
+            })
+            .map(|provider| {
+                let (control, control_handle) = characteristic_control();
+                let uuid = provider.uuid();
+                let latest = Arc::new(Mutex::new(None));
+                controls.push(control);
+                let retry = config.retry_policies().get(provider.name());
+                served.push(Served::new(provider, retry, latest.clone()));
+                Characteristic {
+                    uuid,
+                    read: Some(CharacteristicRead {
+                        read: true,
+                        fun: Box::new(move |_req| {
+                            let result = latest.lock().unwrap().clone().ok_or(ReqError::Failed);
+                            async move { result }.boxed()
+                        }),
+                        ..Default::default()
+                    }),
+                    notify: Some(CharacteristicNotify {
+                        notify: true,
+                        method: CharacteristicNotifyMethod::Io,
+                        ..Default::default()
+                    }),
+                    control_handle,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        crate::tasks::spawn("metrics", serve(controls, served, schedule.clone()));
+        characteristics
+    }
+}
+
+/// A provider with its subscriber and latest sample
+struct Served {
+    provider: Box<dyn MetricProvider>,
+    retry: RetryPolicy,
+    writer_opt: Option<CharacteristicWriter>,
+    sequence: NotifySequence,
+    latest: Arc<Mutex<Option<Vec<u8>>>>,
+}
+
+impl Served {
+    fn new(
+        provider: Box<dyn MetricProvider>,
+        retry: RetryPolicy,
+        latest: Arc<Mutex<Option<Vec<u8>>>>,
+    ) -> Self {
+        Served {
+            sequence: NotifySequence::new(provider.name()),
+            provider,
+            retry,
+            writer_opt: None,
+            latest,
+        }
+    }
+
+    /// Takes on `notifier` as the subscriber, sending it the latest sample
+    /// without waiting for a tick.
+    async fn subscribe(&mut self, mut notifier: CharacteristicWriter) {
+        let name = self.provider.name();
+        if !crate::security::admits(&notifier).await {
+            return;
+        }
+        println!(
+            "Accepting {name} notify request with MTU {}",
+            notifier.mtu()
+        );
+        crate::peers::observe(&notifier, name);
+        let latest = self.latest.lock().unwrap().clone();
+        if let Some(value) = latest {
+            if self
+                .retry
+                .notify(&mut notifier, &self.sequence.stamp(&value))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+        self.writer_opt = Some(notifier);
+    }
+
+    /// Samples the provider into `latest` and notifies the subscriber.
+    async fn update(&mut self) {
+        let Some(value) = self.provider.sample().await else {
+            return;
+        };
+        *self.latest.lock().unwrap() = Some(value.clone());
+        let Some(writer) = &mut self.writer_opt else {
+            return;
+        };
+        if self
+            .retry
+            .notify(writer, &self.sequence.stamp(&value))
+            .await
+            .is_err()
+        {
+            self.writer_opt = None;
+            return;
+        }
+        self.provider.notified();
+    }
+}
+
+/// Samples the providers on their `schedule` intervals, notifying subscribed clients.
+///
+/// Ticks at absolute interval boundaries, unaffected by the time spent sampling.
+async fn serve(
+    controls: Vec<CharacteristicControl>,
+    mut served: Vec<Served>,
+    mut schedule_changes: watch::Receiver<Schedule>,
+) {
+    let mut events = stream::select_all(
+        controls
+            .into_iter()
+            .enumerate()
+            .map(|(index, control)| control.map(move |evt| (index, evt))),
+    );
+    let mut schedule = schedule_changes.borrow_and_update().clone();
+    let mut interval = time::interval(schedule.tick());
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    // Counts ticks for the per provider intervals of `schedule`
+    let mut tick: u64 = 0;
+
+    loop {
+        tokio::select! {
+            evt = events.next() => {
+                match evt {
+                    Some((index, CharacteristicControlEvent::Notify(notifier))) => {
+                        served[index].subscribe(notifier).await;
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Ok(()) = schedule_changes.changed() => {
+                schedule = schedule_changes.borrow_and_update().clone();
+                config::retime(&mut interval, schedule.tick());
+                tick = 0;
+            },
+            deadline = interval.tick() => {
+                crate::stats::record_tick_jitter(deadline.elapsed());
+                let mut due: Vec<&mut Served> = served
+                    .iter_mut()
+                    .filter(|served| schedule.is_due(served.provider.name(), tick))
+                    .collect();
+                // Stable, so equal priorities keep their registration order
+                due.sort_by_key(|served| Reverse(served.provider.priority()));
+                for served in due {
+                    served.update().await;
+                }
+                tick += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{encoding::Encode, payload::NotifySequence, retry::RetryPolicies};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotify, CharacteristicNotifyMethod, CharacteristicRead, ReqError,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use std::{future::Future, time::Duration};
+use tokio::{process::Command, time};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// `get_throttled` bit set while the supply voltage is too low
+const UNDERVOLTAGE_NOW: u32 = 0x1;
+
+/// Runs `vcgencmd` and returns the value after the `=` of its reply.
+async fn vcgencmd(args: &[&str]) -> std::io::Result<String> {
+    let output = Command::new("vcgencmd").args(args).output().await?;
+    let reply = String::from_utf8_lossy(&output.stdout);
+    reply
+        .trim()
+        .split_once('=')
+        .map(|(_, value)| value.to_string())
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unexpected vcgencmd reply: {}", reply.trim()),
+            )
+        })
+}
+
+fn invalid_data(_: impl std::error::Error) -> std::io::Error {
+    std::io::Error::from(std::io::ErrorKind::InvalidData)
+}
+
+/// GPU temperature in °C, from e.g. `temp=48.3'C`.
+async fn gpu_temp() -> std::io::Result<f32> {
+    let value = vcgencmd(&["measure_temp"]).await?;
+    value.trim_end_matches("'C").parse().map_err(invalid_data)
+}
+
+/// GPU core clock in Hz, from e.g. `frequency(1)=500000000`.
+async fn gpu_clock() -> std::io::Result<u64> {
+    vcgencmd(&["measure_clock", "core"])
+        .await?
+        .parse()
+        .map_err(invalid_data)
+}
+
+/// `get_throttled` bitfield, from e.g. `throttled=0x50000`.
+///
+/// Bits 0-3 are the current undervoltage, frequency capped, throttled and
+/// soft temperature limit state, bits 16-19 whether each occurred since boot.
+async fn throttled() -> std::io::Result<u32> {
+    let value = vcgencmd(&["get_throttled"]).await?;
+    u32::from_str_radix(value.trim_start_matches("0x"), 16).map_err(invalid_data)
+}
+
+/// Read method running `sample` on request, the value without notify header.
+fn read<T, F>(name: &'static str, sample: fn() -> F) -> CharacteristicRead
+where
+    T: Encode,
+    F: Future<Output = std::io::Result<T>> + Send + 'static,
+{
+    CharacteristicRead {
+        read: true,
+        fun: Box::new(move |_req| {
+            async move {
+                let value = sample().await.map_err(|err| {
+                    eprintln!("Reading {name} failed: {err}");
+                    ReqError::Failed
+                })?;
+                Ok(value.encode())
+            }
+            .boxed()
+        }),
+        ..Default::default()
+    }
+}
+
+async fn serve(
+    temp_control: CharacteristicControl,
+    clock_control: CharacteristicControl,
+    throttled_control: CharacteristicControl,
+    retry: RetryPolicies,
+) {
+    let mut temp_writer_opt: Option<CharacteristicWriter> = None;
+    let mut clock_writer_opt: Option<CharacteristicWriter> = None;
+    let mut throttled_writer_opt: Option<CharacteristicWriter> = None;
+    let mut temp_sequence = NotifySequence::new("GPU temperature");
+    let mut clock_sequence = NotifySequence::new("GPU clock");
+    let mut throttled_sequence = NotifySequence::new("throttling");
+    // Last bitfield sent, so only changes are notified
+    let mut last_throttled: Option<u32> = None;
+    // Alert once per undervoltage episode, even without subscribers
+    let mut undervoltage = false;
+    let temp_retry = retry.get("gpu_temperature");
+    let clock_retry = retry.get("gpu_clock");
+    let throttled_retry = retry.get("throttled");
+    let mut interval = time::interval(SAMPLE_INTERVAL);
+    pin_mut!(temp_control);
+    pin_mut!(clock_control);
+    pin_mut!(throttled_control);
+
+    loop {
+        tokio::select! {
+            evt = temp_control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting GPU temperature notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "GPU temperature");
+                        temp_writer_opt = Some(notifier);
+                        // Sample right away instead of after the interval
+                        interval.reset_immediately();
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            evt = clock_control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting GPU clock notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "GPU clock");
+                        clock_writer_opt = Some(notifier);
+                        interval.reset_immediately();
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            evt = throttled_control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting throttling notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "throttling");
+                        throttled_writer_opt = Some(notifier);
+                        // A new subscriber gets the current state right away
+                        last_throttled = None;
+                        interval.reset_immediately();
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            _ = interval.tick() => {
+                if let Some(writer) = &mut temp_writer_opt {
+                    match gpu_temp().await {
+                        Ok(temp) => {
+                            if temp_retry.notify(writer, &temp_sequence.stamp(temp)).await.is_err() {
+                                temp_writer_opt = None;
+                            }
+                        }
+                        Err(err) => eprintln!("Reading GPU temperature failed: {err}"),
+                    }
+                }
+                if let Some(writer) = &mut clock_writer_opt {
+                    match gpu_clock().await {
+                        Ok(clock) => {
+                            if clock_retry.notify(writer, &clock_sequence.stamp(clock)).await.is_err() {
+                                clock_writer_opt = None;
+                            }
+                        }
+                        Err(err) => eprintln!("Reading GPU clock failed: {err}"),
+                    }
+                }
+                let bits = match throttled().await {
+                    Ok(bits) => bits,
+                    Err(err) => {
+                        eprintln!("Reading throttling state failed: {err}");
+                        continue;
+                    }
+                };
+                let was_undervoltage = undervoltage;
+                undervoltage = bits & UNDERVOLTAGE_NOW != 0;
+                if undervoltage && !was_undervoltage {
+                    eprintln!("Undervoltage detected, throttled={bits:#x}");
+                    crate::alerts::raise(crate::alerts::UNDERVOLTAGE, bits);
+                }
+                if let Some(writer) = &mut throttled_writer_opt {
+                    if last_throttled != Some(bits) {
+                        let payload = throttled_sequence.stamp(bits);
+                        if throttled_retry.notify(writer, &payload).await.is_err() {
+                            throttled_writer_opt = None;
+                        } else {
+                            last_throttled = Some(bits);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `GPU_TEMPERATURE`, `GPU_CLOCK` and `THROTTLED` characteristics,
+/// read with `vcgencmd`.
+///
+/// The temperature is notified in °C as `f32`, the core clock in Hz as
+/// `u64`. `THROTTLED` notifies the `get_throttled` bitfield as `u32`
+/// whenever it changes. Reads sample all three on request. Undervoltage
+/// raises an [UNDERVOLTAGE](crate::alerts::UNDERVOLTAGE) alert.
+pub fn characteristics(retry: &RetryPolicies) -> Vec<Characteristic> {
+    let (temp_control, temp_handle) = characteristic_control();
+    let (clock_control, clock_handle) = characteristic_control();
+    let (throttled_control, throttled_handle) = characteristic_control();
+    crate::tasks::spawn(
+        "raspi",
+        serve(
+            temp_control,
+            clock_control,
+            throttled_control,
+            retry.clone(),
+        ),
+    );
+
+    vec![
+        // GPU temperature
+        Characteristic {
+            uuid: crate::GPU_TEMPERATURE,
+            read: Some(read("GPU temperature", gpu_temp)),
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle: temp_handle,
+            ..Default::default()
+        },
+        // GPU core clock
+        Characteristic {
+            uuid: crate::GPU_CLOCK,
+            read: Some(read("GPU clock", gpu_clock)),
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle: clock_handle,
+            ..Default::default()
+        },
+        // Undervoltage and throttling flags
+        Characteristic {
+            uuid: crate::THROTTLED,
+            read: Some(read("throttling state", throttled)),
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle: throttled_handle,
+            ..Default::default()
+        },
+    ]
+}
@@ -0,0 +1,223 @@
+use crate::{
+    auth::Gate,
+    config::Schedule,
+    control::{self, Command},
+    payload::{NotifySequence, HEADER_LEN},
+    retry::{RetryPolicies, RetryPolicy},
+    stats::Latencies,
+};
+use bluer::{
+    gatt::{
+        local::{
+            characteristic_control, Characteristic, CharacteristicControl,
+            CharacteristicControlEvent, CharacteristicNotify, CharacteristicNotifyMethod,
+            CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+        },
+        CharacteristicWriter,
+    },
+    Address,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+use tokio::sync::{mpsc, watch};
+
+/// Queues payloads for notification on `WRITE_REQUEST_RESPONSE`
+pub type Responder = mpsc::Sender<Vec<u8>>;
+
+/// Notification size at the default ATT MTU of 23, less the 5 bytes BlueZ
+/// reserves, assumed without subscribers
+const DEFAULT_MTU: usize = 18;
+
+/// Smallest [CharacteristicWriter::mtu] of the centrals subscribed to
+/// `WRITE_REQUEST_RESPONSE`
+static MTU: AtomicUsize = AtomicUsize::new(DEFAULT_MTU);
+
+/// Longest [Responder] payload that fits one notification to every
+/// subscribed central. Longer payloads have to be framed by the sender.
+pub fn max_payload_len() -> usize {
+    MTU.load(Ordering::Relaxed) - HEADER_LEN
+}
+
+/// Called whenever a writer is added or removed.
+fn update_mtu(writers: &HashMap<Address, CharacteristicWriter>) {
+    let mtu = writers.values().map(CharacteristicWriter::mtu).min();
+    MTU.store(mtu.unwrap_or(DEFAULT_MTU), Ordering::Relaxed);
+}
+
+/// A write answered to the central that made it
+enum Request {
+    /// Reply of the [Gate] handshake
+    Handshake(Vec<u8>),
+    /// A [Command] to run, written at the instant
+    Command(Instant, Vec<u8>),
+}
+
+/// Notifies `payload` to the subscription of `device`, dropping it on failure.
+async fn notify(
+    writers: &mut HashMap<Address, CharacteristicWriter>,
+    device: Address,
+    payload: &[u8],
+    sequence: &mut NotifySequence,
+    retry: &RetryPolicy,
+) -> bool {
+    let Some(writer) = writers.get_mut(&device) else {
+        return false;
+    };
+    if retry
+        .notify(writer, &sequence.stamp(payload))
+        .await
+        .is_err()
+    {
+        writers.remove(&device);
+        update_mtu(writers);
+        return false;
+    }
+    true
+}
+
+async fn serve(
+    control: CharacteristicControl,
+    mut responses: mpsc::Receiver<Vec<u8>>,
+    mut requests: mpsc::Receiver<(Address, Request)>,
+    latencies: Latencies,
+    authenticated: bool,
+    schedule: watch::Sender<Schedule>,
+    retry: RetryPolicy,
+) {
+    // Handshake and command replies go to the writing central only
+    let mut writers: HashMap<Address, CharacteristicWriter> = HashMap::new();
+    let mut sequence = NotifySequence::new("response");
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting response notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "response");
+                        writers.insert(notifier.device_address(), notifier);
+                        update_mtu(&writers);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Some(response) = responses.recv() => {
+                let devices: Vec<Address> = writers.keys().copied().collect();
+                for device in devices {
+                    notify(&mut writers, device, &response, &mut sequence, &retry).await;
+                }
+            },
+            Some((device, request)) = requests.recv() => {
+                match request {
+                    Request::Handshake(reply) => {
+                        notify(&mut writers, device, &reply, &mut sequence, &retry).await;
+                    }
+                    Request::Command(written_at, value) => {
+                        let (reply, echo) = match Command::parse(&value) {
+                            Ok(command) => {
+                                let echo = matches!(command, Command::Echo(_));
+                                (control::execute(command, device, authenticated, &schedule), echo)
+                            }
+                            Err(err) => (err.into_bytes(), false),
+                        };
+                        if notify(&mut writers, device, &reply, &mut sequence, &retry).await && echo {
+                            latencies.lock().unwrap().record(written_at.elapsed());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `WRITE_REQUEST_RESPONSE` characteristic.
+///
+/// Values written by a client are passed to `client_writes`, or run as a
+/// [Command] when it is `None`, with the reply notified back. Echo round trip
+/// times are recorded in `latencies`. The returned [Responder] lets other
+/// characteristics report the outcome of their requests here, notified to
+/// every subscribed central. With a `gate`,
+/// centrals have to complete its handshake before any other write is
+/// accepted, which also enables the power commands. `interval` commands
+/// change `schedule`.
+pub fn characteristic(
+    client_writes: Option<mpsc::Sender<Vec<u8>>>,
+    latencies: Latencies,
+    gate: Option<Arc<Gate>>,
+    schedule: watch::Sender<Schedule>,
+    retry: &RetryPolicies,
+) -> (Characteristic, Responder) {
+    let (control, control_handle) = characteristic_control();
+    let (responder, responses) = mpsc::channel(16);
+    let (request_tx, requests) = mpsc::channel(16);
+    crate::tasks::spawn(
+        "response",
+        serve(
+            control,
+            responses,
+            requests,
+            latencies,
+            gate.is_some(),
+            schedule,
+            retry.get("write_request_response"),
+        ),
+    );
+
+    let characteristic = Characteristic {
+        uuid: crate::WRITE_REQUEST_RESPONSE,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, req| {
+                let client_writes = client_writes.clone();
+                let request_tx = request_tx.clone();
+                let handshake = gate.as_ref().map(|gate| {
+                    let reply = gate.handle(req.device_address, &value);
+                    (reply, gate.is_authenticated(req.device_address))
+                });
+                let device = req.device_address;
+                async move {
+                    match handshake {
+                        Some((Some(reply), _)) => {
+                            return request_tx
+                                .send((device, Request::Handshake(reply)))
+                                .await
+                                .map_err(|_| ReqError::Failed);
+                        }
+                        Some((None, false)) => return Err(ReqError::NotAuthorized),
+                        _ => {}
+                    }
+                    let sent = match client_writes {
+                        Some(client_writes) => client_writes.send(value).await.is_ok(),
+                        None => request_tx
+                            .send((device, Request::Command(Instant::now(), value)))
+                            .await
+                            .is_ok(),
+                    };
+                    sent.then_some(()).ok_or(ReqError::Failed)
+                }
+                .boxed()
+            })),
+            ..Default::default()
+        }),
+        notify: Some(CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Io,
+            ..Default::default()
+        }),
+        control_handle,
+        ..Default::default()
+    };
+    (characteristic, responder)
+}
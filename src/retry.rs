@@ -0,0 +1,104 @@
+use bluer::gatt::CharacteristicWriter;
+use serde::Deserialize;
+use std::{collections::HashMap, time::Duration};
+use tokio::{io::AsyncWriteExt, time};
+
+/// Most retries a policy may make, bounding the backoff delay
+const MAX_RETRIES: u32 = 10;
+
+/// How a characteristic retries notifications that failed to send,
+/// `[retry]` in the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub retry_delay_ms: u64,
+    /// Factor applied to the delay after each retry
+    pub backoff_multiplier: f32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            retry_delay_ms: 20,
+            backoff_multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Checks that the backoff stays within [MAX_RETRIES] non-shrinking delays.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_retries > MAX_RETRIES {
+            return Err(format!("max_retries has to be at most {MAX_RETRIES}"));
+        }
+        if !(1.0..=10.0).contains(&self.backoff_multiplier) {
+            return Err("backoff_multiplier has to be between 1 and 10".to_string());
+        }
+        Ok(())
+    }
+
+    /// Notifies a payload stamped by a [NotifySequence](crate::payload::NotifySequence),
+    /// split into [fragments](crate::payload::fragments) when it exceeds the
+    /// MTU of `writer`.
+    pub async fn notify(
+        &self,
+        writer: &mut CharacteristicWriter,
+        stamped: &[u8],
+    ) -> std::io::Result<()> {
+        for fragment in crate::payload::fragments(stamped, writer.mtu()) {
+            self.write_all(writer, &fragment).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes a notification, retrying with exponential backoff before giving up.
+    pub async fn write_all(
+        &self,
+        writer: &mut CharacteristicWriter,
+        payload: &[u8],
+    ) -> std::io::Result<()> {
+        let mut delay = Duration::from_millis(self.retry_delay_ms);
+        let mut retries = 0;
+        loop {
+            match writer.write_all(payload).await {
+                Ok(()) => {
+                    crate::peer_stats::record_sent(writer.device_address(), payload.len());
+                    return Ok(());
+                }
+                Err(err) if retries < self.max_retries => {
+                    retries += 1;
+                    crate::stats::count_notify_retry();
+                    eprintln!(
+                        "Notify to {} failed, retry {retries}/{} in {}ms: {err}",
+                        writer.device_address(),
+                        self.max_retries,
+                        delay.as_millis()
+                    );
+                    time::sleep(delay).await;
+                    delay = delay.mul_f32(self.backoff_multiplier);
+                }
+                Err(err) => {
+                    crate::peer_stats::record_error(writer.device_address());
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+/// The [RetryPolicy] of every characteristic, with overrides by
+/// characteristic name
+#[derive(Debug, Clone, Default)]
+pub struct RetryPolicies {
+    pub default: RetryPolicy,
+    pub overrides: HashMap<String, RetryPolicy>,
+}
+
+impl RetryPolicies {
+    /// Policy of the characteristic `name`, e.g. `cpu_load`.
+    pub fn get(&self, name: &str) -> RetryPolicy {
+        self.overrides.get(name).unwrap_or(&self.default).clone()
+    }
+}
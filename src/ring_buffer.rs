@@ -0,0 +1,42 @@
+/// Fixed-capacity buffer keeping the last `N` values.
+#[derive(Debug, Clone)]
+pub struct RingBuffer<T, const N: usize> {
+    values: [T; N],
+    /// Slot the next value is written to
+    next: usize,
+    len: usize,
+}
+
+impl<T: Copy + Default, const N: usize> RingBuffer<T, N> {
+    pub fn new() -> Self {
+        RingBuffer {
+            values: [T::default(); N],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /// Appends a value, overwriting the oldest one when full.
+    pub fn push(&mut self, value: T) {
+        self.values[self.next] = value;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    /// Values from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        let start = (self.next + N - self.len) % N;
+        (0..self.len).map(move |i| self.values[(start + i) % N])
+    }
+
+    /// All `N` slots from oldest to newest, with defaults for slots not yet written.
+    pub fn padded(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::repeat_n(T::default(), N - self.len).chain(self.iter())
+    }
+}
+
+impl<T: Copy + Default, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{
+    payload::NotifySequence,
+    response::Responder,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotify, CharacteristicNotifyMethod, CharacteristicWrite,
+        CharacteristicWriteMethod, ReqError,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use image::codecs::jpeg::JpegEncoder;
+use tokio::{process::Command, sync::mpsc};
+
+const CAPTURE_PATH: &str = "/tmp/ble_raspi_screenshot.png";
+
+const JPEG_QUALITY: u8 = 50;
+
+/// Bytes of the `u16` LE sequence number leading each frame
+const SEQUENCE_LEN: usize = 2;
+
+/// Captures the display with `scrot` and returns it as JPEG.
+async fn capture() -> std::io::Result<Vec<u8>> {
+    let status = Command::new("scrot")
+        .args(["--overwrite", CAPTURE_PATH])
+        .status()
+        .await?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!("scrot failed with {status}")));
+    }
+
+    tokio::task::spawn_blocking(|| {
+        let screenshot = image::open(CAPTURE_PATH).map_err(std::io::Error::other)?;
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+            .encode_image(&screenshot.to_rgb8())
+            .map_err(std::io::Error::other)?;
+        Ok(jpeg)
+    })
+    .await?
+}
+
+async fn serve(
+    control: CharacteristicControl,
+    mut requests: mpsc::Receiver<()>,
+    responder: Responder,
+    retry: RetryPolicy,
+) {
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut sequence = NotifySequence::new("screenshot");
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting screenshot notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "screenshot");
+                        writer_opt = Some(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Some(()) = requests.recv() => {
+                let jpeg = match capture().await {
+                    Ok(jpeg) => jpeg,
+                    Err(err) => {
+                        eprintln!("Screenshot failed: {err}");
+                        continue;
+                    }
+                };
+                println!("Streaming {} byte screenshot", jpeg.len());
+                if let Some(writer) = &mut writer_opt {
+                    let len = (jpeg.len() as u32).to_le_bytes();
+                    if retry.notify(writer, &sequence.stamp(&len[..])).await.is_err() {
+                        writer_opt = None;
+                    }
+                }
+                // Each frame has to fit a single notification to be told apart
+                let frame_data_len = crate::response::max_payload_len() - SEQUENCE_LEN;
+                for (sequence, chunk) in jpeg.chunks(frame_data_len).enumerate() {
+                    let mut frame = (sequence as u16).to_le_bytes().to_vec();
+                    frame.extend_from_slice(chunk);
+                    if responder.send(frame).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `SCREEN_CAPTURE` characteristic.
+///
+/// Any write captures the display. The JPEG size is notified as `u32` LE,
+/// then the image follows on `WRITE_REQUEST_RESPONSE` in MTU-sized frames led
+/// by a `u16` LE sequence number.
+pub fn characteristic(responder: Responder, retry: &RetryPolicies) -> Characteristic {
+    let (control, control_handle) = characteristic_control();
+    let (request_tx, request_rx) = mpsc::channel(1);
+    crate::tasks::spawn(
+        "screenshot",
+        serve(control, request_rx, responder, retry.get("screen_capture")),
+    );
+
+    Characteristic {
+        uuid: crate::SCREEN_CAPTURE,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |_value, _req| {
+                let result = request_tx.try_send(()).map_err(|_| ReqError::InProgress);
+                async move { result }.boxed()
+            })),
+            ..Default::default()
+        }),
+        notify: Some(CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Io,
+            ..Default::default()
+        }),
+        control_handle,
+        ..Default::default()
+    }
+}
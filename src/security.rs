@@ -0,0 +1,112 @@
+use bluer::{
+    gatt::{local::Application, CharacteristicWriter},
+    Adapter,
+};
+use clap::ValueEnum;
+use std::sync::OnceLock;
+
+/// Link security clients need before reading or writing characteristics.
+///
+/// BlueZ answers requests on a link below the level with an insufficient
+/// encryption or authentication error, upon which centrals start pairing.
+/// bluer has no flags for the client characteristic configuration
+/// descriptor, so notify handlers check subscriptions through [admits].
+/// Characteristics indicating through a callback, such as `ALERTS`, are not
+/// told the subscribing central and check [admits_indications] instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Security {
+    /// No requirements
+    Open,
+    /// Encrypted link, which needs a bond; Just Works pairing suffices
+    Encrypted,
+    /// Encrypted link with a bond made through passkey or numeric comparison
+    Authenticated,
+}
+
+/// Adds the read and write requirements of `security` to every
+/// characteristic in `app`.
+///
+/// `SENSOR_PAIRING_CODE` stays open, as clients write it before pairing.
+pub fn apply(app: &mut Application, security: Security) {
+    if security == Security::Open {
+        return;
+    }
+    let authenticated = security == Security::Authenticated;
+    for service in &mut app.services {
+        for characteristic in &mut service.characteristics {
+            if characteristic.uuid == crate::SENSOR_PAIRING_CODE {
+                continue;
+            }
+            // Only raise requirements, characteristics may already need more
+            if let Some(read) = &mut characteristic.read {
+                read.encrypt_read |= !authenticated;
+                read.encrypt_authenticated_read |= authenticated;
+            }
+            if let Some(write) = &mut characteristic.write {
+                write.encrypt_write |= !authenticated;
+                write.encrypt_authenticated_write |= authenticated;
+            }
+        }
+    }
+}
+
+/// Adapter and security level subscriptions are checked against
+static SUBSCRIPTIONS: OnceLock<(Adapter, Security)> = OnceLock::new();
+
+/// Makes [admits] require `security` of subscribing centrals.
+pub fn require_for_subscriptions(adapter: Adapter, security: Security) {
+    let _ = SUBSCRIPTIONS.set((adapter, security));
+}
+
+/// Whether the central subscribing through `writer` may receive
+/// notifications. Notify handlers drop the writer otherwise.
+///
+/// Beyond open, the central has to be paired. BlueZ does not tell whether a
+/// bond was authenticated, so both levels accept any paired central here.
+pub async fn admits(writer: &CharacteristicWriter) -> bool {
+    let Some((adapter, security)) = SUBSCRIPTIONS.get() else {
+        return true;
+    };
+    if *security == Security::Open {
+        return true;
+    }
+    let address = writer.device_address();
+    let paired = match adapter.device(address) {
+        Ok(device) => device.is_paired().await.unwrap_or(false),
+        Err(_) => false,
+    };
+    if !paired {
+        println!("Ignoring notify request of unpaired central {address}");
+    }
+    paired
+}
+
+/// Whether an indication through a
+/// [CharacteristicNotifier](bluer::gatt::local::CharacteristicNotifier) may be
+/// sent. Indicating handlers hold it back otherwise.
+///
+/// BlueZ indicates every central that enabled indications without telling
+/// which, so beyond open every connected central has to be paired.
+pub async fn admits_indications() -> bool {
+    let Some((adapter, security)) = SUBSCRIPTIONS.get() else {
+        return true;
+    };
+    if *security == Security::Open {
+        return true;
+    }
+    let Ok(addresses) = adapter.device_addresses().await else {
+        return false;
+    };
+    for address in addresses {
+        let Ok(device) = adapter.device(address) else {
+            continue;
+        };
+        if device.is_connected().await.unwrap_or(false)
+            && !device.is_paired().await.unwrap_or(false)
+        {
+            println!("Holding back indications while unpaired central {address} is connected");
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,250 @@
+use crate::{
+    config::{self, Schedule, SnapshotFormat},
+    metrics::InterpolatedMetrics,
+    payload::{unix_timestamp, NotifySequence},
+    proto::{MetricFrame, MetricId, MetricUpdate},
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotify, CharacteristicNotifyMethod, CharacteristicRead, CharacteristicWrite,
+        CharacteristicWriteMethod, ReqError,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use prost::Message;
+use serde::Serialize;
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc, Mutex,
+};
+use systemstat::{Platform, System};
+use tokio::{sync::watch, time};
+
+/// All metrics in one document, absent ones are left out
+#[derive(Debug, Default, Serialize)]
+struct Snapshot {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpu: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temp: Option<f32>,
+    /// Used memory in MB
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory: Option<f32>,
+    /// Uptime in minutes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uptime: Option<f32>,
+    /// Root filesystem usage in %
+    #[serde(skip_serializing_if = "Option::is_none")]
+    disk: Option<f32>,
+    /// 1 minute load average
+    #[serde(skip_serializing_if = "Option::is_none")]
+    load: Option<f32>,
+}
+
+impl Snapshot {
+    /// The snapshot as protobuf updates, all stamped with the current time.
+    fn frame(&self) -> MetricFrame {
+        let timestamp = unix_timestamp();
+        let updates = [
+            (MetricId::CpuLoad, self.cpu),
+            (MetricId::CpuTemperature, self.temp),
+            (MetricId::MemoryUsed, self.memory),
+            (MetricId::Uptime, self.uptime),
+            (MetricId::DiskUsage, self.disk),
+            (MetricId::LoadAverage, self.load),
+        ]
+        .into_iter()
+        .filter_map(|(metric_id, value)| {
+            Some(MetricUpdate {
+                metric_id: metric_id.into(),
+                timestamp,
+                value: value?,
+            })
+        })
+        .collect();
+        MetricFrame {
+            version: crate::proto::VERSION,
+            updates,
+        }
+    }
+}
+
+/// Combines the latest core metric values with freshly read disk usage and load.
+fn snapshot(metrics: &Mutex<InterpolatedMetrics>) -> Snapshot {
+    let [cpu, temp, memory, uptime] = metrics.lock().unwrap().latest();
+    let sys = System::new();
+    let disk = sys.mount_at("/").ok().and_then(|root| {
+        let total = root.total.as_u64();
+        let used = total.saturating_sub(root.free.as_u64());
+        (total > 0).then(|| used as f32 / total as f32 * 100.0)
+    });
+    let load = sys.load_average().ok().map(|load_average| load_average.one);
+    Snapshot {
+        cpu,
+        temp,
+        memory,
+        uptime,
+        disk,
+        load,
+    }
+}
+
+/// Format selected with `SNAPSHOT_FORMAT`, shared by all clients
+#[derive(Debug, Clone)]
+struct Format(Arc<AtomicU8>);
+
+impl Format {
+    fn get(&self) -> SnapshotFormat {
+        match self.0.load(Ordering::Relaxed) {
+            0x01 => SnapshotFormat::Cbor,
+            0x02 => SnapshotFormat::Protobuf,
+            _ => SnapshotFormat::Json,
+        }
+    }
+
+    fn set(&self, format: SnapshotFormat) {
+        self.0.store(format as u8, Ordering::Relaxed);
+    }
+
+    fn encode(&self, snapshot: &Snapshot) -> Vec<u8> {
+        match self.get() {
+            SnapshotFormat::Json => serde_json::to_vec(snapshot).expect("snapshot is serializable"),
+            SnapshotFormat::Cbor => {
+                let mut payload = Vec::new();
+                ciborium::into_writer(snapshot, &mut payload).expect("snapshot is serializable");
+                payload
+            }
+            SnapshotFormat::Protobuf => snapshot.frame().encode_to_vec(),
+        }
+    }
+}
+
+async fn serve(
+    control: CharacteristicControl,
+    metrics: Arc<Mutex<InterpolatedMetrics>>,
+    format: Format,
+    mut schedule: watch::Receiver<Schedule>,
+    retry: RetryPolicy,
+) {
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut sequence = NotifySequence::new("metrics snapshot");
+    let mut interval = time::interval(schedule.borrow_and_update().interval("metrics_snapshot"));
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting metrics snapshot notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "metrics snapshot");
+                        writer_opt = Some(notifier);
+                        // Notify the current snapshot without waiting for the period
+                        interval.reset_immediately();
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Ok(()) = schedule.changed() => {
+                config::retime(&mut interval, schedule.borrow_and_update().interval("metrics_snapshot"));
+            },
+            _ = interval.tick() => {
+                if let Some(writer) = &mut writer_opt {
+                    let payload = format.encode(&snapshot(&metrics));
+                    if retry.notify(writer, &sequence.stamp(&payload)).await.is_err() {
+                        writer_opt = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `METRICS_SNAPSHOT` and `SNAPSHOT_FORMAT` characteristics.
+///
+/// Reads and notifications on the `schedule` interval carry all metrics as one JSON
+/// document, e.g. `{"cpu":12.3,"temp":45.1,"memory":312.5,...}`, or the same
+/// map as CBOR, or a protobuf `MetricFrame`. `SNAPSHOT_FORMAT` reads and
+/// writes the encoding, `0x00` JSON, `0x01` CBOR or `0x02` protobuf, starting
+/// out as `initial_format`.
+pub fn characteristics(
+    metrics: Arc<Mutex<InterpolatedMetrics>>,
+    initial_format: SnapshotFormat,
+    schedule: watch::Receiver<Schedule>,
+    retry: &RetryPolicies,
+) -> Vec<Characteristic> {
+    let (control, control_handle) = characteristic_control();
+    let format = Format(Arc::new(AtomicU8::new(initial_format as u8)));
+    crate::tasks::spawn(
+        "snapshot",
+        serve(
+            control,
+            metrics.clone(),
+            format.clone(),
+            schedule,
+            retry.get("metrics_snapshot"),
+        ),
+    );
+    let read_format = format.clone();
+    let write_format = format.clone();
+
+    vec![
+        // All metrics in one payload
+        Characteristic {
+            uuid: crate::METRICS_SNAPSHOT,
+            read: Some(CharacteristicRead {
+                read: true,
+                fun: Box::new(move |_req| {
+                    let value = format.encode(&snapshot(&metrics));
+                    async move { Ok(value) }.boxed()
+                }),
+                ..Default::default()
+            }),
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle,
+            ..Default::default()
+        },
+        // Snapshot encoding
+        Characteristic {
+            uuid: crate::SNAPSHOT_FORMAT,
+            read: Some(CharacteristicRead {
+                read: true,
+                fun: Box::new(move |_req| {
+                    let value = vec![read_format.get() as u8];
+                    async move { Ok(value) }.boxed()
+                }),
+                ..Default::default()
+            }),
+            write: Some(CharacteristicWrite {
+                write: true,
+                method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                    let result = match value[..] {
+                        [0x00] => Ok(SnapshotFormat::Json),
+                        [0x01] => Ok(SnapshotFormat::Cbor),
+                        [0x02] => Ok(SnapshotFormat::Protobuf),
+                        [_] => Err(ReqError::NotSupported),
+                        _ => Err(ReqError::InvalidValueLength),
+                    }
+                    .map(|format| {
+                        println!("Metrics snapshot format is now {format:?}");
+                        write_format.set(format);
+                    });
+                    async move { result }.boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    ]
+}
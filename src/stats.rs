@@ -0,0 +1,249 @@
+use bluer::gatt::local::{
+    Characteristic, CharacteristicRead, CharacteristicWrite, CharacteristicWriteMethod,
+};
+use futures::FutureExt;
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Upper bounds of all but the last latency bucket
+const BUCKET_LIMITS: [Duration; 9] = [
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(20),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(200),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+];
+
+/// Notifications resent after a failed write, across all characteristics
+static NOTIFY_RETRIES: AtomicU32 = AtomicU32::new(0);
+
+pub fn count_notify_retry() {
+    NOTIFY_RETRIES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Lateness of the metric ticks behind their deadlines
+static TICK_JITTER: Mutex<TickJitter> = Mutex::new(TickJitter::new());
+
+pub fn record_tick_jitter(jitter: Duration) {
+    TICK_JITTER.lock().unwrap().record(jitter);
+}
+
+/// Wire size of the jitter statistics, minimum, maximum and mean as `u32`
+const TICK_JITTER_LEN: usize = 12;
+const _: () = assert!(TICK_JITTER_LEN == 3 * size_of::<u32>());
+
+#[derive(Debug)]
+struct TickJitter {
+    min_us: u32,
+    max_us: u32,
+    total_us: u64,
+    ticks: u64,
+}
+
+impl TickJitter {
+    const fn new() -> Self {
+        TickJitter {
+            min_us: u32::MAX,
+            max_us: 0,
+            total_us: 0,
+            ticks: 0,
+        }
+    }
+
+    fn record(&mut self, jitter: Duration) {
+        let us = u32::try_from(jitter.as_micros()).unwrap_or(u32::MAX);
+        self.min_us = self.min_us.min(us);
+        self.max_us = self.max_us.max(us);
+        self.total_us = self.total_us.saturating_add(us as u64);
+        self.ticks += 1;
+    }
+
+    fn encode(&self) -> [u8; TICK_JITTER_LEN] {
+        let (min_us, mean_us) = match self.ticks {
+            0 => (0, 0),
+            ticks => (self.min_us, (self.total_us / ticks) as u32),
+        };
+        crate::encoding::fixed(&[
+            &min_us.to_le_bytes(),
+            &self.max_us.to_le_bytes(),
+            &mean_us.to_le_bytes(),
+        ])
+    }
+}
+
+/// Counts of write to notify round trips, shared with the characteristics that measure them
+pub type Latencies = Arc<Mutex<LatencyHistogram>>;
+
+/// Wire size of the histogram, one `u32` per bucket
+const HISTOGRAM_LEN: usize = 40;
+const _: () = assert!(size_of::<LatencyHistogram>() == HISTOGRAM_LEN);
+
+#[derive(Debug, Default)]
+pub struct LatencyHistogram {
+    counts: [u32; BUCKET_LIMITS.len() + 1],
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let bucket = BUCKET_LIMITS
+            .iter()
+            .position(|limit| latency < *limit)
+            .unwrap_or(BUCKET_LIMITS.len());
+        self.counts[bucket] = self.counts[bucket].saturating_add(1);
+    }
+
+    fn reset(&mut self) {
+        self.counts = Default::default();
+    }
+
+    fn encode(&self) -> [u8; HISTOGRAM_LEN] {
+        crate::encoding::fixed(&[self.counts.map(u32::to_le_bytes).as_flattened()])
+    }
+}
+
+/// Creates the `LATENCY_HISTOGRAM`, `NOTIFY_RETRIES`, `TICK_JITTER` and
+/// `STATS_RESET` characteristics.
+///
+/// Histogram reads return ten `u32` LE bucket counts: below 1, 5, 10, 20, 50,
+/// 100, 200 and 500 ms, below 1 s and above. Retries are a `u32` LE. Jitter
+/// reads return the minimum, maximum and mean lateness of the metric ticks in
+/// microseconds, each a `u32` LE. Any write to `STATS_RESET` clears them and
+/// the command history.
+pub fn characteristics(latencies: Latencies) -> Vec<Characteristic> {
+    let reset_latencies = latencies.clone();
+
+    vec![
+        // Write to notify round trip times
+        Characteristic {
+            uuid: crate::LATENCY_HISTOGRAM,
+            read: Some(CharacteristicRead {
+                read: true,
+                fun: Box::new(move |_req| {
+                    let value = latencies.lock().unwrap().encode().to_vec();
+                    async move { Ok(value) }.boxed()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        // Cumulative notification retries
+        Characteristic {
+            uuid: crate::NOTIFY_RETRIES,
+            read: Some(CharacteristicRead {
+                read: true,
+                fun: Box::new(move |_req| {
+                    let value = NOTIFY_RETRIES
+                        .load(Ordering::Relaxed)
+                        .to_le_bytes()
+                        .to_vec();
+                    async move { Ok(value) }.boxed()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        // Lateness of the metric ticks
+        Characteristic {
+            uuid: crate::TICK_JITTER,
+            read: Some(CharacteristicRead {
+                read: true,
+                fun: Box::new(move |_req| {
+                    let value = TICK_JITTER.lock().unwrap().encode().to_vec();
+                    async move { Ok(value) }.boxed()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        // Clears all statistics
+        Characteristic {
+            uuid: crate::STATS_RESET,
+            write: Some(CharacteristicWrite {
+                write: true,
+                method: CharacteristicWriteMethod::Fun(Box::new(move |_value, _req| {
+                    reset_latencies.lock().unwrap().reset();
+                    NOTIFY_RETRIES.store(0, Ordering::Relaxed);
+                    *TICK_JITTER.lock().unwrap() = TickJitter::new();
+                    crate::commands::clear_history();
+                    println!("Statistics reset");
+                    async move { Ok(()) }.boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bucket `latency` lands in.
+    fn bucket(latency: Duration) -> usize {
+        let mut histogram = LatencyHistogram::default();
+        histogram.record(latency);
+        histogram
+            .counts
+            .iter()
+            .position(|&count| count == 1)
+            .unwrap()
+    }
+
+    #[test]
+    fn buckets_limits_into_the_next_bucket() {
+        assert_eq!(bucket(Duration::ZERO), 0);
+        for (i, limit) in BUCKET_LIMITS.into_iter().enumerate() {
+            assert_eq!(
+                bucket(limit - Duration::from_nanos(1)),
+                i,
+                "below {limit:?}"
+            );
+            assert_eq!(bucket(limit), i + 1, "at {limit:?}");
+        }
+        assert_eq!(bucket(Duration::MAX), BUCKET_LIMITS.len());
+    }
+
+    #[test]
+    fn saturates_bucket_counts() {
+        let mut histogram = LatencyHistogram::default();
+        histogram.counts[3] = u32::MAX - 1;
+        histogram.record(Duration::from_millis(15));
+        histogram.record(Duration::from_millis(15));
+        assert_eq!(histogram.counts[3], u32::MAX);
+        assert_eq!(histogram.encode()[12..16], u32::MAX.to_le_bytes());
+    }
+
+    #[test]
+    fn encodes_buckets_in_order() {
+        let mut histogram = LatencyHistogram::default();
+        histogram.record(Duration::from_micros(500));
+        histogram.record(Duration::from_secs(2));
+        histogram.record(Duration::from_secs(2));
+        let encoded = histogram.encode();
+        assert_eq!(encoded[..4], 1u32.to_le_bytes());
+        assert_eq!(encoded[36..], 2u32.to_le_bytes());
+        histogram.reset();
+        assert_eq!(histogram.encode(), [0; HISTOGRAM_LEN]);
+    }
+
+    #[test]
+    fn saturates_tick_jitter() {
+        let mut jitter = TickJitter::new();
+        assert_eq!(jitter.encode(), [0; TICK_JITTER_LEN]);
+        jitter.record(Duration::from_micros(10));
+        jitter.record(Duration::MAX);
+        let encoded = jitter.encode();
+        assert_eq!(encoded[..4], 10u32.to_le_bytes());
+        assert_eq!(encoded[4..8], u32::MAX.to_le_bytes());
+    }
+}
@@ -0,0 +1,170 @@
+use bluer::gatt::local::{
+    Characteristic, CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+};
+use futures::FutureExt;
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use tokio::{fs, sync::Mutex, task::AbortHandle, time};
+
+/// LED sysfs directories, Pi 4/5 first, then Pi 3
+const LED_DIRS: [&str; 2] = ["/sys/class/leds/ACT", "/sys/class/leds/led0"];
+
+/// Time each bit of a blink pattern is shown
+const PATTERN_STEP: Duration = Duration::from_millis(125);
+
+const LED_OFF: u8 = 0x00;
+const LED_ON: u8 = 0x01;
+const LED_HEARTBEAT: u8 = 0x02;
+const LED_DEFAULT: u8 = 0xFF;
+
+struct Led {
+    dir: PathBuf,
+    /// Trigger active at startup, restored by `LED_DEFAULT`
+    default_trigger: String,
+    pattern: Option<AbortHandle>,
+}
+
+impl Led {
+    async fn set_trigger(&self, trigger: &str) -> std::io::Result<()> {
+        fs::write(self.dir.join("trigger"), trigger).await
+    }
+
+    async fn set_brightness(&self, on: bool) -> std::io::Result<()> {
+        set_brightness(&self.dir, on).await
+    }
+
+    fn stop_pattern(&mut self) {
+        if let Some(pattern) = self.pattern.take() {
+            pattern.abort();
+        }
+    }
+}
+
+async fn set_brightness(dir: &std::path::Path, on: bool) -> std::io::Result<()> {
+    fs::write(dir.join("brightness"), if on { "1" } else { "0" }).await
+}
+
+/// Finds the onboard LED and remembers its current trigger.
+async fn find_led() -> Option<Led> {
+    for dir in LED_DIRS {
+        let dir = PathBuf::from(dir);
+        if let Ok(triggers) = fs::read_to_string(dir.join("trigger")).await {
+            // The active trigger is the one in square brackets
+            let default_trigger = triggers
+                .split_whitespace()
+                .find_map(|t| t.strip_prefix('[')?.strip_suffix(']'))
+                .unwrap_or("mmc0")
+                .to_string();
+            return Some(Led {
+                dir,
+                default_trigger,
+                pattern: None,
+            });
+        }
+    }
+    None
+}
+
+async fn set_mode(led: &Arc<Mutex<Led>>, mode: u8) -> std::io::Result<()> {
+    let mut led = led.lock().await;
+    led.stop_pattern();
+    match mode {
+        LED_OFF | LED_ON => {
+            led.set_trigger("none").await?;
+            led.set_brightness(mode == LED_ON).await?;
+        }
+        LED_HEARTBEAT => led.set_trigger("heartbeat").await?,
+        _ => {
+            let trigger = led.default_trigger.clone();
+            led.set_trigger(&trigger).await?;
+        }
+    }
+    println!("Status LED set to mode {mode:#04x}");
+    Ok(())
+}
+
+/// Blinks the LED bit by bit (LSB first), repeating until aborted.
+async fn set_pattern(led: &Arc<Mutex<Led>>, pattern: u32) -> std::io::Result<()> {
+    let mut led = led.lock().await;
+    led.stop_pattern();
+    led.set_trigger("none").await?;
+    if pattern == 0 {
+        led.set_brightness(false).await?;
+        return Ok(());
+    }
+
+    let dir = led.dir.clone();
+    led.pattern = Some(crate::tasks::spawn("led-pattern", async move {
+        let mut interval = time::interval(PATTERN_STEP);
+        for bit in (0..32).cycle() {
+            interval.tick().await;
+            if let Err(err) = set_brightness(&dir, pattern & (1 << bit) != 0).await {
+                eprintln!("Stopping LED pattern: {err}");
+                break;
+            }
+        }
+    }));
+    println!("Status LED pattern set to {pattern:#010x}");
+    Ok(())
+}
+
+/// Creates the `STATUS_LED` and `LED_PATTERN` characteristics.
+///
+/// Returns no characteristics when the board has no controllable LED.
+pub async fn characteristics() -> Vec<Characteristic> {
+    let Some(led) = find_led().await else {
+        eprintln!("No onboard status LED found, skipping LED characteristics");
+        return Vec::new();
+    };
+    let led = Arc::new(Mutex::new(led));
+    let pattern_led = led.clone();
+
+    vec![
+        // Status LED mode
+        Characteristic {
+            uuid: crate::STATUS_LED,
+            write: Some(CharacteristicWrite {
+                write: true,
+                method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                    let led = led.clone();
+                    async move {
+                        let [mode] = value[..] else {
+                            return Err(ReqError::InvalidValueLength);
+                        };
+                        if !matches!(mode, LED_OFF | LED_ON | LED_HEARTBEAT | LED_DEFAULT) {
+                            return Err(ReqError::NotSupported);
+                        }
+                        set_mode(&led, mode).await.map_err(led_error)
+                    }
+                    .boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        // Custom blink pattern
+        Characteristic {
+            uuid: crate::LED_PATTERN,
+            write: Some(CharacteristicWrite {
+                write: true,
+                method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                    let led = pattern_led.clone();
+                    async move {
+                        let pattern: [u8; 4] =
+                            value.try_into().map_err(|_| ReqError::InvalidValueLength)?;
+                        set_pattern(&led, u32::from_le_bytes(pattern))
+                            .await
+                            .map_err(led_error)
+                    }
+                    .boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    ]
+}
+
+fn led_error(err: std::io::Error) -> ReqError {
+    eprintln!("Failed to control status LED: {err}");
+    ReqError::Failed
+}
@@ -0,0 +1,127 @@
+use bluer::gatt::local::{
+    Characteristic, CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+};
+use futures::FutureExt;
+use std::{
+    hint::black_box,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use systemstat::{Platform, System};
+
+const CPU: u8 = 0x01;
+const MEMORY: u8 = 0x02;
+const BOTH: u8 = 0x03;
+
+const MAX_DURATION_SECS: u16 = 300;
+
+/// Upper bound of the memory repeatedly written by the memory stress test
+const MAX_MEMORY_LEN: usize = 256 * 1024 * 1024;
+
+/// The memory stress test takes at most this fraction of the free memory
+const MEMORY_DIVISOR: u64 = 4;
+
+/// Size of the memory stress buffer, leaving most free memory to the system.
+fn memory_len() -> usize {
+    match System::new().memory() {
+        Ok(memory) => ((memory.free.as_u64() / MEMORY_DIVISOR) as usize).min(MAX_MEMORY_LEN),
+        Err(err) => {
+            eprintln!("Reading free memory failed, skipping memory stress: {err}");
+            0
+        }
+    }
+}
+
+/// Keeps one core busy with integer mixing until `deadline`.
+fn stress_cpu(deadline: Instant) {
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    while Instant::now() < deadline {
+        for _ in 0..100_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state = black_box(state.wrapping_mul(0x2545_F491_4F6C_DD1D));
+        }
+    }
+}
+
+/// Keeps writing a large buffer until `deadline`, one byte per page per pass.
+fn stress_memory(deadline: Instant, len: usize) {
+    let mut buffer = vec![0u8; len];
+    let mut pass = 0u8;
+    while Instant::now() < deadline {
+        pass = pass.wrapping_add(1);
+        for page in buffer.chunks_mut(4096) {
+            page.fill(pass);
+        }
+        black_box(&buffer);
+    }
+}
+
+/// Runs the stress test on blocking threads and clears `running` once all finished.
+async fn run(kind: u8, duration: Duration, running: Arc<AtomicBool>) {
+    let deadline = Instant::now() + duration;
+    let mut workers = Vec::new();
+    if kind & CPU != 0 {
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+        for _ in 0..cores {
+            workers.push(tokio::task::spawn_blocking(move || stress_cpu(deadline)));
+        }
+    }
+    if kind & MEMORY != 0 {
+        let len = memory_len();
+        println!("Stressing {} MiB of memory", len / (1024 * 1024));
+        workers.push(tokio::task::spawn_blocking(move || {
+            stress_memory(deadline, len)
+        }));
+    }
+    for worker in workers {
+        let _ = worker.await;
+    }
+    println!("Stress test finished");
+    running.store(false, Ordering::Relaxed);
+}
+
+/// Creates the `STRESS_TEST` characteristic.
+///
+/// Writes are a type byte (`0x01` CPU, `0x02` memory, `0x03` both) and a
+/// `u16` LE duration of up to 300 seconds. Only one test runs at a time, and
+/// starting one needs an authenticated link. The memory test writes a quarter
+/// of the free memory, up to 256 MiB.
+pub fn characteristic() -> Characteristic {
+    let running = Arc::new(AtomicBool::new(false));
+
+    Characteristic {
+        uuid: crate::STRESS_TEST,
+        write: Some(CharacteristicWrite {
+            write: true,
+            encrypt_authenticated_write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                let result = match value[..] {
+                    [kind @ (CPU | MEMORY | BOTH), low, high] => {
+                        let secs = u16::from_le_bytes([low, high]);
+                        if secs == 0 || secs > MAX_DURATION_SECS {
+                            Err(ReqError::NotSupported)
+                        } else if running.swap(true, Ordering::Relaxed) {
+                            Err(ReqError::InProgress)
+                        } else {
+                            println!("Starting stress test {kind:#04x} for {secs}s");
+                            let duration = Duration::from_secs(secs as u64);
+                            crate::tasks::spawn("stress", run(kind, duration, running.clone()));
+                            Ok(())
+                        }
+                    }
+                    [_, _, _] => Err(ReqError::NotSupported),
+                    _ => Err(ReqError::InvalidValueLength),
+                };
+                async move { result }.boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
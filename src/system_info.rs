@@ -0,0 +1,76 @@
+use bluer::gatt::local::{Characteristic, CharacteristicRead};
+use futures::FutureExt;
+use serde::Serialize;
+use systemstat::{Platform, System};
+use tokio::fs;
+
+const HOSTNAME: &str = "/proc/sys/kernel/hostname";
+const KERNEL_RELEASE: &str = "/proc/sys/kernel/osrelease";
+const OS_RELEASE: &str = "/etc/os-release";
+
+#[derive(Debug, Serialize)]
+struct SystemInfo {
+    hostname: String,
+    kernel: String,
+    os: String,
+    /// System locale, e.g. `de_DE.UTF-8`
+    locale: String,
+    /// Uptime as text in the language of `locale`
+    uptime: String,
+}
+
+async fn read_trimmed(path: &str) -> String {
+    fs::read_to_string(path)
+        .await
+        .map(|content| content.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// `PRETTY_NAME` from `/etc/os-release`, e.g. `Debian GNU/Linux 12 (bookworm)`.
+async fn pretty_name() -> String {
+    let os_release = fs::read_to_string(OS_RELEASE).await.unwrap_or_default();
+    os_release
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("PRETTY_NAME="))
+        .map(|name| name.trim_matches('"').to_string())
+        .unwrap_or_default()
+}
+
+async fn system_info() -> SystemInfo {
+    SystemInfo {
+        hostname: read_trimmed(HOSTNAME).await,
+        kernel: read_trimmed(KERNEL_RELEASE).await,
+        os: pretty_name().await,
+        locale: crate::locale::current(),
+        uptime: System::new()
+            .uptime()
+            .map(|uptime| {
+                crate::uptime::format_uptime(uptime.as_secs(), &crate::locale::language())
+            })
+            .unwrap_or_default(),
+    }
+}
+
+/// Creates the `SYSTEM_INFO` characteristic.
+///
+/// Reads return hostname, kernel release, OS name, locale and the uptime
+/// formatted for it as CBOR, so boxes in a fleet can be told apart.
+pub fn characteristic() -> Characteristic {
+    Characteristic {
+        uuid: crate::SYSTEM_INFO,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(|_req| {
+                async move {
+                    let mut payload = Vec::new();
+                    ciborium::into_writer(&system_info().await, &mut payload)
+                        .expect("string struct is encodable");
+                    Ok(payload)
+                }
+                .boxed()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
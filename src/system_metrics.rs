@@ -0,0 +1,449 @@
+use crate::{
+    alerts,
+    encoding::{self, Encode},
+    events,
+    metrics::{self, InterpolatedMetrics},
+    provider::MetricProvider,
+};
+use futures::{future::BoxFuture, FutureExt};
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use systemstat::{CPULoad, DelayedMeasurement, Platform, System};
+use uuid::Uuid;
+
+/// Current frequency of the first core in kHz; all cores share one clock on the Pi
+const CPU_FREQ_PATH: &str = "/sys/devices/system/cpu/cpu0/cpufreq/scaling_cur_freq";
+
+/// System CPU load, measured between two samples. Degraded readings are
+/// not notified, as for the other [InterpolatedMetrics].
+pub struct CpuLoad {
+    metrics: Arc<Mutex<InterpolatedMetrics>>,
+    measurement: Option<io::Result<DelayedMeasurement<CPULoad>>>,
+}
+
+impl CpuLoad {
+    pub fn new(metrics: Arc<Mutex<InterpolatedMetrics>>) -> Self {
+        CpuLoad {
+            metrics,
+            measurement: None,
+        }
+    }
+}
+
+impl MetricProvider for CpuLoad {
+    fn uuid(&self) -> Uuid {
+        crate::CPU_LOAD
+    }
+
+    fn name(&self) -> &'static str {
+        "cpu_load"
+    }
+
+    fn sample(&mut self) -> BoxFuture<'_, Option<Vec<u8>>> {
+        // Started on one sample and read on the next, so the load covers a whole interval
+        let started = System::new().cpu_load_aggregate();
+        let Some(previous) = self.measurement.replace(started) else {
+            return async { None }.boxed();
+        };
+        let reading = previous
+            .and_then(|measurement| measurement.done())
+            .map(|load| load.system);
+        let mut metrics = self.metrics.lock().unwrap();
+        let load = metrics.cpu_load.update(reading).inspect(|&load| {
+            metrics.record(metrics::CPU_LOAD_INDEX, load);
+            println!("Updated CPU load characteristic: {load:.2}");
+        });
+        async move { load.map(|load| load.encode()) }.boxed()
+    }
+
+    fn priority(&self) -> u8 {
+        self.metrics
+            .lock()
+            .unwrap()
+            .priority(metrics::CPU_LOAD_INDEX)
+    }
+
+    fn notified(&mut self) {
+        self.metrics
+            .lock()
+            .unwrap()
+            .notified(metrics::CPU_LOAD_INDEX);
+    }
+}
+
+/// CPU temperature in °C, alerting once per excursion above a threshold
+pub struct CpuTemperature {
+    metrics: Arc<Mutex<InterpolatedMetrics>>,
+    /// Threshold in °C
+    alert: f32,
+    over_alert: bool,
+}
+
+impl CpuTemperature {
+    pub fn new(metrics: Arc<Mutex<InterpolatedMetrics>>, alert: f32) -> Self {
+        CpuTemperature {
+            metrics,
+            alert,
+            over_alert: false,
+        }
+    }
+
+    fn check_alert(&mut self, temperature: f32) {
+        let was_over = self.over_alert;
+        self.over_alert = temperature > self.alert;
+        if self.over_alert && !was_over {
+            eprintln!("CPU temperature {temperature}C exceeds {}C", self.alert);
+            events::publish(events::SystemEvent::ThresholdBreached {
+                metric: "temperature".to_string(),
+                value: temperature,
+            });
+            alerts::raise(alerts::OVER_TEMPERATURE, temperature);
+        }
+    }
+}
+
+impl MetricProvider for CpuTemperature {
+    fn uuid(&self) -> Uuid {
+        crate::TEMPERATURE
+    }
+
+    fn name(&self) -> &'static str {
+        "temperature"
+    }
+
+    fn sample(&mut self) -> BoxFuture<'_, Option<Vec<u8>>> {
+        let temperature = {
+            let mut metrics = self.metrics.lock().unwrap();
+            let temperature = metrics.cpu_temp.update(System::new().cpu_temp());
+            if let Some(temperature) = temperature {
+                metrics.record(metrics::CPU_TEMP_INDEX, temperature);
+                println!("Updated CPU temp characteristic: {temperature:.2}C");
+            }
+            temperature
+        };
+        if let Some(temperature) = temperature {
+            self.check_alert(temperature);
+        }
+        async move { temperature.map(|temperature| temperature.encode()) }.boxed()
+    }
+
+    fn priority(&self) -> u8 {
+        self.metrics
+            .lock()
+            .unwrap()
+            .priority(metrics::CPU_TEMP_INDEX)
+    }
+
+    fn notified(&mut self) {
+        self.metrics
+            .lock()
+            .unwrap()
+            .notified(metrics::CPU_TEMP_INDEX);
+    }
+}
+
+/// Used and total memory bytes
+pub struct MemoryUsage {
+    metrics: Arc<Mutex<InterpolatedMetrics>>,
+}
+
+impl MemoryUsage {
+    pub fn new(metrics: Arc<Mutex<InterpolatedMetrics>>) -> Self {
+        MemoryUsage { metrics }
+    }
+}
+
+impl MetricProvider for MemoryUsage {
+    fn uuid(&self) -> Uuid {
+        crate::RAM_USAGE
+    }
+
+    fn name(&self) -> &'static str {
+        "ram_usage"
+    }
+
+    fn sample(&mut self) -> BoxFuture<'_, Option<Vec<u8>>> {
+        let mut metrics = self.metrics.lock().unwrap();
+        let usage = metrics.memory.update(System::new().memory()).map(|memory| {
+            let total = memory.total.as_u64();
+            let used = total.saturating_sub(memory.free.as_u64());
+            metrics.record(metrics::MEMORY_INDEX, used as f32 / 1024.0 / 1024.0);
+            println!("Updated memory usage characteristic: {used}/{total} bytes");
+            encoding::usage(used, total).to_vec()
+        });
+        async move { usage }.boxed()
+    }
+
+    fn priority(&self) -> u8 {
+        self.metrics.lock().unwrap().priority(metrics::MEMORY_INDEX)
+    }
+
+    fn notified(&mut self) {
+        self.metrics.lock().unwrap().notified(metrics::MEMORY_INDEX);
+    }
+}
+
+/// Uptime in whole minutes
+pub struct Uptime {
+    metrics: Arc<Mutex<InterpolatedMetrics>>,
+}
+
+impl Uptime {
+    pub fn new(metrics: Arc<Mutex<InterpolatedMetrics>>) -> Self {
+        Uptime { metrics }
+    }
+}
+
+impl MetricProvider for Uptime {
+    fn uuid(&self) -> Uuid {
+        crate::UPTIME
+    }
+
+    fn name(&self) -> &'static str {
+        "uptime"
+    }
+
+    fn sample(&mut self) -> BoxFuture<'_, Option<Vec<u8>>> {
+        let mut metrics = self.metrics.lock().unwrap();
+        let uptime = metrics.uptime.update(System::new().uptime()).map(|uptime| {
+            let minutes = uptime.as_secs() / 60;
+            metrics.record(metrics::UPTIME_INDEX, minutes as f32);
+            println!("Updated uptime minutes characteristic: {minutes}");
+            encoding::uptime(uptime)
+        });
+        async move { uptime }.boxed()
+    }
+
+    fn priority(&self) -> u8 {
+        self.metrics.lock().unwrap().priority(metrics::UPTIME_INDEX)
+    }
+
+    fn notified(&mut self) {
+        self.metrics.lock().unwrap().notified(metrics::UPTIME_INDEX);
+    }
+}
+
+/// Used, total bytes and percentage of the root filesystem
+pub struct DiskUsage;
+
+impl MetricProvider for DiskUsage {
+    fn uuid(&self) -> Uuid {
+        crate::DISK_USAGE
+    }
+
+    fn name(&self) -> &'static str {
+        "disk_usage"
+    }
+
+    fn sample(&mut self) -> BoxFuture<'_, Option<Vec<u8>>> {
+        let usage = match System::new().mount_at("/") {
+            Ok(root) => {
+                let total = root.total.as_u64();
+                let used = total.saturating_sub(root.free.as_u64());
+                let percent = match total {
+                    0 => 0.0,
+                    _ => used as f32 / total as f32 * 100.0,
+                };
+                println!("Updated disk usage: {used}/{total} bytes ({percent:.1}%)");
+                Some(encoding::disk_usage(used, total, percent).to_vec())
+            }
+            Err(err) => {
+                eprintln!("Reading root filesystem usage failed: {err}");
+                None
+            }
+        };
+        async move { usage }.boxed()
+    }
+}
+
+/// Load of each core, measured between two samples
+#[derive(Default)]
+pub struct CpuCoreLoad {
+    measurement: Option<DelayedMeasurement<Vec<CPULoad>>>,
+}
+
+impl MetricProvider for CpuCoreLoad {
+    fn uuid(&self) -> Uuid {
+        crate::CPU_CORE_LOAD
+    }
+
+    fn name(&self) -> &'static str {
+        "cpu_core_load"
+    }
+
+    fn sample(&mut self) -> BoxFuture<'_, Option<Vec<u8>>> {
+        // Started on one sample and read on the next, so the load covers a whole interval
+        let previous = std::mem::replace(&mut self.measurement, System::new().cpu_load().ok());
+        let loads = match previous.map(|measurement| measurement.done()) {
+            Some(Ok(core_loads)) => {
+                let loads: Vec<f32> = core_loads.iter().map(|load| 1.0 - load.idle).collect();
+                println!(
+                    "Updated CPU core load characteristic for {} cores",
+                    core_loads.len()
+                );
+                Some(encoding::core_loads(&loads))
+            }
+            Some(Err(err)) => {
+                eprintln!("Reading CPU core load failed: {err}");
+                None
+            }
+            None => None,
+        };
+        async move { loads }.boxed()
+    }
+}
+
+/// 1, 5 and 15 minute load averages
+pub struct LoadAverage;
+
+impl MetricProvider for LoadAverage {
+    fn uuid(&self) -> Uuid {
+        crate::LOAD_AVERAGE
+    }
+
+    fn name(&self) -> &'static str {
+        "load_average"
+    }
+
+    fn sample(&mut self) -> BoxFuture<'_, Option<Vec<u8>>> {
+        let averages = match System::new().load_average() {
+            Ok(load_average) => {
+                println!(
+                    "Updated load average characteristic: {:.2} {:.2} {:.2}",
+                    load_average.one, load_average.five, load_average.fifteen
+                );
+                Some(
+                    encoding::load_averages(
+                        load_average.one,
+                        load_average.five,
+                        load_average.fifteen,
+                    )
+                    .to_vec(),
+                )
+            }
+            Err(err) => {
+                eprintln!("Reading load average failed: {err}");
+                None
+            }
+        };
+        async move { averages }.boxed()
+    }
+}
+
+/// Used and total swap bytes
+pub struct SwapUsage;
+
+impl MetricProvider for SwapUsage {
+    fn uuid(&self) -> Uuid {
+        crate::SWAP_USAGE
+    }
+
+    fn name(&self) -> &'static str {
+        "swap_usage"
+    }
+
+    fn sample(&mut self) -> BoxFuture<'_, Option<Vec<u8>>> {
+        let usage = match System::new().swap() {
+            Ok(swap) => {
+                let total = swap.total.as_u64();
+                let used = total.saturating_sub(swap.free.as_u64());
+                println!("Updated swap usage: {used}/{total} bytes");
+                Some(encoding::usage(used, total).to_vec())
+            }
+            Err(err) => {
+                eprintln!("Reading swap usage failed: {err}");
+                None
+            }
+        };
+        async move { usage }.boxed()
+    }
+}
+
+/// Received and transmitted bytes per second of one interface
+pub struct NetworkThroughput {
+    interface: String,
+    /// Time and rx/tx byte counters of the previous sample
+    previous: Option<(Instant, u64, u64)>,
+}
+
+impl NetworkThroughput {
+    pub fn new(interface: String) -> Self {
+        NetworkThroughput {
+            interface,
+            previous: None,
+        }
+    }
+}
+
+impl MetricProvider for NetworkThroughput {
+    fn uuid(&self) -> Uuid {
+        crate::NETWORK_THROUGHPUT
+    }
+
+    fn name(&self) -> &'static str {
+        "network_throughput"
+    }
+
+    fn sample(&mut self) -> BoxFuture<'_, Option<Vec<u8>>> {
+        let interface = &self.interface;
+        let throughput = match System::new().network_stats(interface) {
+            Ok(stats) => {
+                let now = Instant::now();
+                let (rx_total, tx_total) = (stats.rx_bytes.as_u64(), stats.tx_bytes.as_u64());
+                self.previous
+                    .replace((now, rx_total, tx_total))
+                    .map(|(previous, rx_bytes, tx_bytes)| {
+                        let secs = now.duration_since(previous).as_secs_f64().max(f64::EPSILON);
+                        let rx_per_sec = (rx_total.saturating_sub(rx_bytes) as f64 / secs) as u64;
+                        let tx_per_sec = (tx_total.saturating_sub(tx_bytes) as f64 / secs) as u64;
+                        println!("Updated {interface} throughput: rx {rx_per_sec} B/s, tx {tx_per_sec} B/s");
+                        encoding::throughput(rx_per_sec, tx_per_sec).to_vec()
+                    })
+            }
+            Err(err) => {
+                eprintln!("Reading {interface} statistics failed: {err}");
+                None
+            }
+        };
+        async move { throughput }.boxed()
+    }
+}
+
+/// Current CPU frequency in Hz
+pub struct CpuFrequency;
+
+impl MetricProvider for CpuFrequency {
+    fn uuid(&self) -> Uuid {
+        crate::CPU_FREQUENCY
+    }
+
+    fn name(&self) -> &'static str {
+        "cpu_frequency"
+    }
+
+    fn sample(&mut self) -> BoxFuture<'_, Option<Vec<u8>>> {
+        async move {
+            match tokio::fs::read_to_string(CPU_FREQ_PATH).await {
+                Ok(khz) => match khz.trim().parse::<u64>() {
+                    Ok(khz) => {
+                        let hz = khz * 1000;
+                        println!("Updated CPU frequency: {hz} Hz");
+                        Some(hz.encode())
+                    }
+                    Err(err) => {
+                        eprintln!("Parsing CPU frequency failed: {err}");
+                        None
+                    }
+                },
+                Err(err) => {
+                    eprintln!("Reading CPU frequency failed: {err}");
+                    None
+                }
+            }
+        }
+        .boxed()
+    }
+}
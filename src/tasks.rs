@@ -0,0 +1,176 @@
+use bluer::gatt::local::{Characteristic, CharacteristicRead, ReqError};
+use futures::FutureExt;
+use serde::Serialize;
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap, VecDeque},
+    future::Future,
+    panic::AssertUnwindSafe,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+use tokio::{
+    task::{AbortHandle, JoinHandle},
+    time,
+};
+
+/// Pause before restarting a panicked task
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Exited tasks whose outcome is kept, the oldest are forgotten first
+const MAX_EXITED: usize = 32;
+
+/// Largest `TASK_STATUS` value, fits one long ATT read
+const MAX_STATUS_SIZE: usize = 512;
+
+static REGISTRY: LazyLock<Mutex<TaskRegistry>> =
+    LazyLock::new(|| Mutex::new(TaskRegistry::default()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum TaskState {
+    Running,
+    Finished,
+    Panicked,
+}
+
+/// Background tasks by name
+#[derive(Default)]
+pub struct TaskRegistry {
+    tasks: HashMap<String, JoinHandle<()>>,
+    /// Outcome of tasks that were reaped from `tasks`, oldest first, at most [MAX_EXITED]
+    exited: VecDeque<(String, TaskState)>,
+}
+
+impl TaskRegistry {
+    fn insert(&mut self, name: &str, handle: JoinHandle<()>) {
+        self.reap();
+        self.exited.retain(|(exited, _)| exited != name);
+        if let Some(previous) = self.tasks.insert(name.to_string(), handle) {
+            eprintln!("Task {name} registered while the previous one runs, detaching it");
+            drop(previous);
+        }
+    }
+
+    /// Moves finished tasks from `tasks` to `exited`.
+    fn reap(&mut self) {
+        let finished: Vec<String> = self
+            .tasks
+            .iter()
+            .filter(|(_, handle)| handle.is_finished())
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in finished {
+            let handle = self.tasks.remove(&name).expect("finished task");
+            let state = match handle.now_or_never() {
+                Some(Err(err)) if err.is_panic() => TaskState::Panicked,
+                _ => TaskState::Finished,
+            };
+            self.exited.retain(|(exited, _)| *exited != name);
+            if self.exited.len() == MAX_EXITED {
+                self.exited.pop_front();
+            }
+            self.exited.push_back((name, state));
+        }
+    }
+
+    /// Reaps finished tasks and returns the state of every task, running
+    /// ones by name followed by exited ones, most recent first.
+    fn states(&mut self) -> Vec<(String, TaskState)> {
+        self.reap();
+        let mut running: Vec<String> = self.tasks.keys().cloned().collect();
+        running.sort();
+        running
+            .into_iter()
+            .map(|name| (name, TaskState::Running))
+            .chain(self.exited.iter().rev().cloned())
+            .collect()
+    }
+}
+
+/// Encodes the task states that fit in [MAX_STATUS_SIZE] as a CBOR map,
+/// leaving out the tasks that exited longest ago first.
+fn encode() -> Result<Vec<u8>, ciborium::ser::Error<std::io::Error>> {
+    let states = REGISTRY.lock().unwrap().states();
+    let mut len = states.len();
+    loop {
+        let by_name: BTreeMap<_, _> = states[..len].iter().cloned().collect();
+        let mut value = Vec::new();
+        ciborium::into_writer(&by_name, &mut value)?;
+        if value.len() <= MAX_STATUS_SIZE || len == 0 {
+            return Ok(value);
+        }
+        len -= 1;
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+/// Logs a panic of `future` before passing it on to the task's join handle.
+async fn logged<F: Future<Output = ()>>(name: String, future: F) {
+    if let Err(panic) = AssertUnwindSafe(future).catch_unwind().await {
+        eprintln!("Task {name} panicked: {}", panic_message(panic.as_ref()));
+        std::panic::resume_unwind(panic);
+    }
+}
+
+/// Spawns a background task and registers it under `name`. The returned
+/// handle stops the task.
+pub fn spawn<F>(name: &str, future: F) -> AbortHandle
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let handle = tokio::spawn(logged(name.to_string(), future));
+    let abort = handle.abort_handle();
+    REGISTRY.lock().unwrap().insert(name, handle);
+    abort
+}
+
+/// Like [spawn], but restarts the task with a fresh future from `make` after a panic.
+pub fn spawn_restarting<M, F>(name: &str, make: M)
+where
+    M: Fn() -> F + Send + 'static,
+    F: Future<Output = ()> + Send + 'static,
+{
+    let task_name = name.to_string();
+    spawn(name, async move {
+        loop {
+            let result = tokio::spawn(logged(task_name.clone(), make())).await;
+            match result {
+                Err(err) if err.is_panic() => {
+                    println!("Restarting task {task_name}");
+                    time::sleep(RESTART_DELAY).await;
+                }
+                _ => break,
+            }
+        }
+    });
+}
+
+/// Creates the `TASK_STATUS` characteristic.
+///
+/// Reads return a CBOR map of task names to `running`, `finished` or
+/// `panicked`, leaving out the tasks that exited longest ago beyond 512 bytes.
+pub fn characteristic() -> Characteristic {
+    Characteristic {
+        uuid: crate::TASK_STATUS,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(|_req| {
+                let result = encode().map_err(|err| {
+                    eprintln!("Encoding task states failed: {err}");
+                    ReqError::Failed
+                });
+                async move { result }.boxed()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
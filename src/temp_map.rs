@@ -0,0 +1,132 @@
+use crate::{
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotify, CharacteristicNotifyMethod,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, StreamExt};
+use std::{path::PathBuf, time::Duration};
+use tokio::{fs, time};
+
+const THERMAL_DIR: &str = "/sys/class/thermal";
+
+const MAX_ZONES: usize = 8;
+const MAX_NAME_LEN: usize = 20;
+
+/// Reads the type and temperature in millidegrees Celsius of each thermal zone.
+async fn read_zones() -> std::io::Result<Vec<(String, i32)>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(THERMAL_DIR)?
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("thermal_zone")
+        })
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+
+    let mut zones = Vec::new();
+    for path in paths.into_iter().take(MAX_ZONES) {
+        let Ok(temp) = fs::read_to_string(path.join("temp")).await else {
+            continue;
+        };
+        let Ok(temp) = temp.trim().parse() else {
+            continue;
+        };
+        let name = fs::read_to_string(path.join("type"))
+            .await
+            .unwrap_or_default();
+        zones.push((name.trim().to_string(), temp));
+    }
+    Ok(zones)
+}
+
+/// Encodes a zone count, then per zone its name length, name and `i32` LE
+/// temperature, leaving out zones that do not fit in `max_len`.
+fn encode(zones: &[(String, i32)], max_len: usize) -> Vec<u8> {
+    let mut payload = vec![0u8];
+    for (name, temp) in zones {
+        let mut end = name.len().min(MAX_NAME_LEN);
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        if payload.len() + 1 + end + 4 > max_len {
+            break;
+        }
+        payload.push(end as u8);
+        payload.extend_from_slice(&name.as_bytes()[..end]);
+        payload.extend_from_slice(&temp.to_le_bytes());
+        payload[0] += 1;
+    }
+    payload
+}
+
+async fn serve(control: CharacteristicControl, retry: RetryPolicy) {
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut sequence = NotifySequence::new("temperature map");
+    let mut interval = time::interval(Duration::from_secs(1));
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting temperature map notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "temperature map");
+                        writer_opt = Some(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            _ = interval.tick() => {
+                let Some(writer) = &mut writer_opt else {
+                    continue;
+                };
+                let zones = match read_zones().await {
+                    Ok(zones) => zones,
+                    Err(err) => {
+                        eprintln!("Reading thermal zones failed: {err}");
+                        continue;
+                    }
+                };
+                let payload = encode(&zones, writer.mtu() - crate::payload::HEADER_LEN);
+                if retry.notify(writer, &sequence.stamp(&payload)).await.is_err() {
+                    writer_opt = None;
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `TEMP_MAP` characteristic, notifying all thermal zones every second.
+///
+/// The payload is a zone count followed by, for each of up to eight zones, a
+/// name length, the name (at most 20 bytes) and the `i32` LE temperature in
+/// millidegrees Celsius.
+pub fn characteristic(retry: &RetryPolicies) -> Characteristic {
+    let (control, control_handle) = characteristic_control();
+    crate::tasks::spawn("temp-map", serve(control, retry.get("temp_map")));
+
+    Characteristic {
+        uuid: crate::TEMP_MAP,
+        notify: Some(CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Io,
+            ..Default::default()
+        }),
+        control_handle,
+        ..Default::default()
+    }
+}
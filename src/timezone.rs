@@ -0,0 +1,83 @@
+use std::time::Duration;
+use tokio::{process::Command, sync::watch};
+
+/// Returns the IANA timezone of the public IP as plain text
+const GEOIP_URL: &str = "https://ipapi.co/timezone";
+
+/// Upper bound for the GeoIP lookup
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sets the system timezone with `timedatectl`.
+pub async fn set(timezone: &str) -> std::io::Result<()> {
+    let output = Command::new("timedatectl")
+        .args(["set-timezone", timezone])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    println!("Timezone set to {timezone}");
+    Ok(())
+}
+
+/// Looks up the timezone of the public IP address.
+async fn lookup(client: &reqwest::Client) -> reqwest::Result<String> {
+    let timezone = client
+        .get(GEOIP_URL)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(timezone.trim().to_string())
+}
+
+/// Names such as `Europe/Berlin` or `America/Argentina/Buenos_Aires`
+fn valid_timezone(timezone: &str) -> bool {
+    !timezone.is_empty()
+        && !timezone.starts_with('/')
+        && !timezone.contains("..")
+        && timezone
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/_+-".contains(c))
+}
+
+async fn detect(client: &reqwest::Client) {
+    let timezone = match lookup(client).await {
+        Ok(timezone) if valid_timezone(&timezone) => timezone,
+        Ok(timezone) => {
+            eprintln!("GeoIP returned unexpected timezone {timezone:?}, keeping current one");
+            return;
+        }
+        Err(err) => {
+            eprintln!("GeoIP timezone lookup failed, keeping current one: {err}");
+            return;
+        }
+    };
+    if let Err(err) = set(&timezone).await {
+        eprintln!("Could not set timezone {timezone}: {err}");
+    }
+}
+
+/// Detects the timezone on startup and again whenever the connected SSID changes.
+pub async fn auto_detect(mut ssid: watch::Receiver<Option<String>>) {
+    let client = match reqwest::Client::builder().timeout(LOOKUP_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("Timezone detection unavailable: {err}");
+            return;
+        }
+    };
+
+    let mut detected_for = ssid.borrow_and_update().clone();
+    detect(&client).await;
+    while ssid.changed().await.is_ok() {
+        let current = ssid.borrow_and_update().clone();
+        if current != detected_for {
+            detected_for = current;
+            detect(&client).await;
+        }
+    }
+}
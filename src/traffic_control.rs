@@ -0,0 +1,166 @@
+use bluer::gatt::local::{
+    Characteristic, CharacteristicRead, CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+};
+use futures::FutureExt;
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+use tokio::{fs, process::Command};
+
+/// Bucket size for both directions
+const BURST: &str = "32kbit";
+
+/// Network interfaces in index order, sorted by name.
+async fn interfaces() -> std::io::Result<Vec<String>> {
+    let mut entries = fs::read_dir("/sys/class/net").await?;
+    let mut names = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    Ok(names)
+}
+
+async fn interface(index: u8) -> Result<String, ReqError> {
+    let interfaces = interfaces().await.map_err(|_| ReqError::Failed)?;
+    interfaces
+        .into_iter()
+        .nth(index as usize)
+        .ok_or(ReqError::NotSupported)
+}
+
+async fn tc(args: &[&str]) -> std::io::Result<String> {
+    let output = Command::new("tc").args(args).output().await?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Limits egress with a token bucket and ingress with a policer. Zero removes a limit.
+async fn set_limits(iface: &str, download_kbps: u32, upload_kbps: u32) -> std::io::Result<()> {
+    if upload_kbps == 0 {
+        // Fails when no limit is set, which is what we want anyway
+        let _ = tc(&["qdisc", "del", "dev", iface, "root"]).await;
+    } else {
+        let rate = format!("{upload_kbps}kbit");
+        tc(&[
+            "qdisc", "replace", "dev", iface, "root", "tbf", "rate", &rate, "burst", BURST,
+            "latency", "400ms",
+        ])
+        .await?;
+    }
+
+    let _ = tc(&["qdisc", "del", "dev", iface, "ingress"]).await;
+    if download_kbps != 0 {
+        let rate = format!("{download_kbps}kbit");
+        tc(&["qdisc", "add", "dev", iface, "handle", "ffff:", "ingress"]).await?;
+        tc(&[
+            "filter", "add", "dev", iface, "parent", "ffff:", "protocol", "all", "prio", "1",
+            "u32", "match", "u32", "0", "0", "police", "rate", &rate, "burst", BURST, "drop",
+            "flowid", ":1",
+        ])
+        .await?;
+    }
+    println!("Limited {iface} to {download_kbps} kbit/s down, {upload_kbps} kbit/s up");
+    Ok(())
+}
+
+/// Finds the first `rate <N><unit>` in `tc` output and converts it to kbit/s.
+fn parse_rate_kbps(output: &str) -> u32 {
+    let mut words = output.split_whitespace();
+    let Some(rate) = words
+        .find(|word| *word == "rate")
+        .and_then(|_| words.next())
+    else {
+        return 0;
+    };
+    let split = rate
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(rate.len());
+    let (value, unit) = rate.split_at(split);
+    let value: f64 = value.parse().unwrap_or_default();
+    let kbps = match unit.to_ascii_lowercase().as_str() {
+        "gbit" => value * 1_000_000.0,
+        "mbit" => value * 1_000.0,
+        "kbit" => value,
+        "bit" => value / 1_000.0,
+        _ => 0.0,
+    };
+    kbps.round() as u32
+}
+
+async fn get_limits(iface: &str) -> std::io::Result<(u32, u32)> {
+    let root = tc(&["qdisc", "show", "dev", iface, "root"]).await?;
+    let upload = if root.contains("tbf") {
+        parse_rate_kbps(&root)
+    } else {
+        0
+    };
+    let ingress = tc(&["filter", "show", "dev", iface, "parent", "ffff:"])
+        .await
+        .unwrap_or_default();
+    Ok((parse_rate_kbps(&ingress), upload))
+}
+
+fn tc_error(err: std::io::Error) -> ReqError {
+    eprintln!("Traffic control failed: {err}");
+    ReqError::Failed
+}
+
+/// Creates the `TC_LIMIT` characteristic.
+///
+/// Writes are an interface index followed by download and upload limits in
+/// kbit/s (`u32` LE each). Reads return the same layout for the interface
+/// written last. Both require a bonded connection.
+pub fn characteristic() -> Characteristic {
+    let selected = Arc::new(AtomicU8::new(0));
+    let read_selected = selected.clone();
+
+    Characteristic {
+        uuid: crate::TC_LIMIT,
+        read: Some(CharacteristicRead {
+            read: true,
+            encrypt_authenticated_read: true,
+            fun: Box::new(move |_req| {
+                let index = read_selected.load(Ordering::Relaxed);
+                async move {
+                    let iface = interface(index).await?;
+                    let (download, upload) = get_limits(&iface).await.map_err(tc_error)?;
+                    let mut value = vec![index];
+                    value.extend_from_slice(&download.to_le_bytes());
+                    value.extend_from_slice(&upload.to_le_bytes());
+                    Ok(value)
+                }
+                .boxed()
+            }),
+            ..Default::default()
+        }),
+        write: Some(CharacteristicWrite {
+            write: true,
+            encrypt_authenticated_write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |value, _req| {
+                let selected = selected.clone();
+                async move {
+                    let [index, d0, d1, d2, d3, u0, u1, u2, u3] = value[..] else {
+                        return Err(ReqError::InvalidValueLength);
+                    };
+                    let iface = interface(index).await?;
+                    let download = u32::from_le_bytes([d0, d1, d2, d3]);
+                    let upload = u32::from_le_bytes([u0, u1, u2, u3]);
+                    set_limits(&iface, download, upload)
+                        .await
+                        .map_err(tc_error)?;
+                    selected.store(index, Ordering::Relaxed);
+                    Ok(())
+                }
+                .boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
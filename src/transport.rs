@@ -0,0 +1,39 @@
+use bluer::Adapter;
+use clap::ValueEnum;
+
+/// Radio the GATT service is offered over.
+///
+/// BlueZ serves a registered GATT application over both LE and, on adapters
+/// with classic support, BR/EDR: it publishes an SDP record per primary
+/// service and accepts ATT on L2CAP PSM 31. What differs is how clients find
+/// the server. LE centrals scan for the advertisement, while classic devices
+/// only see an adapter that is discoverable through inquiry. Classic links
+/// have to be paired before most hosts resolve the GATT services.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Transport {
+    /// Advertise over Bluetooth Low Energy only
+    Le,
+    /// Make the adapter discoverable over classic Bluetooth only
+    Bredr,
+    /// Both of the above
+    Dual,
+}
+
+impl Transport {
+    pub fn le(self) -> bool {
+        matches!(self, Transport::Le | Transport::Dual)
+    }
+
+    pub fn bredr(self) -> bool {
+        matches!(self, Transport::Bredr | Transport::Dual)
+    }
+}
+
+/// Keeps the adapter discoverable and pairable for classic inquiries.
+pub async fn enable_bredr(adapter: &Adapter) -> bluer::Result<()> {
+    adapter.set_pairable(true).await?;
+    adapter.set_discoverable_timeout(0).await?;
+    adapter.set_discoverable(true).await?;
+    println!("Discoverable over BR/EDR as {}", adapter.alias().await?);
+    Ok(())
+}
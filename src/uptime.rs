@@ -0,0 +1,134 @@
+use bluer::gatt::local::{Characteristic, CharacteristicRead, ReqError};
+use futures::FutureExt;
+use systemstat::{Platform, System};
+
+/// Words of one language: singular and plural of day, hour and minute, then
+/// the text for less than a minute
+type Words = ([(&'static str, &'static str); 3], &'static str);
+
+/// Words by language of the locale, English for any other
+const WORDS: &[(&str, Words)] = &[
+    (
+        "en",
+        (
+            [("day", "days"), ("hour", "hours"), ("minute", "minutes")],
+            "just started",
+        ),
+    ),
+    (
+        "de",
+        (
+            [
+                ("Tag", "Tage"),
+                ("Stunde", "Stunden"),
+                ("Minute", "Minuten"),
+            ],
+            "gerade gestartet",
+        ),
+    ),
+    (
+        "fr",
+        (
+            [
+                ("jour", "jours"),
+                ("heure", "heures"),
+                ("minute", "minutes"),
+            ],
+            "vient de démarrer",
+        ),
+    ),
+    (
+        "es",
+        (
+            [("día", "días"), ("hora", "horas"), ("minuto", "minutos")],
+            "recién iniciado",
+        ),
+    ),
+];
+
+fn words(language: &str) -> &'static Words {
+    WORDS
+        .iter()
+        .find(|(known, _)| *known == language)
+        .map_or(&WORDS[0].1, |(_, words)| words)
+}
+
+/// Formats an uptime in `language` as e.g. `3 days, 4 hours, 12 minutes`,
+/// leaving out zero units.
+pub fn format_uptime(secs: u64, language: &str) -> String {
+    let (units, just_started) = words(language);
+    let counts = [secs / 86400, secs % 86400 / 3600, secs % 3600 / 60];
+    let parts = counts
+        .iter()
+        .zip(units)
+        .filter(|(count, _)| **count > 0)
+        .map(|(count, (singular, plural))| match count {
+            1 => format!("1 {singular}"),
+            _ => format!("{count} {plural}"),
+        })
+        .collect::<Vec<_>>();
+    if parts.is_empty() {
+        just_started.to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+/// Creates the `UPTIME_HUMAN` characteristic, reading the uptime as UTF-8
+/// text in the language of the system locale.
+pub fn characteristic() -> Characteristic {
+    Characteristic {
+        uuid: crate::UPTIME_HUMAN,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(|_req| {
+                let result = match System::new().uptime() {
+                    Ok(uptime) => Ok(
+                        format_uptime(uptime.as_secs(), &crate::locale::language()).into_bytes()
+                    ),
+                    Err(err) => {
+                        eprintln!("Reading uptime failed: {err}");
+                        Err(ReqError::Failed)
+                    }
+                };
+                async move { result }.boxed()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_single_units() {
+        assert_eq!(format_uptime(86400, "en"), "1 day");
+        assert_eq!(format_uptime(3600, "en"), "1 hour");
+        assert_eq!(format_uptime(60, "en"), "1 minute");
+    }
+
+    #[test]
+    fn formats_plurals_and_skips_zero_units() {
+        assert_eq!(
+            format_uptime(3 * 86400 + 4 * 3600 + 12 * 60, "en"),
+            "3 days, 4 hours, 12 minutes"
+        );
+        assert_eq!(format_uptime(2 * 86400 + 5 * 60, "en"), "2 days, 5 minutes");
+    }
+
+    #[test]
+    fn formats_in_the_locale_language() {
+        assert_eq!(format_uptime(86400 + 2 * 60, "de"), "1 Tag, 2 Minuten");
+        assert_eq!(format_uptime(30, "fr"), "vient de démarrer");
+        assert_eq!(format_uptime(3600, "xx"), "1 hour");
+    }
+
+    #[test]
+    fn sub_minute_is_just_started() {
+        assert_eq!(format_uptime(0, "en"), "just started");
+        assert_eq!(format_uptime(59, "en"), "just started");
+    }
+}
@@ -0,0 +1,90 @@
+use crate::ring_buffer::RingBuffer;
+use bluer::gatt::local::{Characteristic, CharacteristicRead};
+use futures::FutureExt;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{fs, time};
+
+/// Power supplies registered by battery and UPS HAT drivers
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Samples kept, one per [SAMPLE_INTERVAL]
+const HISTORY_LEN: usize = 60;
+
+type VoltageHistory = RingBuffer<u16, HISTORY_LEN>;
+
+/// Wire size of `VOLTAGE_HISTORY`
+const VOLTAGE_HISTORY_LEN: usize = 120;
+const _: () = assert!(VOLTAGE_HISTORY_LEN == HISTORY_LEN * size_of::<u16>());
+
+/// Encodes the history as `u16` LE millivolts, oldest first.
+fn encode(history: &VoltageHistory) -> [u8; VOLTAGE_HISTORY_LEN] {
+    let millivolts: Vec<[u8; 2]> = history.padded().map(u16::to_le_bytes).collect();
+    crate::encoding::fixed(&[millivolts.as_flattened()])
+}
+
+/// Finds the first power supply reporting its voltage.
+fn find_source() -> std::io::Result<PathBuf> {
+    let mut supplies = std::fs::read_dir(POWER_SUPPLY_DIR)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path().join("voltage_now"))
+        .filter(|path| path.exists())
+        .collect::<Vec<_>>();
+    supplies.sort();
+    supplies.into_iter().next().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no power supply reports voltage_now",
+        )
+    })
+}
+
+/// Reads a voltage in millivolts; sysfs reports microvolts.
+async fn read_millivolts(source: &Path) -> std::io::Result<u16> {
+    let microvolts: u64 = fs::read_to_string(source)
+        .await?
+        .trim()
+        .parse()
+        .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
+    Ok((microvolts / 1000).min(u16::MAX as u64) as u16)
+}
+
+async fn sample(source: PathBuf, history: Arc<Mutex<VoltageHistory>>) {
+    let mut interval = time::interval(SAMPLE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match read_millivolts(&source).await {
+            Ok(millivolts) => history.lock().unwrap().push(millivolts),
+            Err(err) => eprintln!("Reading {} failed: {err}", source.display()),
+        }
+    }
+}
+
+/// Creates the `VOLTAGE_HISTORY` characteristic and starts sampling the supply voltage.
+///
+/// Reads return the last 60 samples as `u16` LE millivolts, oldest first,
+/// with zeros for samples not taken yet.
+pub fn characteristic() -> std::io::Result<Characteristic> {
+    let source = find_source()?;
+    println!("Recording voltage history of {}", source.display());
+    let history = Arc::new(Mutex::new(VoltageHistory::new()));
+    crate::tasks::spawn("voltage", sample(source, history.clone()));
+
+    Ok(Characteristic {
+        uuid: crate::VOLTAGE_HISTORY,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(move |_req| {
+                let value = encode(&history.lock().unwrap()).to_vec();
+                async move { Ok(value) }.boxed()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,131 @@
+use crate::response::Responder;
+use bluer::gatt::local::{
+    Characteristic, CharacteristicWrite, CharacteristicWriteMethod, ReqError,
+};
+use futures::FutureExt;
+use serde::Deserialize;
+use std::{process::Stdio, time::Duration};
+use tokio::{io::AsyncWriteExt, process::Command, sync::watch, time};
+use zeroize::Zeroize;
+
+/// Upper bound for association and DHCP
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const CONNECTED: u8 = 0x00;
+const AUTH_FAILURE: u8 = 0x01;
+const NOT_FOUND: u8 = 0x02;
+const TIMEOUT: u8 = 0x03;
+/// `nmcli` could not be run or failed for another reason
+const FAILED: u8 = 0x04;
+
+/// What `nmcli` reports when activation failed for want of the right password
+const SECRETS_REQUIRED: &str = "Secrets were required";
+
+#[derive(Deserialize)]
+struct Credentials {
+    ssid: String,
+    password: String,
+}
+
+/// Adds and activates the network with `nmcli`, returning a status byte.
+///
+/// The password goes to `nmcli --ask` on stdin, keeping it out of the
+/// command line other users can read in `/proc`.
+async fn connect(credentials: &Credentials) -> u8 {
+    let wait_secs = CONNECT_TIMEOUT.as_secs().to_string();
+    let child = Command::new("nmcli")
+        .args(["--ask", "--wait", &wait_secs, "device", "wifi", "connect"])
+        .arg(&credentials.ssid)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("Could not run nmcli: {err}");
+            return FAILED;
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        let mut line = format!("{}\n", credentials.password).into_bytes();
+        let written = stdin.write_all(&line).await;
+        line.zeroize();
+        if let Err(err) = written {
+            eprintln!("Passing the Wi-Fi password to nmcli failed: {err}");
+            return FAILED;
+        }
+    }
+
+    let output = match time::timeout(
+        CONNECT_TIMEOUT + Duration::from_secs(5),
+        child.wait_with_output(),
+    )
+    .await
+    {
+        Err(_) => return TIMEOUT,
+        Ok(Err(err)) => {
+            eprintln!("Waiting for nmcli failed: {err}");
+            return FAILED;
+        }
+        Ok(Ok(output)) => output,
+    };
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // See "EXIT STATUS" in nmcli(1)
+    match output.status.code() {
+        Some(0) => CONNECTED,
+        Some(3) => TIMEOUT,
+        Some(10) => NOT_FOUND,
+        Some(4) if stderr.contains(SECRETS_REQUIRED) => AUTH_FAILURE,
+        code => {
+            eprintln!("nmcli failed with {code:?}: {}", stderr.trim());
+            FAILED
+        }
+    }
+}
+
+/// Creates the `WIFI_CONNECT` characteristic.
+///
+/// Accepts a CBOR map with `ssid` and `password` and reports the outcome
+/// on `WRITE_REQUEST_RESPONSE`: `0x00` connected, `0x01` authentication
+/// failure, `0x02` network not found, `0x03` timeout, `0x04` any other
+/// `nmcli` failure.
+/// The SSID of each successful connection is published on `connected_ssid`.
+pub fn characteristic(
+    responder: Responder,
+    connected_ssid: watch::Sender<Option<String>>,
+) -> Characteristic {
+    Characteristic {
+        uuid: crate::WIFI_CONNECT,
+        write: Some(CharacteristicWrite {
+            write: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |mut value, _req| {
+                let credentials: Result<Credentials, _> = ciborium::from_reader(value.as_slice());
+                value.zeroize();
+                let Ok(mut credentials) = credentials else {
+                    return async move { Err(ReqError::Failed) }.boxed();
+                };
+
+                let responder = responder.clone();
+                let connected_ssid = connected_ssid.clone();
+                crate::tasks::spawn("wifi-connect", async move {
+                    println!("Connecting to Wi-Fi network {}", credentials.ssid);
+                    let status = connect(&credentials).await;
+                    credentials.password.zeroize();
+                    println!(
+                        "Wi-Fi connect to {} finished with {status:#04x}",
+                        credentials.ssid
+                    );
+                    if status == CONNECTED {
+                        connected_ssid.send_replace(Some(credentials.ssid));
+                    }
+                    let _ = responder.send(vec![status]).await;
+                });
+                async move { Ok(()) }.boxed()
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
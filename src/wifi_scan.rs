@@ -0,0 +1,302 @@
+use crate::{
+    payload::{NotifySequence, HEADER_LEN},
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotify, CharacteristicNotifyMethod, CharacteristicWrite,
+        CharacteristicWriteMethod, ReqError,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::{process::Command, sync::mpsc, time};
+
+/// Wireless interface that is scanned
+const INTERFACE: &str = "wlan0";
+
+/// Upper bound for a single scan
+const SCAN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of access points reported, strongest first
+const MAX_RESULTS: usize = 10;
+
+/// Bytes of the `MORE_DATA` or `DONE` flag leading each results frame
+const FLAG_LEN: usize = 1;
+/// Flags whether more frames of the results follow
+const MORE_DATA: u8 = 0x01;
+const DONE: u8 = 0x00;
+
+#[derive(Debug, Serialize)]
+struct AccessPoint {
+    ssid: String,
+    bssid: String,
+    signal_dbm: i8,
+    channel: u8,
+}
+
+/// Maps a centre frequency in MHz to its Wi-Fi channel number.
+fn channel(freq_mhz: u32) -> u8 {
+    let channel = match freq_mhz {
+        2484 => 14,
+        2412..=2472 => (freq_mhz - 2407) / 5,
+        5955..=7115 => (freq_mhz - 5950) / 5,
+        5000..=5900 => (freq_mhz - 5000) / 5,
+        _ => 0,
+    };
+    channel as u8
+}
+
+/// Undoes the `\xNN` escapes `iw` prints for spaces at the ends, backslashes
+/// and non-printable bytes of an SSID.
+fn unescape_ssid(escaped: &str) -> String {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let hex = tail
+            .strip_prefix(b"x")
+            .and_then(|hex| hex.get(..2))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(decoded) if byte == b'\\' => {
+                bytes.push(decoded);
+                rest = &tail[3..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Parses the output of `iw dev <iface> scan`.
+fn parse_scan(output: &str) -> Vec<AccessPoint> {
+    let mut access_points: Vec<AccessPoint> = Vec::new();
+    for line in output.lines() {
+        if let Some(bss) = line.strip_prefix("BSS ") {
+            access_points.push(AccessPoint {
+                ssid: String::new(),
+                bssid: bss.chars().take(17).collect(),
+                signal_dbm: i8::MIN,
+                channel: 0,
+            });
+            continue;
+        }
+        let Some(ap) = access_points.last_mut() else {
+            continue;
+        };
+        let line = line.trim();
+        if let Some(ssid) = line.strip_prefix("SSID:") {
+            ap.ssid = unescape_ssid(ssid.trim_start());
+        } else if let Some(signal) = line.strip_prefix("signal: ") {
+            let dbm: f32 = signal.trim_end_matches(" dBm").parse().unwrap_or(f32::MIN);
+            ap.signal_dbm = dbm.clamp(i8::MIN as f32, i8::MAX as f32) as i8;
+        } else if let Some(freq) = line.strip_prefix("freq: ") {
+            // Newer iw versions print fractional frequencies
+            let freq: f32 = freq.parse().unwrap_or_default();
+            ap.channel = channel(freq as u32);
+        }
+    }
+
+    access_points.sort_by_key(|ap| std::cmp::Reverse(ap.signal_dbm));
+    access_points.truncate(MAX_RESULTS);
+    access_points
+}
+
+async fn scan() -> std::io::Result<Vec<AccessPoint>> {
+    let output = time::timeout(
+        SCAN_TIMEOUT,
+        Command::new("iw")
+            .args(["dev", INTERFACE, "scan"])
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "Wi-Fi scan timed out"))??;
+    if !output.status.success() {
+        return Err(std::io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(parse_scan(&String::from_utf8_lossy(&output.stdout)))
+}
+
+async fn serve(
+    control: CharacteristicControl,
+    mut triggers: mpsc::Receiver<()>,
+    retry: RetryPolicy,
+) {
+    let mut results_writer_opt: Option<CharacteristicWriter> = None;
+    let mut sequence = NotifySequence::new("Wi-Fi scan");
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting Wi-Fi scan notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "Wi-Fi scan");
+                        results_writer_opt = Some(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            Some(()) = triggers.recv() => {
+                let access_points = match scan().await {
+                    Ok(access_points) => access_points,
+                    Err(err) => {
+                        eprintln!("Wi-Fi scan failed: {err}");
+                        Vec::new()
+                    }
+                };
+                println!("Wi-Fi scan found {} access points", access_points.len());
+
+                let mut payload = Vec::new();
+                if let Err(err) = ciborium::into_writer(&access_points, &mut payload) {
+                    eprintln!("Could not encode Wi-Fi scan results: {err}");
+                    continue;
+                }
+                let Some(writer) = &mut results_writer_opt else {
+                    continue;
+                };
+                let frame_len = writer.mtu() - HEADER_LEN - FLAG_LEN;
+                let frames = payload.chunks(frame_len).count();
+                for (i, chunk) in payload.chunks(frame_len).enumerate() {
+                    let flag = if i + 1 == frames { DONE } else { MORE_DATA };
+                    let frame = [&[flag], chunk].concat();
+                    if retry.notify(writer, &sequence.stamp(&frame)).await.is_err() {
+                        results_writer_opt = None;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `WIFI_SCAN_TRIGGER` and `WIFI_SCAN_RESULTS` characteristics.
+pub fn characteristics(retry: &RetryPolicies) -> Vec<Characteristic> {
+    let (control, control_handle) = characteristic_control();
+    let (trigger_tx, trigger_rx) = mpsc::channel(1);
+    crate::tasks::spawn(
+        "wifi-scan",
+        serve(control, trigger_rx, retry.get("wifi_scan_results")),
+    );
+
+    vec![
+        // Any write starts a scan
+        Characteristic {
+            uuid: crate::WIFI_SCAN_TRIGGER,
+            write: Some(CharacteristicWrite {
+                write: true,
+                method: CharacteristicWriteMethod::Fun(Box::new(move |_value, _req| {
+                    // A full channel means a scan is already running
+                    let result = trigger_tx.try_send(()).map_err(|_| ReqError::InProgress);
+                    async move { result }.boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        // Scan results as CBOR, split into frames at the subscriber's MTU
+        // behind a `MORE_DATA` or `DONE` flag byte
+        Characteristic {
+            uuid: crate::WIFI_SCAN_RESULTS,
+            notify: Some(CharacteristicNotify {
+                notify: true,
+                method: CharacteristicNotifyMethod::Io,
+                ..Default::default()
+            }),
+            control_handle,
+            ..Default::default()
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCAN: &str = "\
+BSS 11:22:33:44:55:66(on wlan0)
+\tfreq: 2437
+\tsignal: -71.00 dBm
+\tSSID: guest\\x3anet\\x20
+BSS aa:bb:cc:dd:ee:ff(on wlan0) -- associated
+\tfreq: 5180.0
+\tsignal: -48.00 dBm
+\tSSID: home:5G
+BSS 00:00:00:00:00:01(on wlan0)
+\tfreq: 2412
+\tsignal: -90.00 dBm
+\tSSID: 
+";
+
+    #[test]
+    fn sorts_strongest_first() {
+        let access_points = parse_scan(SCAN);
+        let bssids: Vec<&str> = access_points.iter().map(|ap| ap.bssid.as_str()).collect();
+        assert_eq!(
+            bssids,
+            [
+                "aa:bb:cc:dd:ee:ff",
+                "11:22:33:44:55:66",
+                "00:00:00:00:00:01"
+            ]
+        );
+        assert_eq!(access_points[0].signal_dbm, -48);
+    }
+
+    #[test]
+    fn keeps_colons_in_ssids() {
+        let access_points = parse_scan(SCAN);
+        assert_eq!(access_points[0].ssid, "home:5G");
+        assert_eq!(access_points[1].ssid, "guest:net ");
+    }
+
+    #[test]
+    fn parses_empty_ssids() {
+        let access_points = parse_scan(SCAN);
+        assert_eq!(access_points[2].ssid, "");
+        assert_eq!(access_points[2].channel, 1);
+    }
+
+    #[test]
+    fn unescapes_ssids() {
+        assert_eq!(unescape_ssid(r"a\x5cb"), r"a\b");
+        assert_eq!(unescape_ssid(r"\x"), r"\x");
+        assert_eq!(unescape_ssid(r"\xzz"), r"\xzz");
+    }
+
+    #[test]
+    fn converts_frequencies_to_channels() {
+        assert_eq!(channel(2412), 1);
+        assert_eq!(channel(2472), 13);
+        assert_eq!(channel(2484), 14);
+        assert_eq!(channel(5180), 36);
+        assert_eq!(channel(5825), 165);
+        assert_eq!(channel(5955), 1);
+        assert_eq!(channel(900), 0);
+    }
+
+    #[test]
+    fn limits_results() {
+        let output: String = (0..MAX_RESULTS + 5)
+            .map(|i| format!("BSS 00:00:00:00:00:{i:02x}(on wlan0)\n\tsignal: -{i}.00 dBm\n"))
+            .collect();
+        let access_points = parse_scan(&output);
+        assert_eq!(access_points.len(), MAX_RESULTS);
+        assert_eq!(access_points[0].signal_dbm, 0);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{
+    payload::NotifySequence,
+    retry::{RetryPolicies, RetryPolicy},
+};
+use bluer::gatt::{
+    local::{
+        characteristic_control, Characteristic, CharacteristicControl, CharacteristicControlEvent,
+        CharacteristicNotify, CharacteristicNotifyMethod, CharacteristicRead, ReqError,
+    },
+    CharacteristicWriter,
+};
+use futures::{pin_mut, FutureExt, StreamExt};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::{process::Command, time};
+
+/// Wireless interface that is reported
+const INTERFACE: &str = "wlan0";
+
+const NOTIFY_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+struct Link {
+    ssid: String,
+    signal_dbm: i8,
+}
+
+/// Parses the output of `iw dev <iface> link`, `None` while not connected.
+fn parse_link(output: &str) -> Option<Link> {
+    if !output.starts_with("Connected to ") {
+        return None;
+    }
+    let mut link = Link {
+        ssid: String::new(),
+        signal_dbm: i8::MIN,
+    };
+    for line in output.lines().map(str::trim) {
+        if let Some(ssid) = line.strip_prefix("SSID: ") {
+            link.ssid = ssid.to_string();
+        } else if let Some(signal) = line.strip_prefix("signal: ") {
+            let dbm: f32 = signal.trim_end_matches(" dBm").parse().unwrap_or(f32::MIN);
+            link.signal_dbm = dbm.clamp(i8::MIN as f32, i8::MAX as f32) as i8;
+        }
+    }
+    Some(link)
+}
+
+/// Current link encoded as CBOR, `null` while not connected.
+async fn status() -> std::io::Result<Vec<u8>> {
+    let output = Command::new("iw")
+        .args(["dev", INTERFACE, "link"])
+        .output()
+        .await?;
+    let link = parse_link(&String::from_utf8_lossy(&output.stdout));
+    let mut payload = Vec::new();
+    ciborium::into_writer(&link, &mut payload).map_err(std::io::Error::other)?;
+    Ok(payload)
+}
+
+async fn serve(control: CharacteristicControl, retry: RetryPolicy) {
+    let mut writer_opt: Option<CharacteristicWriter> = None;
+    let mut sequence = NotifySequence::new("Wi-Fi status");
+    let mut interval = time::interval(NOTIFY_INTERVAL);
+    pin_mut!(control);
+
+    loop {
+        tokio::select! {
+            evt = control.next() => {
+                match evt {
+                    Some(CharacteristicControlEvent::Notify(notifier)) => {
+                        if !crate::security::admits(&notifier).await {
+                            continue;
+                        }
+                        println!("Accepting Wi-Fi status notify request with MTU {}", notifier.mtu());
+                        crate::peers::observe(&notifier, "Wi-Fi status");
+                        writer_opt = Some(notifier);
+                    },
+                    None => break,
+                    _ => {}
+                }
+            },
+            _ = interval.tick() => {
+                if let Some(writer) = &mut writer_opt {
+                    match status().await {
+                        Ok(payload) => {
+                            if retry.notify(writer, &sequence.stamp(&payload)).await.is_err() {
+                                writer_opt = None;
+                            }
+                        }
+                        Err(err) => eprintln!("Reading Wi-Fi status failed: {err}"),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the `WIFI_STATUS` characteristic.
+///
+/// Reads and notifications carry the SSID and signal strength in dBm of the
+/// current `wlan0` connection as CBOR, or `null` while not connected.
+pub fn characteristic(retry: &RetryPolicies) -> Characteristic {
+    let (control, control_handle) = characteristic_control();
+    crate::tasks::spawn("wifi-status", serve(control, retry.get("wifi_status")));
+
+    Characteristic {
+        uuid: crate::WIFI_STATUS,
+        read: Some(CharacteristicRead {
+            read: true,
+            fun: Box::new(|_req| {
+                async move {
+                    status().await.map_err(|err| {
+                        eprintln!("Reading Wi-Fi status failed: {err}");
+                        ReqError::Failed
+                    })
+                }
+                .boxed()
+            }),
+            ..Default::default()
+        }),
+        notify: Some(CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Io,
+            ..Default::default()
+        }),
+        control_handle,
+        ..Default::default()
+    }
+}